@@ -1,13 +1,31 @@
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::result::Result as StdResult;
 
-use clap::{ArgAction, Parser};
+use clap::{ArgAction, Parser, Subcommand};
 use env_logger::Builder as LoggerBuilder;
-use log::{LevelFilter as LogLevelFilter, info};
+use indexmap::IndexMap;
+use log::{LevelFilter as LogLevelFilter, info, warn};
 
-use crate::output::WriteMode;
-use crate::templates::Loader;
-use crate::{Result, config, render, schemes};
+use crate::output::table::{self, Format as TableFormat};
+use crate::output::{WriteMode, commit};
+use crate::render::Selection;
+use crate::schema::{self, Kind as SchemaKind};
+use crate::schemes::extract::Options as ExtractOptions;
+use crate::schemes::format::Mode as FormatMode;
+use crate::schemes::generate::Seeds;
+use crate::schemes::report::{self, Report};
+use crate::schemes::{
+    Appearance, Color, Error as SchemeError, Name as SchemeName, Namer, derive,
+    extract, generate, scaffold,
+};
+use crate::templates::{Loader, analysis, builtin, sources};
+use crate::{Result, config, lsp, render, schemes, watch};
+
+/// Seeds of the scheme `they init` starts a project with.
+const STARTER_BG: &str = "#1e1b26";
+const STARTER_FG: &str = "#e8e1ef";
+const STARTER_ACCENTS: [&str; 3] = ["#eaa5a7", "#a5c9ea", "#b9e0a5"];
 
 // TODO: better documentation
 // TODO: add `prune` flag
@@ -15,29 +33,242 @@ use crate::{Result, config, render, schemes};
 #[derive(Debug, Clone, Parser)]
 #[command(name = "they", version, about, long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Output more info per invocation (-v, -vv, -vvv)
-    #[arg(short, long, action = ArgAction::Count)]
+    #[arg(short, long, action = ArgAction::Count, global = true)]
     verbose: u8,
 
     /// Silence all output except errors
-    #[arg(short, long, conflicts_with = "verbose")]
+    #[arg(short, long, conflicts_with = "verbose", global = true)]
     quiet: bool,
 
     /// Don't overwrite existing files
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     keep: bool,
 
     /// Delete current contents of output directory before rendering
-    #[arg(short, long, conflicts_with = "keep")]
+    #[arg(short, long, conflicts_with = "keep", global = true)]
     clean: bool,
 
     /// Overwrite all existing files, even user-modified ones
-    #[arg(short, long, conflicts_with = "keep")]
+    #[arg(short, long, conflicts_with = "keep", global = true)]
     force: bool,
 
     /// Preview changes without writing them to disk
     #[arg(long, alias = "dry")]
     dry_run: bool,
+
+    /// Only render schemes whose name matches GLOB (repeatable)
+    #[arg(
+        id = "scheme_globs",
+        long = "scheme",
+        value_name = "GLOB",
+        conflicts_with = "clean",
+        global = true
+    )]
+    schemes: Vec<String>,
+
+    /// Only render templates whose path matches GLOB, e.g. `ports/fish/*`
+    /// (repeatable)
+    #[arg(
+        id = "template_globs",
+        long = "template",
+        value_name = "GLOB",
+        conflicts_with = "clean",
+        global = true
+    )]
+    templates: Vec<String>,
+
+    /// Only render templates of port NAME, in `ports/NAME/` or
+    /// `assets/NAME/` (repeatable)
+    #[arg(
+        id = "port_names",
+        long = "port",
+        value_name = "NAME",
+        conflicts_with = "clean",
+        global = true
+    )]
+    ports: Vec<String>,
+
+    /// Commit rendered files in the git repos they're in, as configured in
+    /// `[commit]`
+    #[arg(long, conflicts_with = "dry_run")]
+    commit: bool,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum Command {
+    /// Suggest swatch names for colors from the built-in color dictionary
+    Name {
+        /// Hex colors to name (e.g. `#eaa5a7`)
+        #[arg(required = true)]
+        colors: Vec<String>,
+    },
+
+    /// Rewrite scheme files in canonical order, keeping comments
+    Fmt {
+        /// Scheme files to format [default: every scheme in the project]
+        paths: Vec<PathBuf>,
+
+        /// Don't write anything, but fail if any scheme isn't formatted
+        #[arg(long)]
+        check: bool,
+
+        /// Add commented-out stubs for every unset optional role
+        #[arg(long)]
+        stubs: bool,
+    },
+
+    /// Show which roles each scheme sets, aliases or inherits, and how its
+    /// palette is used
+    Report {
+        /// Schemes to report on [default: every scheme in the project]
+        schemes: Vec<String>,
+
+        /// Output format
+        #[arg(short = 'F', long, value_enum, default_value_t)]
+        format: TableFormat,
+    },
+
+    /// Show which roles each template reads, and any unknown references
+    Analyze {
+        /// Output format
+        #[arg(short = 'F', long, value_enum, default_value_t)]
+        format: TableFormat,
+    },
+
+    /// Scaffold new project files
+    New {
+        #[command(subcommand)]
+        kind: NewKind,
+    },
+
+    /// Derive new schemes from existing ones
+    Derive {
+        #[command(subcommand)]
+        kind: DeriveKind,
+    },
+
+    /// Generate a complete scheme from a background, a foreground and a few
+    /// accent colors
+    Generate {
+        /// Name of the scheme, also used as its file name
+        name: String,
+
+        /// Background color
+        #[arg(long, value_name = "HEX")]
+        bg: String,
+
+        /// Foreground color
+        #[arg(long, value_name = "HEX")]
+        fg: String,
+
+        /// Accent color to build the palette around (repeatable)
+        #[arg(short, long = "accent", value_name = "HEX")]
+        accents: Vec<String>,
+
+        /// Seed for generated colors, to reproduce a scheme
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+    },
+
+    /// Print a JSON Schema, for completion and validation in editors
+    Schema {
+        #[arg(value_enum)]
+        kind: SchemaKind,
+    },
+
+    /// Generate a scheme from the colors of a PNG or JPEG image
+    Extract {
+        /// Image to take colors from
+        image: PathBuf,
+
+        /// Name of the scheme [default: the image's file name]
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Number of color clusters to find in the image
+        #[arg(long, default_value_t = 8)]
+        clusters: usize,
+
+        /// Minimum contrast ratio of the foreground and accents against the
+        /// background
+        #[arg(long, default_value_t = 4.5)]
+        min_contrast: f64,
+    },
+
+    /// Run a language server for scheme files over stdio
+    Lsp,
+
+    /// Render, then re-render whatever schemes, templates or `theymer.toml`
+    /// changes affect until interrupted
+    Watch {
+        /// Preview changes without writing them to disk
+        #[arg(long, alias = "dry")]
+        dry_run: bool,
+    },
+
+    /// Scaffold `theymer.toml`, a starter scheme and an empty templates
+    /// directory in the current directory
+    Init {
+        /// Name of the starter scheme
+        #[arg(short, long, default_value = "starter")]
+        name: String,
+    },
+
+    /// List or eject the templates bundled with theymer
+    Templates {
+        #[command(subcommand)]
+        kind: TemplatesKind,
+    },
+
+    /// Fetch git template sources and lock them to the latest commit of
+    /// their ref in `theymer.lock`
+    Update,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum NewKind {
+    /// Create a scheme listing every role
+    Scheme {
+        /// Name of the scheme, also used as its file name
+        name: String,
+
+        /// Seed the palette with a color, named (`name=#hex`) or named from
+        /// the color dictionary (`#hex`)
+        #[arg(short = 'C', long = "color", value_name = "[NAME=]HEX")]
+        colors: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum TemplatesKind {
+    /// List the built-in ports and their templates
+    List,
+
+    /// Copy built-in ports into the templates directory, where they override
+    /// the built-in ones
+    Eject {
+        /// Ports to eject, like `fish` or `circles`
+        #[arg(required = true)]
+        ports: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum DeriveKind {
+    /// Create a light companion for a dark scheme, as a starting point for
+    /// hand tuning
+    Light {
+        /// Scheme to derive from
+        scheme: String,
+
+        /// Name of the new scheme [default: `<scheme>-light`]
+        #[arg(short, long)]
+        name: Option<String>,
+    },
 }
 
 impl Args {
@@ -51,6 +282,10 @@ impl Args {
             WriteMode::Smart
         }
     }
+
+    fn selection(&self) -> Result<Selection> {
+        Ok(Selection::new(&self.schemes, &self.templates, &self.ports)?)
+    }
 }
 
 fn init_logger(verbosity: u8, quiet: bool) {
@@ -68,15 +303,352 @@ fn init_logger(verbosity: u8, quiet: bool) {
     LoggerBuilder::new().filter_level(level).init();
 }
 
+fn name(colors: &[String]) -> Result<()> {
+    let mut namer = Namer::new();
+
+    for hex in colors {
+        let swatch = namer.name(Color::parse(hex)?)?;
+
+        println!(
+            "{} = \"{}\"",
+            schemes::format::key(swatch.name.as_str()),
+            swatch.hex()
+        );
+    }
+
+    Ok(())
+}
+
+fn fmt(paths: &[PathBuf], mode: FormatMode, stubs: bool) -> Result<()> {
+    let paths = if paths.is_empty() {
+        let config = config::load()?;
+
+        schemes::find(&config.dirs.schemes)
+    } else {
+        paths.to_vec()
+    };
+
+    let mut unformatted = Vec::new();
+
+    for path in &paths {
+        if schemes::format::file(path, stubs, mode)?
+            && mode == FormatMode::Check
+        {
+            unformatted.push(path.display().to_string());
+        }
+    }
+
+    if unformatted.is_empty() {
+        Ok(())
+    } else {
+        Err(SchemeError::Unformatted { paths: unformatted }.into())
+    }
+}
+
+fn report(names: &[String], format: TableFormat) -> Result<()> {
+    let config = config::load()?;
+    let mut schemes = schemes::load_all(&config.dirs.schemes)?;
+
+    let reports = if names.is_empty() {
+        schemes.values().map(Report::new).collect()
+    } else {
+        names
+            .iter()
+            .map(|name| {
+                schemes.shift_remove(name).map_or_else(
+                    || Err(SchemeError::NotFound { name: name.clone() }),
+                    |scheme| Ok(Report::new(&scheme)),
+                )
+            })
+            .collect::<StdResult<Vec<_>, _>>()?
+    };
+
+    print!("{}", report::render(&reports, format)?);
+
+    Ok(())
+}
+
+fn analyze(format: TableFormat) -> Result<()> {
+    let config = config::load()?;
+    let schemes = schemes::load_all(&config.dirs.schemes)?;
+    let templates =
+        Loader::init(&config, &schemes::features(schemes.values()))?;
+
+    print!("{}", analysis::render(&templates.usage, format)?);
+
+    Ok(())
+}
+
+fn new_scheme(name: &str, colors: &[String], force: bool) -> Result<()> {
+    let config = config::load()?;
+
+    let name = SchemeName::parse(name)?;
+    let path = Path::new(&config.dirs.schemes).join(format!("{name}.toml"));
+    let path_str = path.display().to_string();
+
+    if path.exists() && !force {
+        return Err(SchemeError::Exists { path: path_str }.into());
+    }
+
+    let palette = scaffold::palette(colors, &path_str)?;
+    let content = scaffold::scheme(&palette, &IndexMap::new(), &path_str)?;
+
+    write_scheme(&config.dirs.schemes, &path, &content)
+}
+
+fn derive_light(scheme: &str, name: Option<&str>, force: bool) -> Result<()> {
+    let config = config::load()?;
+    let mut schemes = schemes::load_all(&config.dirs.schemes)?;
+
+    let source =
+        schemes
+            .shift_remove(scheme)
+            .ok_or_else(|| SchemeError::NotFound {
+                name: scheme.to_owned(),
+            })?;
+
+    let name = SchemeName::parse(
+        &name.map_or_else(|| format!("{scheme}-light"), str::to_owned),
+    )?;
+    let path = Path::new(&config.dirs.schemes).join(format!("{name}.toml"));
+    let path_str = path.display().to_string();
+
+    if path.exists() && !force {
+        return Err(SchemeError::Exists { path: path_str }.into());
+    }
+
+    if source.appearance == Appearance::Light {
+        warn!("scheme `{scheme}` is already light");
+    }
+
+    let content = derive::light(&source, &path_str)?;
+
+    write_scheme(&config.dirs.schemes, &path, &content)
+}
+
+fn generate(name: &str, seeds: &Seeds, force: bool) -> Result<()> {
+    let config = config::load()?;
+
+    let name = SchemeName::parse(name)?;
+    let path = Path::new(&config.dirs.schemes).join(format!("{name}.toml"));
+    let path_str = path.display().to_string();
+
+    if path.exists() && !force {
+        return Err(SchemeError::Exists { path: path_str }.into());
+    }
+
+    let generated = generate::generate(seeds)?;
+    let content =
+        scaffold::scheme(&generated.palette, &generated.roles, &path_str)?;
+
+    write_scheme(&config.dirs.schemes, &path, &content)
+}
+
+fn schema(kind: SchemaKind) -> Result<()> {
+    // custom roles are part of the scheme schema
+    if kind == SchemaKind::Scheme {
+        config::load()?;
+    }
+
+    println!("{}", table::json(&schema::generate(kind))?);
+
+    Ok(())
+}
+
+fn lsp() -> Result<()> {
+    // custom roles are only known inside a project
+    if let Err(err) = config::load() {
+        warn!("custom roles are unavailable: {err}");
+    }
+
+    lsp::run()
+}
+
+fn init(name: &str, force: bool) -> Result<()> {
+    config::init(force)?;
+    info!("created `{}`", config::FILENAME);
+
+    let seeds = Seeds {
+        bg: Color::parse(STARTER_BG)?,
+        fg: Color::parse(STARTER_FG)?,
+        accents: STARTER_ACCENTS
+            .iter()
+            .map(|accent| Color::parse(accent))
+            .collect::<Result<_>>()?,
+        seed: 0,
+    };
+
+    generate(name, &seeds, force)
+}
+
+fn list_templates() {
+    let ejected = config::load()
+        .ok()
+        .map(|config| PathBuf::from(config.dirs.templates));
+
+    for port in builtin::ports() {
+        println!("{port}");
+
+        for (name, _) in builtin::templates(port) {
+            let is_ejected =
+                ejected.as_ref().is_some_and(|dir| dir.join(name).exists());
+
+            if is_ejected {
+                println!("  {name} (ejected)");
+            } else {
+                println!("  {name}");
+            }
+        }
+    }
+}
+
+fn eject(ports: &[String], force: bool) -> Result<()> {
+    let config = config::load()?;
+
+    for port in ports {
+        for path in
+            builtin::eject(port, Path::new(&config.dirs.templates), force)?
+        {
+            info!("ejected `{}`", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+fn update() -> Result<()> {
+    let config = config::load()?;
+
+    sources::layers(&config, true)?;
+
+    Ok(())
+}
+
+fn write_scheme(dir: &str, path: &Path, content: &str) -> Result<()> {
+    let path_str = path.display().to_string();
+
+    fs::create_dir_all(dir).map_err(|src| SchemeError::Writing {
+        path: path_str.clone(),
+        src,
+    })?;
+    fs::write(path, content).map_err(|src| SchemeError::Writing {
+        path: path_str.clone(),
+        src,
+    })?;
+
+    info!("created scheme `{path_str}`");
+
+    Ok(())
+}
+
 pub fn run() -> Result<()> {
     let cli = Args::parse();
 
     init_logger(cli.verbose, cli.quiet);
 
+    match &cli.command {
+        Some(Command::Name { colors }) => name(colors),
+        Some(Command::Fmt {
+            paths,
+            check,
+            stubs,
+        }) => {
+            let mode = if *check {
+                FormatMode::Check
+            } else {
+                FormatMode::Write
+            };
+
+            fmt(paths, mode, *stubs)
+        }
+        Some(Command::Report { schemes, format }) => report(schemes, *format),
+        Some(Command::Analyze { format }) => analyze(*format),
+        Some(Command::New {
+            kind: NewKind::Scheme { name, colors },
+        }) => new_scheme(name, colors, cli.force),
+        Some(Command::Derive {
+            kind: DeriveKind::Light { scheme, name },
+        }) => derive_light(scheme, name.as_deref(), cli.force),
+        Some(Command::Generate {
+            name,
+            bg,
+            fg,
+            accents,
+            seed,
+        }) => {
+            let seeds = Seeds {
+                bg: Color::parse(bg)?,
+                fg: Color::parse(fg)?,
+                accents: accents
+                    .iter()
+                    .map(|accent| Color::parse(accent))
+                    .collect::<Result<_>>()?,
+                seed: *seed,
+            };
+
+            generate(name, &seeds, cli.force)
+        }
+        Some(Command::Schema { kind }) => schema(*kind),
+        Some(Command::Extract {
+            image,
+            name,
+            clusters,
+            min_contrast,
+        }) => {
+            let options = ExtractOptions {
+                clusters: *clusters,
+                min_contrast: *min_contrast,
+            };
+            let name = name.clone().map_or_else(
+                || {
+                    image
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().into_owned())
+                        .ok_or_else(|| SchemeError::InvalidStructure {
+                            path: image.display().to_string(),
+                            reason: "image has no file name".to_owned(),
+                        })
+                },
+                Ok,
+            )?;
+
+            generate(&name, &extract::seeds(image, options)?, cli.force)
+        }
+        Some(Command::Lsp) => lsp(),
+        Some(Command::Init { name }) => init(name, cli.force),
+        Some(Command::Templates {
+            kind: TemplatesKind::List,
+        }) => {
+            list_templates();
+
+            Ok(())
+        }
+        Some(Command::Templates {
+            kind: TemplatesKind::Eject { ports },
+        }) => eject(ports, cli.force),
+        Some(Command::Update) => update(),
+        Some(Command::Watch { dry_run }) => {
+            if cli.commit {
+                warn!("`--commit` is ignored while watching");
+            }
+
+            watch::run(
+                cli.write_mode(),
+                cli.dry_run || *dry_run,
+                cli.selection()?,
+            )
+        }
+        None => render(&cli),
+    }
+}
+
+fn render(cli: &Args) -> Result<()> {
+    let selection = cli.selection()?;
     let config = config::load()?;
 
-    let templates = Loader::init(&config)?;
     let schemes = schemes::load_all(&config.dirs.schemes)?;
+    let templates =
+        Loader::init(&config, &schemes::features(schemes.values()))?;
 
     if cli.clean {
         let render_dir = Path::new(&config.dirs.render);
@@ -90,7 +662,63 @@ pub fn run() -> Result<()> {
         }
     }
 
-    render::all(&templates, &schemes, &config, cli.write_mode(), cli.dry_run)?;
+    if !selection.is_everything()
+        && !schemes.keys().any(|scheme| {
+            templates
+                .env
+                .templates()
+                .any(|(template, _)| selection.includes(scheme, template))
+        })
+    {
+        warn!("no scheme and template match the selection");
+    }
+
+    let written = render::selected(
+        &templates,
+        &schemes,
+        &config,
+        cli.write_mode(),
+        cli.dry_run,
+        |scheme, template| selection.includes(scheme, template),
+    )?;
+
+    if cli.commit {
+        commit::all(&written, &config.commit)?;
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory as _;
+
+    use super::*;
+
+    #[test]
+    fn args_are_consistent() {
+        Args::command().debug_assert();
+    }
+
+    #[test]
+    fn flags_apply_where_they_take_effect() {
+        let args = Args::try_parse_from([
+            "they",
+            "watch",
+            "--scheme",
+            "cutie*",
+            "--dry-run",
+            "--force",
+        ])
+        .expect("valid args");
+        assert_eq!(args.schemes, ["cutie*"]);
+        assert!(args.force);
+        assert!(matches!(
+            args.command,
+            Some(Command::Watch { dry_run: true })
+        ));
+
+        Args::try_parse_from(["they", "init", "--dry-run"])
+            .expect_err("init always writes");
+    }
+}
@@ -1,13 +1,46 @@
 use std::borrow::Cow;
 use std::path::{Path, PathBuf};
 use std::result::Result as StdResult;
-use std::{env, fs, io};
+use std::{env, fs, io, mem};
 
 use indexmap::IndexMap;
 use log::debug;
 use serde::Deserialize;
 
-const FILENAME: &str = "theymer.toml";
+use crate::schemes::{RoleError, roles};
+
+pub(crate) const FILENAME: &str = "theymer.toml";
+/// Written by `they init`, with every option commented out.
+const STARTER: &str = r#"# dirs are relative to this file
+# [dirs]
+# schemes = "schemes"
+# templates = "templates"
+# render = "render"
+
+# render the templates bundled with theymer, unless `templates/` or a template
+# source has one with the same path. `they templates eject <port>` copies one
+# into `templates/` to customize it
+# builtin_templates = true
+
+# commits of rendered files in their git repos, made with `they --commit`
+# [commit]
+# message = "Update {ports} for {schemes}"
+# tag = "{schemes}-{version}"
+# push = "origin"
+
+# upstream urls of rendered files, for `special.upstream_file` and friends
+# [upstream.repos."render/cutiepro"]
+# url = "https://github.com/owner/cutiepro"
+# branch = "main"
+# [upstream.schemes.cutiepro]
+# remote = "upstream"
+
+# templates shared between projects, layered under `templates/`
+# [[template_sources]]
+# git = "https://github.com/owner/templates"
+# ref = "main"
+# dir = "templates"
+"#;
 
 type Result<T> = StdResult<T, Error>;
 
@@ -20,6 +53,12 @@ pub(crate) enum Error {
     #[error("failed to read `{FILENAME}`: {src}")]
     Reading { src: io::Error },
 
+    #[error("`{path}` already exists")]
+    Exists { path: String },
+
+    #[error("failed to write `{path}`: {src}")]
+    Writing { path: String, src: io::Error },
+
     #[error("failed to parse `{FILENAME}`: {src}")]
     Parsing { src: Box<toml::de::Error> },
 
@@ -29,6 +68,9 @@ pub(crate) enum Error {
         src: shellexpand::LookupError<env::VarError>,
     },
 
+    #[error("invalid custom roles: {src}")]
+    Roles { src: RoleError },
+
     #[error("failed to move from `{cwd}` to project root `{root}`: {src}")]
     ChangingDir {
         cwd: String,
@@ -45,8 +87,25 @@ pub struct Config {
     pub strip_directives: Vec<Vec<String>>,
     pub dirs: Dirs,
 
+    /// Project-defined roles, see [`CustomRole`].
+    pub roles: IndexMap<String, CustomRole>,
+
     #[serde(rename(serialize = "provider"))]
     pub providers: Vec<Provider>,
+
+    /// Shared templates layered under the project's own, see
+    /// [`TemplateSource`].
+    pub template_sources: Vec<TemplateSource>,
+
+    /// Whether the templates bundled with theymer render under the
+    /// project's own and its template sources.
+    pub builtin_templates: bool,
+
+    /// How `--commit` commits rendered files in their git repos.
+    pub commit: Commit,
+
+    /// Overrides for the upstream urls of rendered files.
+    pub upstream: Upstreams,
 }
 
 impl Default for Config {
@@ -57,11 +116,137 @@ impl Default for Config {
             strip_directives: vec![vec!["#:tombi".to_owned()]],
 
             dirs: Dirs::default(),
+            roles: IndexMap::new(),
             providers: default_providers(),
+            template_sources: Vec::new(),
+            builtin_templates: true,
+            commit: Commit::default(),
+            upstream: Upstreams::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Flattens the `[roles]` table into full role names and their bases.
+    #[must_use]
+    pub(crate) fn custom_roles(&self) -> IndexMap<String, String> {
+        self.roles
+            .iter()
+            .flat_map(|(name, role)| match role {
+                CustomRole::Role(base) => vec![(name.clone(), base.clone())],
+                CustomRole::Group(roles) => roles
+                    .iter()
+                    .map(|(key, base)| (format!("{name}.{key}"), base.clone()))
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+/// A custom role mapped to the role it falls back to (`scroll = "guide"`), or
+/// a group of them (`[roles.vcs]`). Groups may extend built-in ones.
+#[non_exhaustive]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum CustomRole {
+    Role(String),
+    Group(IndexMap<String, String>),
+}
+
+/// A directory of templates shared between projects, either a local `path`
+/// or a `git` repository checked out at `ref` (its default branch if unset).
+/// `dir` is where the templates are inside it.
+///
+/// Earlier sources take precedence over later ones, and the project's own
+/// templates over all of them.
+#[non_exhaustive]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum TemplateSource {
+    Local {
+        path: String,
+        dir: Option<String>,
+    },
+    Git {
+        git: String,
+        #[serde(rename = "ref")]
+        reference: Option<String>,
+        dir: Option<String>,
+    },
+}
+
+/// Commits of rendered files, made per git repo after rendering with
+/// `--commit`. `message` and `tag` may use `{schemes}`, `{ports}` and
+/// `{version}`, theymer's version.
+#[non_exhaustive]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(default)]
+pub struct Commit {
+    pub message: String,
+    /// Tags each commit if set.
+    pub tag: Option<String>,
+    /// Remote to push commits and tags to, if any.
+    pub push: Option<String>,
+}
+
+impl Default for Commit {
+    fn default() -> Self {
+        Self {
+            message: "Update {ports} for {schemes}\n\nRendered with theymer \
+                      {version}."
+                .to_owned(),
+            tag: None,
+            push: None,
         }
     }
 }
 
+/// Where upstream urls of rendered files point, overriding what's detected
+/// from their git repos. Scheme overrides take precedence over repo ones,
+/// and both over a provider's `branch`.
+#[non_exhaustive]
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(default)]
+pub struct Upstreams {
+    /// By directory, relative to the project root, like `render/cutiepro`.
+    pub repos: IndexMap<String, RepoUpstream>,
+    /// By scheme name.
+    pub schemes: IndexMap<String, SchemeUpstream>,
+}
+
+/// Overrides for files rendered under a directory. With `url`, the
+/// directory doesn't need to be a git repo at all.
+#[non_exhaustive]
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RepoUpstream {
+    pub url: Option<String>,
+    pub remote: Option<String>,
+    pub branch: Option<String>,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SchemeUpstream {
+    pub remote: Option<String>,
+    pub branch: Option<String>,
+}
+
+impl Upstreams {
+    /// The override for the innermost directory containing `path`.
+    #[must_use]
+    pub(crate) fn repo(&self, path: &Path) -> Option<(&Path, &RepoUpstream)> {
+        self.repos
+            .iter()
+            .map(|(dir, repo)| (Path::new(dir), repo))
+            .filter(|(dir, _)| path.starts_with(dir))
+            .max_by_key(|(dir, _)| dir.components().count())
+    }
+}
+
 #[non_exhaustive]
 #[derive(Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -139,9 +324,45 @@ pub(crate) fn load() -> Result<Config> {
 
     config.providers = merge_providers_with_defaults(&config.providers);
 
+    config.upstream.repos = mem::take(&mut config.upstream.repos)
+        .into_iter()
+        .map(|(dir, repo)| Ok((expand_and_resolve(&dir, project_root)?, repo)))
+        .collect::<Result<_>>()?;
+
+    for source in &mut config.template_sources {
+        if let TemplateSource::Local { path, .. } = source {
+            *path = expand_and_resolve(path, project_root)?;
+        }
+    }
+
+    roles::register(&config.custom_roles())
+        .map_err(|src| Error::Roles { src })?;
+
     Ok(config)
 }
 
+/// Scaffolds a project in the current directory: `theymer.toml` and an empty
+/// templates directory.
+pub(crate) fn init(force: bool) -> Result<()> {
+    let writing = |path: &str| {
+        let path = path.to_owned();
+        move |src| Error::Writing { path, src }
+    };
+
+    if Path::new(FILENAME).exists() && !force {
+        return Err(Error::Exists {
+            path: FILENAME.to_owned(),
+        });
+    }
+
+    fs::write(FILENAME, STARTER).map_err(writing(FILENAME))?;
+
+    let templates = Dirs::default().templates;
+    fs::create_dir_all(&templates).map_err(writing(&templates))?;
+
+    Ok(())
+}
+
 fn default_providers() -> Vec<Provider> {
     vec![
         Provider {
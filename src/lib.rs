@@ -24,19 +24,26 @@ use std::io;
 use std::result::Result as StdResult;
 
 mod extensions;
+mod lsp;
 mod manifest;
 mod output;
 mod render;
+mod schema;
 pub(crate) mod schemes;
 mod templates;
+mod watch;
 
 use self::config::Error as ConfigError;
+use self::lsp::Error as LspError;
 use self::manifest::Error as ManifestError;
 pub(crate) use self::manifest::{Entry as ManifestEntry, Manifest};
-use self::output::UpstreamError;
+use self::output::{CommitError, UpstreamError};
+use self::render::SelectionError;
 pub(crate) use self::schemes::Scheme;
 use self::schemes::{Error as SchemeError, NameError, RoleError, SwatchError};
-use self::templates::{DirectiveError, ProviderError};
+use self::templates::{
+    BuiltinError, DirectiveError, ProviderError, SourceError,
+};
 
 pub mod cli;
 pub mod config;
@@ -78,12 +85,27 @@ pub enum Error {
     #[error("git provider error: {0}")]
     Provider(#[from] ProviderError),
 
+    #[error("template source error: {0}")]
+    Source(#[from] SourceError),
+
+    #[error("built-in template error: {0}")]
+    Builtin(#[from] BuiltinError),
+
     #[error("error rendering: {0}")]
     Rendering(#[source] anyhow::Error),
 
+    #[error("invalid selection: {0}")]
+    Selection(#[from] SelectionError),
+
     #[error("upstream error: {0}")]
     Upstream(#[from] UpstreamError),
 
+    #[error("commit error: {0}")]
+    Commit(#[from] CommitError),
+
+    #[error("language server error: {0}")]
+    Lsp(#[from] LspError),
+
     #[error("file system error: {0}")]
     Io(#[from] io::Error),
 
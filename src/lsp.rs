@@ -0,0 +1,357 @@
+//! A language server for scheme files, over stdio.
+
+use std::collections::HashMap;
+use std::result::Result as StdResult;
+
+use log::{debug, info};
+use lsp_server::{
+    Connection, ErrorCode, Message, Notification, ProtocolError, Request,
+    Response,
+};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as _, PublishDiagnostics,
+};
+use lsp_types::request::{
+    ColorPresentationRequest, Completion, DocumentColor, HoverRequest, Rename,
+    Request as LspRequest,
+};
+use lsp_types::{
+    ColorPresentationParams, ColorProviderCapability, CompletionOptions,
+    CompletionParams, CompletionResponse, Diagnostic,
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, DocumentColorParams, HoverParams,
+    HoverProviderCapability, OneOf, PublishDiagnosticsParams, RenameParams,
+    ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+
+use self::document::Document;
+use crate::schemes::{self, Scheme};
+
+mod document;
+mod handlers;
+
+#[non_exhaustive]
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum Error {
+    #[error("protocol error: {0}")]
+    Protocol(#[from] ProtocolError),
+
+    #[error("invalid message: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("client disconnected")]
+    Disconnected,
+}
+
+/// An open document and the scheme it loads as, if it does.
+struct Open {
+    document: Document,
+    scheme: Option<Scheme>,
+}
+
+/// Request parameters that name the document they're about.
+trait DocumentParams {
+    fn uri(&self) -> &Url;
+}
+
+struct Server {
+    connection: Connection,
+    open: HashMap<Url, Open>,
+}
+
+pub(crate) fn run() -> crate::Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities =
+        serde_json::to_value(capabilities()).map_err(Error::from)?;
+    connection.initialize(capabilities).map_err(Error::from)?;
+
+    info!("language server started");
+
+    let mut server = Server {
+        connection,
+        open: HashMap::new(),
+    };
+    server.main_loop()?;
+
+    // the connection has to be dropped for the io threads to finish
+    drop(server);
+    io_threads.join()?;
+
+    Ok(())
+}
+
+fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(
+            TextDocumentSyncKind::FULL,
+        )),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(
+                ["$", "\"", "."].map(str::to_owned).to_vec(),
+            ),
+            ..CompletionOptions::default()
+        }),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        color_provider: Some(ColorProviderCapability::Simple(true)),
+        rename_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    }
+}
+
+impl Server {
+    fn main_loop(&mut self) -> StdResult<(), Error> {
+        let receiver = self.connection.receiver.clone();
+
+        for message in &receiver {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+
+                    let response = self.request(request);
+                    self.send(response.into())?;
+                }
+                Message::Notification(notification) => {
+                    self.notification(notification)?;
+                }
+                Message::Response(_) => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn request(&self, request: Request) -> Response {
+        match request.method.as_str() {
+            Completion::METHOD => {
+                self.respond::<Completion>(request, |open, params| {
+                    let position = params.text_document_position.position;
+
+                    Ok(Some(CompletionResponse::Array(handlers::completion(
+                        &open.document,
+                        position,
+                    ))))
+                })
+            }
+            HoverRequest::METHOD => {
+                self.respond::<HoverRequest>(request, |open, params| {
+                    Ok(handlers::hover(
+                        &open.document,
+                        open.scheme.as_ref(),
+                        params.text_document_position_params.position,
+                    ))
+                })
+            }
+            DocumentColor::METHOD => self
+                .respond::<DocumentColor>(request, |open, _| {
+                    Ok(handlers::colors(&open.document))
+                }),
+            ColorPresentationRequest::METHOD => self
+                .respond::<ColorPresentationRequest>(request, |_, params| {
+                    Ok(handlers::presentations(params.color, params.range))
+                }),
+            Rename::METHOD => {
+                self.respond::<Rename>(request, |open, params| {
+                    let position = params.text_document_position;
+
+                    handlers::rename(
+                        &open.document,
+                        &position.text_document.uri,
+                        position.position,
+                        &params.new_name,
+                    )
+                    .map_err(|err| err.to_string())
+                })
+            }
+            method => Response::new_err(
+                request.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("unsupported request `{method}`"),
+            ),
+        }
+    }
+
+    /// Runs `handler` on the open document a request is about.
+    fn respond<R: LspRequest>(
+        &self,
+        request: Request,
+        handler: impl FnOnce(&Open, R::Params) -> StdResult<R::Result, String>,
+    ) -> Response
+    where
+        R::Params: DocumentParams,
+    {
+        let id = request.id;
+
+        let params = match serde_json::from_value::<R::Params>(request.params) {
+            Ok(params) => params,
+            Err(err) => {
+                return Response::new_err(
+                    id,
+                    ErrorCode::InvalidParams as i32,
+                    err.to_string(),
+                );
+            }
+        };
+
+        let Some(open) = self.open.get(params.uri()) else {
+            return Response::new_err(
+                id,
+                ErrorCode::InvalidParams as i32,
+                format!("document `{}` isn't open", params.uri()),
+            );
+        };
+
+        match handler(open, params) {
+            Ok(result) => Response::new_ok(id, result),
+            Err(message) => {
+                Response::new_err(id, ErrorCode::InvalidParams as i32, message)
+            }
+        }
+    }
+
+    fn notification(
+        &mut self,
+        notification: Notification,
+    ) -> StdResult<(), Error> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+
+                self.update(
+                    params.text_document.uri,
+                    &params.text_document.text,
+                )
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: DidChangeTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+
+                // with full sync, the last change is the whole document
+                match params.content_changes.into_iter().last() {
+                    Some(change) => {
+                        self.update(params.text_document.uri, &change.text)
+                    }
+                    None => Ok(()),
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+
+                self.open.remove(&params.text_document.uri);
+                self.publish(params.text_document.uri, Vec::new())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Reloads a document and publishes its diagnostics.
+    fn update(&mut self, uri: Url, text: &str) -> StdResult<(), Error> {
+        let path = uri.to_file_path().map_or_else(
+            |()| uri.to_string(),
+            |path| path.display().to_string(),
+        );
+        let name = scheme_name(&uri);
+
+        let (scheme, diagnostics) = match schemes::parse(&name, text, &path) {
+            Ok(scheme) => (Some(scheme), Vec::new()),
+            Err(err) => {
+                debug!("`{path}` doesn't load: {err}");
+
+                (None, vec![handlers::diagnostic(&err, text)])
+            }
+        };
+
+        self.open.insert(uri.clone(), Open {
+            document: Document::parse(text),
+            scheme,
+        });
+
+        self.publish(uri, diagnostics)
+    }
+
+    fn publish(
+        &self,
+        uri: Url,
+        diagnostics: Vec<Diagnostic>,
+    ) -> StdResult<(), Error> {
+        let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+
+        self.send(
+            Notification::new(PublishDiagnostics::METHOD.to_owned(), params)
+                .into(),
+        )
+    }
+
+    fn send(&self, message: Message) -> StdResult<(), Error> {
+        self.connection
+            .sender
+            .send(message)
+            .map_err(|_err| Error::Disconnected)
+    }
+}
+
+impl DocumentParams for CompletionParams {
+    fn uri(&self) -> &Url {
+        &self.text_document_position.text_document.uri
+    }
+}
+
+impl DocumentParams for HoverParams {
+    fn uri(&self) -> &Url {
+        &self.text_document_position_params.text_document.uri
+    }
+}
+
+impl DocumentParams for DocumentColorParams {
+    fn uri(&self) -> &Url {
+        &self.text_document.uri
+    }
+}
+
+impl DocumentParams for ColorPresentationParams {
+    fn uri(&self) -> &Url {
+        &self.text_document.uri
+    }
+}
+
+impl DocumentParams for RenameParams {
+    fn uri(&self) -> &Url {
+        &self.text_document_position.text_document.uri
+    }
+}
+
+/// The name a scheme file gets from its file name, decoded from `uri`.
+fn scheme_name(uri: &Url) -> String {
+    let path = uri.to_file_path().unwrap_or_else(|()| {
+        percent_encoding::percent_decode_str(uri.path())
+            .decode_utf8_lossy()
+            .into_owned()
+            .into()
+    });
+
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scheme_names_are_decoded() {
+        for (uri, name) in [
+            ("file:///schemes/caf%C3%A9.toml", "café"),
+            ("file:///schemes/THEME%20Latte.toml", "THEME Latte"),
+            ("untitled:caf%C3%A9.toml", "café"),
+        ] {
+            let uri = Url::parse(uri).expect("valid uri");
+            assert_eq!(scheme_name(&uri), name);
+        }
+    }
+}
@@ -0,0 +1,413 @@
+//! A line-based view of scheme files, including ones that don't parse yet.
+
+use lsp_types::{Position, Range};
+
+/// The kind of table a line belongs to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Section {
+    Meta,
+    Palette,
+    /// `[roles]`, or a group of it like `[roles.syntax]`.
+    Roles(Option<String>),
+    /// `[extra]` or `[features]`.
+    Features,
+    Other,
+}
+
+impl Section {
+    fn parse(header: &str) -> Self {
+        let parts = header
+            .split('.')
+            .map(|part| part.trim().trim_matches('"'))
+            .collect::<Vec<_>>();
+
+        match parts.as_slice() {
+            ["meta", ..] => Self::Meta,
+            ["palette"] => Self::Palette,
+            ["roles"] => Self::Roles(None),
+            ["roles", group] => Self::Roles(Some((*group).to_owned())),
+            ["extra" | "features", ..] => Self::Features,
+            _ => Self::Other,
+        }
+    }
+
+    /// Whether string values in this section refer to swatches or roles.
+    #[must_use]
+    pub(crate) const fn has_references(&self) -> bool {
+        matches!(self, Self::Roles(_) | Self::Features)
+    }
+}
+
+/// A key, without quotes. Its range covers the quotes, if any.
+#[derive(Debug, Clone)]
+pub(crate) struct Key {
+    pub name: String,
+    pub range: Range,
+    pub section: Section,
+}
+
+impl Key {
+    /// The role this key sets, if it's in a roles table.
+    #[must_use]
+    pub(crate) fn role(&self) -> Option<String> {
+        match &self.section {
+            Section::Roles(None) => Some(self.name.clone()),
+            Section::Roles(Some(group)) => {
+                Some(format!("{group}.{}", self.name))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A string value. Its range only covers the content between the quotes.
+#[derive(Debug, Clone)]
+pub(crate) struct Value {
+    pub text: String,
+    pub range: Range,
+    pub section: Section,
+    /// The key the value is assigned to, which for multi-line arrays is on
+    /// an earlier line.
+    pub key: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Document {
+    keys: Vec<Key>,
+    values: Vec<Value>,
+    lines: Vec<String>,
+    /// Table headers and the line they're on.
+    sections: Vec<(u32, Section)>,
+}
+
+impl Document {
+    #[must_use]
+    pub(crate) fn parse(text: &str) -> Self {
+        let mut document = Self::default();
+        let mut section = Section::Other;
+        let mut key = None;
+        // open brackets of multi-line arrays, where `[` isn't a header
+        let mut depth = 0_usize;
+
+        for (number, line) in (0_u32..).zip(text.lines()) {
+            document.lines.push(line.to_owned());
+
+            let trimmed = line.trim_start();
+            if depth == 0 && trimmed.starts_with('[') {
+                let header = trimmed.trim_start_matches('[');
+                let header = header.split(']').next().unwrap_or_default();
+
+                section = if trimmed.starts_with("[[") {
+                    Section::Other
+                } else {
+                    Section::parse(header)
+                };
+                key = None;
+                document.sections.push((number, section.clone()));
+
+                continue;
+            }
+
+            let scanned = scan(line, depth == 0);
+
+            if let Some((name, start, end)) = scanned.key {
+                key = Some(name.clone());
+                document.keys.push(Key {
+                    name,
+                    range: range(number, line, start, end),
+                    section: section.clone(),
+                });
+            }
+
+            document.values.extend(scanned.values.into_iter().map(
+                |(text, start, end)| Value {
+                    text,
+                    range: range(number, line, start, end),
+                    section: section.clone(),
+                    key: key.clone(),
+                },
+            ));
+
+            depth = depth
+                .saturating_add(scanned.opened)
+                .saturating_sub(scanned.closed);
+        }
+
+        document
+    }
+
+    #[must_use]
+    pub(crate) fn keys(&self) -> &[Key] {
+        &self.keys
+    }
+
+    #[must_use]
+    pub(crate) fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    /// The section of the table `line` is in.
+    #[must_use]
+    pub(crate) fn section_at(&self, line: u32) -> Section {
+        self.sections
+            .iter()
+            .take_while(|&&(start, _)| start <= line)
+            .last()
+            .map_or(Section::Other, |(_, section)| section.clone())
+    }
+
+    #[must_use]
+    pub(crate) fn key_at(&self, position: Position) -> Option<&Key> {
+        self.keys.iter().find(|key| contains(key.range, position))
+    }
+
+    #[must_use]
+    pub(crate) fn value_at(&self, position: Position) -> Option<&Value> {
+        self.values
+            .iter()
+            .find(|value| contains(value.range, position))
+    }
+
+    /// Whether `position` is where a key goes, i.e. before any `=` on its
+    /// line.
+    #[must_use]
+    pub(crate) fn is_key_position(&self, position: Position) -> bool {
+        let Some(line) = usize::try_from(position.line)
+            .ok()
+            .and_then(|line| self.lines.get(line))
+        else {
+            return true;
+        };
+
+        let before = &line[..byte_offset(line, position.character)];
+
+        !before.contains('=') && !before.trim_start().starts_with('#')
+    }
+
+    /// The hex value of swatch `name`, as written in the palette.
+    #[must_use]
+    pub(crate) fn swatch_hex(&self, name: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|value| {
+                value.section == Section::Palette
+                    && value.key.as_deref() == Some(name)
+                    && value.text.starts_with('#')
+            })
+            .map(|value| value.text.as_str())
+    }
+}
+
+#[derive(Debug, Default)]
+struct Scanned {
+    key: Option<(String, usize, usize)>,
+    values: Vec<(String, usize, usize)>,
+    opened: usize,
+    closed: usize,
+}
+
+/// Finds the key and string values on a line, as byte offsets. Keys are only
+/// looked for when the line isn't inside a multi-line array.
+fn scan(line: &str, expect_key: bool) -> Scanned {
+    let mut scanned = Scanned::default();
+
+    let value_start = if expect_key {
+        let Some(eq) = find_unquoted(line, '=') else {
+            return scanned;
+        };
+        let raw = &line[..eq];
+        let start = raw.len() - raw.trim_start().len();
+        let end = raw.trim_end().len();
+
+        if start < end {
+            let name = raw[start..end].trim_matches(['"', '\'']).to_owned();
+            scanned.key = Some((name, start, end));
+        }
+
+        eq + 1
+    } else {
+        0
+    };
+
+    let mut chars = line[value_start..]
+        .char_indices()
+        .map(|(i, c)| (i + value_start, c));
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '#' => break,
+            '[' => scanned.opened += 1,
+            ']' => scanned.closed += 1,
+            '"' | '\'' => {
+                let start = i + 1;
+                let mut end = line.len();
+                let mut escaped = false;
+
+                for (j, next) in chars.by_ref() {
+                    if next == c && !escaped {
+                        end = j;
+                        break;
+                    }
+                    escaped = c == '"' && next == '\\' && !escaped;
+                }
+
+                scanned
+                    .values
+                    .push((line[start..end].to_owned(), start, end));
+            }
+            _ => {}
+        }
+    }
+
+    scanned
+}
+
+fn find_unquoted(line: &str, needle: char) -> Option<usize> {
+    let mut quote = None;
+
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '#') => return None,
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, _) if c == needle => return Some(i),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+fn range(line_number: u32, line: &str, start: usize, end: usize) -> Range {
+    Range::new(
+        Position::new(line_number, utf16_len(&line[..start])),
+        Position::new(line_number, utf16_len(&line[..end])),
+    )
+}
+
+/// Converts a byte offset into `text` into a position.
+#[must_use]
+pub(crate) fn position(text: &str, offset: usize) -> Position {
+    let before = &text[..text.floor_char_boundary(offset)];
+    let line = before.rsplit('\n').next().unwrap_or_default();
+
+    Position::new(
+        u32::try_from(before.matches('\n').count()).unwrap_or(u32::MAX),
+        utf16_len(line),
+    )
+}
+
+/// Positions count UTF-16 code units, like the LSP default.
+fn utf16_len(text: &str) -> u32 {
+    u32::try_from(text.encode_utf16().count()).unwrap_or(u32::MAX)
+}
+
+fn byte_offset(line: &str, character: u32) -> usize {
+    let mut units = 0;
+
+    for (i, c) in line.char_indices() {
+        if units >= character {
+            return i;
+        }
+        units += u32::try_from(c.len_utf16()).unwrap_or(1);
+    }
+
+    line.len()
+}
+
+/// Whether `range` contains `position`, including its end so completion
+/// works right after the last character.
+#[must_use]
+pub(crate) fn contains(range: Range, position: Position) -> bool {
+    range.start <= position && position <= range.end
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    const SCHEME: &str = indoc! {r##"
+        [palette]
+        cherry_blossom = "#eaa5a7"
+        "château" = { hex = "#181716", ascii = "chateau" }
+
+        [roles]
+        bg = "$château" # comment with "quotes"
+        fg = "$cherry_blossom"
+
+        [roles.syntax]
+        keyword = "accent"
+
+        [extra]
+        rainbow = [
+            "$cherry_blossom",
+            "fg",
+        ]
+    "##};
+
+    #[test]
+    fn keys_and_values_are_found_by_section() {
+        let document = Document::parse(SCHEME);
+
+        let names = document
+            .keys
+            .iter()
+            .map(|key| (key.name.as_str(), key.role()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(names, [
+            ("cherry_blossom", None),
+            ("château", None),
+            ("bg", Some("bg".to_owned())),
+            ("fg", Some("fg".to_owned())),
+            ("keyword", Some("syntax.keyword".to_owned())),
+            ("rainbow", None),
+        ]);
+
+        let rainbow = document
+            .values
+            .iter()
+            .filter(|value| value.key.as_deref() == Some("rainbow"))
+            .map(|value| value.text.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(rainbow, ["$cherry_blossom", "fg"]);
+        assert_eq!(document.swatch_hex("château"), Some("#181716"));
+        assert_eq!(document.section_at(13), Section::Features);
+    }
+
+    #[test]
+    fn positions_count_utf16() {
+        let document = Document::parse(SCHEME);
+
+        let bg = document
+            .values
+            .iter()
+            .find(|value| value.text == "$château")
+            .expect("bg value");
+
+        assert_eq!(
+            bg.range,
+            Range::new(Position::new(5, 6), Position::new(5, 14))
+        );
+        assert_eq!(
+            document
+                .value_at(Position::new(5, 10))
+                .map(|value| value.text.as_str()),
+            Some("$château")
+        );
+        assert_eq!(position("a\nbé\nc", 6), Position::new(2, 0));
+        assert_eq!(position("a\nbé", 5), Position::new(1, 2));
+    }
+
+    #[test]
+    fn key_positions_end_at_equals() {
+        let document = Document::parse(SCHEME);
+
+        assert!(document.is_key_position(Position::new(6, 1)));
+        assert!(!document.is_key_position(Position::new(6, 7)));
+        assert!(document.is_key_position(Position::new(99, 0)));
+    }
+}
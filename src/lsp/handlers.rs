@@ -0,0 +1,553 @@
+//! Answers to editor requests.
+
+use std::collections::HashMap;
+
+use lsp_types::{
+    Color as LspColor, ColorInformation, ColorPresentation, CompletionItem,
+    CompletionItemKind, CompletionTextEdit, Diagnostic, DiagnosticSeverity,
+    Documentation, Hover, HoverContents, MarkupContent, MarkupKind, Position,
+    Range, TextEdit, Url, WorkspaceEdit,
+};
+
+use super::document::{self, Document, Section};
+use crate::schema;
+use crate::schemes::format::key as toml_key;
+use crate::schemes::roles::{self, Group};
+use crate::schemes::{
+    Color, Error as SchemeError, RoleKind, RoleName, RoleValue, Scheme,
+    SwatchName,
+};
+
+/// Swatch and role references inside a value, or role keys at the start of
+/// a line in a roles table.
+#[must_use]
+pub(crate) fn completion(
+    document: &Document,
+    position: Position,
+) -> Vec<CompletionItem> {
+    if let Some(value) = document.value_at(position) {
+        if !value.section.has_references() {
+            return Vec::new();
+        }
+
+        return references(document, value.range);
+    }
+
+    match document.section_at(position.line) {
+        Section::Roles(group) if document.is_key_position(position) => {
+            role_keys(document, group.as_deref())
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn references(document: &Document, range: Range) -> Vec<CompletionItem> {
+    let item = |label: String, kind, detail: Option<String>| CompletionItem {
+        text_edit: Some(CompletionTextEdit::Edit(TextEdit::new(
+            range,
+            label.clone(),
+        ))),
+        label,
+        kind: Some(kind),
+        // editors show a color preview for hex documentation
+        documentation: detail.clone().map(Documentation::String),
+        detail,
+        ..CompletionItem::default()
+    };
+
+    let swatches = document
+        .keys()
+        .iter()
+        .filter(|key| key.section == Section::Palette)
+        .map(|key| {
+            item(
+                format!("${}", key.name),
+                CompletionItemKind::COLOR,
+                document.swatch_hex(&key.name).map(str::to_owned),
+            )
+        });
+
+    let roles = roles::iter().map(|role| {
+        item(
+            role.to_string(),
+            CompletionItemKind::REFERENCE,
+            Some(schema::describe(role)),
+        )
+    });
+
+    swatches.chain(roles).collect()
+}
+
+fn role_keys(document: &Document, group: Option<&str>) -> Vec<CompletionItem> {
+    let section = Section::Roles(group.map(str::to_owned));
+    let set = |key: &str| {
+        document
+            .keys()
+            .iter()
+            .any(|k| k.section == section && k.name == key)
+    };
+
+    roles::iter()
+        .filter_map(|role| {
+            let key = match (role.group(), group) {
+                (Group::Root, None) => role.as_str(),
+                (Group::Named(name), Some(group)) if name == group => role
+                    .as_str()
+                    .strip_prefix(name)
+                    .and_then(|key| key.strip_prefix('.'))?,
+                _ => return None,
+            };
+
+            (!set(key)).then(|| CompletionItem {
+                label: key.to_owned(),
+                kind: Some(CompletionItemKind::PROPERTY),
+                detail: Some(schema::describe(role)),
+                ..CompletionItem::default()
+            })
+        })
+        .collect()
+}
+
+/// A role's resolved color and how it got there, or a swatch's color and
+/// how many roles use it.
+#[must_use]
+pub(crate) fn hover(
+    document: &Document,
+    scheme: Option<&Scheme>,
+    position: Position,
+) -> Option<Hover> {
+    let (target, range) = if let Some(key) = document.key_at(position) {
+        if key.section == Section::Palette {
+            (format!("${}", key.name), key.range)
+        } else {
+            (key.role()?, key.range)
+        }
+    } else {
+        let value = document.value_at(position)?;
+        if !value.section.has_references() {
+            return None;
+        }
+
+        (value.text.clone(), value.range)
+    };
+
+    let markdown = if let Some(swatch) = target.strip_prefix('$') {
+        hover_swatch(document, scheme, swatch)?
+    } else {
+        hover_role(scheme, target.parse().ok()?)
+    };
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: markdown,
+        }),
+        range: Some(range),
+    })
+}
+
+fn hover_swatch(
+    document: &Document,
+    scheme: Option<&Scheme>,
+    name: &str,
+) -> Option<String> {
+    let Some(scheme) = scheme else {
+        let hex = document.swatch_hex(name)?;
+
+        return Some(format!("**`${name}`** `{hex}`"));
+    };
+
+    let swatch = scheme.palette.get(name)?;
+    let used = scheme
+        .resolved_roles
+        .values()
+        .filter(|resolved| resolved.swatch == name)
+        .count();
+
+    Some(format!(
+        "**`${name}`** `{}`\n\nused by {used} role{}",
+        swatch.color.hex(),
+        if used == 1 { "" } else { "s" },
+    ))
+}
+
+fn hover_role(scheme: Option<&Scheme>, role: RoleName) -> String {
+    let title = scheme
+        .and_then(|scheme| scheme.resolved_roles.get(&role))
+        .map_or_else(
+            || format!("**`{role}`**"),
+            |resolved| {
+                format!(
+                    "**`{role}`** `{}` (`${}`)",
+                    resolved.hex, resolved.swatch
+                )
+            },
+        );
+
+    let mut lines = vec![title, String::new()];
+    lines.push(schema::describe(role));
+
+    if let Some(scheme) = scheme {
+        lines.push(String::new());
+        lines.extend(chain(scheme, role));
+    }
+
+    lines.join("\n")
+}
+
+/// Each step from `role` to its swatch, as markdown list items.
+fn chain(scheme: &Scheme, mut role: RoleName) -> Vec<String> {
+    let mut steps = Vec::new();
+
+    // any longer and there's a cycle, which the loader reports anyway
+    for _ in 0..roles::iter().count() {
+        let next = match scheme.roles.get(&role) {
+            Some(RoleValue::Swatch(swatch)) => {
+                steps.push(format!("- `{role}` is `${swatch}`"));
+                break;
+            }
+            Some(RoleValue::Role(target)) => {
+                steps.push(format!("- `{role}` is set to `{target}`"));
+                *target
+            }
+            None => match role.classify() {
+                RoleKind::Optional { base } => {
+                    steps.push(format!("- `{role}` falls back to `{base}`"));
+                    base
+                }
+                RoleKind::Base(_) => {
+                    steps.push(format!("- `{role}` isn't set"));
+                    break;
+                }
+            },
+        };
+
+        role = next;
+    }
+
+    steps
+}
+
+/// A diagnostic for a loader error, placed at the TOML error's span or the
+/// first name it mentions.
+#[must_use]
+pub(crate) fn diagnostic(err: &crate::Error, text: &str) -> Diagnostic {
+    let message = err.to_string();
+
+    let range = match err {
+        crate::Error::Scheme(SchemeError::ParsingRaw { src, .. }) => {
+            src.span().map(|span| {
+                Range::new(
+                    document::position(text, span.start),
+                    document::position(text, span.end),
+                )
+            })
+        }
+        // the role may only reach the swatch through other roles
+        crate::Error::Scheme(SchemeError::UndefinedSwatch {
+            swatch, ..
+        }) => mentioned(&[swatch.as_str()], text),
+        _ => {
+            let names = message.split('`').skip(1).step_by(2);
+
+            mentioned(&names.collect::<Vec<_>>(), text)
+        }
+    }
+    .unwrap_or_default();
+
+    Diagnostic {
+        range,
+        severity: Some(DiagnosticSeverity::ERROR),
+        source: Some("theymer".to_owned()),
+        message,
+        ..Diagnostic::default()
+    }
+}
+
+/// Where the first of `names` is referenced in `text`, or failing that,
+/// appears at all.
+fn mentioned(names: &[&str], text: &str) -> Option<Range> {
+    let names = names.iter().filter(|name| !name.is_empty());
+
+    let quoted = names.clone().find_map(|name| {
+        [format!("\"{name}\""), format!("\"${name}\"")]
+            .iter()
+            .find_map(|quoted| {
+                // point at the name, not the quotes or `$`
+                let skip = quoted.len() - name.len() - 1;
+
+                text.find(quoted.as_str()).map(|i| (i + skip, name.len()))
+            })
+    });
+
+    let (offset, len) = quoted.or_else(|| {
+        names
+            .into_iter()
+            .find_map(|name| text.find(name).map(|i| (i, name.len())))
+    })?;
+
+    Some(Range::new(
+        document::position(text, offset),
+        document::position(text, offset + len),
+    ))
+}
+
+/// Every valid hex color in the palette.
+#[must_use]
+pub(crate) fn colors(document: &Document) -> Vec<ColorInformation> {
+    document
+        .values()
+        .iter()
+        .filter(|value| value.section == Section::Palette)
+        .filter_map(|value| {
+            let color = Color::parse(&value.text).ok()?;
+            let hex = color.hex().color();
+            let channel = |c: u8| f32::from(c) / 255.0;
+
+            Some(ColorInformation {
+                range: value.range,
+                color: LspColor {
+                    red: channel(hex.r),
+                    green: channel(hex.g),
+                    blue: channel(hex.b),
+                    alpha: channel(hex.a),
+                },
+            })
+        })
+        .collect()
+}
+
+/// `color` as a hex string, with alpha only if it isn't opaque.
+#[must_use]
+pub(crate) fn presentations(
+    color: LspColor,
+    range: Range,
+) -> Vec<ColorPresentation> {
+    let channel = |c: f32| {
+        #[expect(
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss,
+            reason = "clamped to 0..=255"
+        )]
+        let c = (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+        c
+    };
+
+    let (r, g, b, a) = (
+        channel(color.red),
+        channel(color.green),
+        channel(color.blue),
+        channel(color.alpha),
+    );
+
+    let label = if a == u8::MAX {
+        format!("#{r:02x}{g:02x}{b:02x}")
+    } else {
+        format!("#{r:02x}{g:02x}{b:02x}{a:02x}")
+    };
+
+    vec![ColorPresentation {
+        text_edit: Some(TextEdit::new(range, label.clone())),
+        label,
+        additional_text_edits: None,
+    }]
+}
+
+/// Renames the swatch at `position` in the palette and every `$` reference
+/// to it, or `None` if there's no swatch there.
+pub(crate) fn rename(
+    document: &Document,
+    uri: &Url,
+    position: Position,
+    new_name: &str,
+) -> crate::Result<Option<WorkspaceEdit>> {
+    let old = if let Some(key) = document.key_at(position) {
+        if key.section != Section::Palette {
+            return Ok(None);
+        }
+
+        key.name.clone()
+    } else {
+        let Some(name) = document
+            .value_at(position)
+            .filter(|value| value.section.has_references())
+            .and_then(|value| value.text.strip_prefix('$'))
+        else {
+            return Ok(None);
+        };
+
+        name.to_owned()
+    };
+
+    let new =
+        SwatchName::parse(new_name.strip_prefix('$').unwrap_or(new_name))?;
+    let new = new.as_str();
+
+    let keys = document
+        .keys()
+        .iter()
+        .filter(|key| key.section == Section::Palette && key.name == old)
+        .map(|key| TextEdit::new(key.range, toml_key(new)));
+
+    let reference = format!("${old}");
+    let references = document
+        .values()
+        .iter()
+        .filter(|value| {
+            value.section.has_references() && value.text == reference
+        })
+        .map(|value| TextEdit::new(value.range, format!("${new}")));
+
+    Ok(Some(WorkspaceEdit::new(HashMap::from([(
+        uri.clone(),
+        keys.chain(references).collect(),
+    )]))))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use indoc::indoc;
+
+    use super::*;
+    use crate::schemes;
+
+    const SCHEME: &str = indoc! {r##"
+        [palette]
+        ink = "#111010"
+        chalk = "#e6e1dc"
+        pink = "#fa7fb5"
+
+        [roles]
+        bg = "$ink"
+        fg = "$chalk"
+        toolbar = "bg"
+        select = "$pink"
+        accent = "$pink"
+
+        [roles.syntax]
+        keyword = "accent"
+    "##};
+
+    fn cutiepro() -> String {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("schemes")
+            .join("cutiepro.toml");
+
+        fs::read_to_string(path).expect("readable repo scheme")
+    }
+
+    fn labels(items: &[CompletionItem]) -> Vec<&str> {
+        items.iter().map(|item| item.label.as_str()).collect()
+    }
+
+    #[test]
+    fn references_and_keys_are_completed() {
+        let document = Document::parse(SCHEME);
+
+        let references = completion(&document, Position::new(6, 7));
+        assert!(labels(&references).contains(&"$chalk"));
+        assert!(labels(&references).contains(&"syntax.keyword"));
+
+        let keys = completion(&document, Position::new(14, 0));
+        assert!(labels(&keys).contains(&"string"));
+        assert!(!labels(&keys).contains(&"keyword"));
+        assert!(!labels(&keys).contains(&"bg"));
+
+        assert!(completion(&document, Position::new(1, 8)).is_empty());
+    }
+
+    #[test]
+    fn hover_shows_the_fallback_chain() {
+        let text = cutiepro()
+            .replace("keyword = \"$strawberry\"", "keyword = \"accent\"");
+        let document = Document::parse(&text);
+        let scheme = schemes::parse("cutiepro", &text, "test.toml")
+            .expect("valid scheme");
+
+        assert_eq!(
+            chain(&scheme, "syntax.keyword_operator".parse().expect("role")),
+            [
+                "- `syntax.keyword_operator` falls back to `syntax.keyword`",
+                "- `syntax.keyword` is set to `accent`",
+                "- `accent` is `$cherry_blossom`",
+            ]
+        );
+
+        let keyword = document
+            .keys()
+            .iter()
+            .find(|key| key.role().as_deref() == Some("syntax.keyword"))
+            .expect("keyword key");
+        let hover = hover(&document, Some(&scheme), keyword.range.start)
+            .expect("hover on role key");
+        let HoverContents::Markup(content) = hover.contents else {
+            panic!("expected markdown");
+        };
+
+        assert!(content.value.contains("#eaa5a7"));
+    }
+
+    #[test]
+    fn rename_updates_references() {
+        let document = Document::parse(SCHEME);
+        let uri = Url::parse("file:///test.toml").expect("valid url");
+
+        let edit = rename(&document, &uri, Position::new(3, 1), "château")
+            .expect("valid name")
+            .expect("swatch at position");
+        let edits = edit
+            .changes
+            .and_then(|mut c| c.remove(&uri))
+            .unwrap_or_default();
+
+        assert_eq!(
+            edits
+                .iter()
+                .map(|e| e.new_text.as_str())
+                .collect::<Vec<_>>(),
+            ["\"château\"", "$château", "$château"]
+        );
+        rename(&document, &uri, Position::new(3, 1), "no spaces")
+            .expect_err("names can't have spaces");
+        assert!(
+            rename(&document, &uri, Position::new(8, 1), "x")
+                .expect("no error")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn colors_round_trip() {
+        let document = Document::parse(SCHEME);
+        let colors = colors(&document);
+
+        assert_eq!(colors.len(), 3);
+
+        let pink = colors.get(2).expect("pink");
+        let presented = presentations(pink.color, pink.range);
+        assert_eq!(
+            presented.first().map(|p| p.label.as_str()),
+            Some("#fa7fb5")
+        );
+    }
+
+    #[test]
+    fn diagnostics_point_at_the_problem() {
+        let line = |text: &str| {
+            let err = schemes::parse("cutiepro", text, "test.toml")
+                .expect_err("invalid scheme");
+
+            diagnostic(&err, text).range.start
+        };
+
+        let undefined = cutiepro().replace("\"$chalk\"", "\"$chalky\"");
+        assert_eq!(line(&undefined), Position::new(46, 7));
+
+        let unclosed = cutiepro().replace("[roles.mode]", "[roles.mode");
+        assert_eq!(line(&unclosed).line, 77);
+    }
+}
@@ -0,0 +1,454 @@
+//! Committing rendered files in the git repos they're in.
+
+use std::path::PathBuf;
+use std::result::Result as StdResult;
+
+use git2::{
+    Cred, CredentialType, PushOptions, RemoteCallbacks, Repository, Status,
+    StatusOptions,
+};
+use indexmap::{IndexMap, IndexSet};
+use itertools::Itertools as _;
+use log::{info, warn};
+
+use super::upstream::Cache;
+use crate::config::Commit as Settings;
+use crate::render::Written;
+use crate::templates;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+type Result<T> = StdResult<T, Error>;
+
+#[non_exhaustive]
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum Error {
+    #[error("failed to open git repo `{repo}`: {src}")]
+    Opening { repo: String, src: git2::Error },
+
+    #[error(
+        "git repo `{repo}` has uncommitted changes theymer didn't make: {}",
+        .paths.iter().map(|p| format!("`{p}`")).join(", ")
+    )]
+    Dirty { repo: String, paths: Vec<String> },
+
+    #[error("tag `{tag}` already exists in `{repo}`")]
+    TagExists { repo: String, tag: String },
+
+    #[error("git repo `{repo}` has no branch checked out to push")]
+    Detached { repo: String },
+
+    #[error("failed to commit in `{repo}`: {src}")]
+    Committing { repo: String, src: git2::Error },
+
+    #[error("failed to push `{repo}` to `{remote}`: {src}")]
+    Pushing {
+        repo: String,
+        remote: String,
+        src: git2::Error,
+    },
+}
+
+/// A repo with the files to commit in it, checked and ready to commit.
+struct Pending<'a> {
+    root: PathBuf,
+    repo: Repository,
+    /// Paths relative to the repo root.
+    files: Vec<PathBuf>,
+    message: String,
+    tag: Option<String>,
+    settings: &'a Settings,
+}
+
+/// Commits the `written` files in each git repo they're in. Files outside
+/// any repo with a remote are left alone.
+pub(crate) fn all(written: &[Written], settings: &Settings) -> Result<()> {
+    let mut cache = Cache::new();
+    let mut repos: IndexMap<PathBuf, Vec<(PathBuf, &Written)>> =
+        IndexMap::new();
+
+    for file in written {
+        let Ok(path) = file.path.canonicalize() else {
+            warn!("failed to find written file `{}`", file.path.display());
+            continue;
+        };
+        let Some(upstream) = cache.get_or_detect(&path, None) else {
+            continue;
+        };
+        let Ok(relative) = path.strip_prefix(&upstream.root) else {
+            continue;
+        };
+
+        repos
+            .entry(upstream.root.clone())
+            .or_default()
+            .push((relative.to_path_buf(), file));
+    }
+
+    let pending = repos
+        .into_iter()
+        .map(|(root, files)| check(root, &files, settings))
+        .collect::<Result<Vec<_>>>()?;
+
+    for repo in pending {
+        commit(&repo)?;
+    }
+
+    Ok(())
+}
+
+/// Opens the repo at `root`, refusing it if it has changes besides `files`,
+/// or if its tag exists already.
+fn check<'a>(
+    root: PathBuf,
+    files: &[(PathBuf, &Written)],
+    settings: &'a Settings,
+) -> Result<Pending<'a>> {
+    let name = root.display().to_string();
+    let repo = Repository::open(&root).map_err(|src| Error::Opening {
+        repo: name.clone(),
+        src,
+    })?;
+
+    let written = files
+        .iter()
+        .map(|(path, _)| path.to_string_lossy().replace('\\', "/"))
+        .collect::<IndexSet<_>>();
+
+    let dirty = repo
+        .statuses(Some(
+            StatusOptions::new()
+                .include_untracked(true)
+                .recurse_untracked_dirs(true),
+        ))
+        .map_err(|src| Error::Opening {
+            repo: name.clone(),
+            src,
+        })?
+        .iter()
+        .filter(|entry| !entry.status().intersects(Status::IGNORED))
+        .filter_map(|entry| entry.path().map(str::to_owned))
+        .filter(|path| !written.contains(path))
+        .collect::<Vec<_>>();
+    if !dirty.is_empty() {
+        return Err(Error::Dirty {
+            repo: name,
+            paths: dirty,
+        });
+    }
+
+    let schemes = files
+        .iter()
+        .flat_map(|(_, file)| &file.schemes)
+        .map(String::as_str)
+        .unique()
+        .collect::<Vec<_>>();
+    let ports = files
+        .iter()
+        .map(|(_, file)| {
+            templates::port(&file.template).unwrap_or(&file.template)
+        })
+        .unique()
+        .collect::<Vec<_>>();
+
+    let tag = settings
+        .tag
+        .as_ref()
+        .map(|tag| expand(tag, &schemes, &ports, "+"));
+    if let Some(tag) = &tag
+        && repo.find_reference(&format!("refs/tags/{tag}")).is_ok()
+    {
+        return Err(Error::TagExists {
+            repo: name,
+            tag: tag.clone(),
+        });
+    }
+
+    if settings.push.is_some() && repo.head_detached().unwrap_or(false) {
+        return Err(Error::Detached { repo: name });
+    }
+
+    Ok(Pending {
+        message: expand(&settings.message, &schemes, &ports, ", "),
+        tag,
+        files: files.iter().map(|(path, _)| path.clone()).collect(),
+        root,
+        repo,
+        settings,
+    })
+}
+
+fn commit(pending: &Pending<'_>) -> Result<()> {
+    let Pending {
+        root,
+        repo,
+        files,
+        message,
+        tag,
+        settings,
+    } = pending;
+    let name = root.display().to_string();
+    let committing = |src| Error::Committing {
+        repo: name.clone(),
+        src,
+    };
+
+    let mut index = repo.index().map_err(committing)?;
+    for file in files {
+        index.add_path(file).map_err(committing)?;
+    }
+    index.write().map_err(committing)?;
+    let tree_id = index.write_tree().map_err(committing)?;
+
+    let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+    if parent
+        .as_ref()
+        .is_some_and(|parent| parent.tree_id() == tree_id)
+    {
+        info!("nothing to commit in `{name}`");
+        return Ok(());
+    }
+
+    let tree = repo.find_tree(tree_id).map_err(committing)?;
+    let signature = repo.signature().map_err(committing)?;
+    let parents = parent.iter().collect::<Vec<_>>();
+
+    let id = repo
+        .commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )
+        .map_err(committing)?;
+    info!("committed {} files in `{name}`", files.len());
+
+    if let Some(tag) = tag {
+        let object = repo.find_object(id, None).map_err(committing)?;
+        repo.tag(tag, &object, &signature, message, false)
+            .map_err(committing)?;
+        info!("tagged `{tag}` in `{name}`");
+    }
+
+    if let Some(remote) = &settings.push {
+        push(repo, &name, remote, tag.as_deref())?;
+    }
+
+    Ok(())
+}
+
+fn push(
+    repo: &Repository,
+    name: &str,
+    remote: &str,
+    tag: Option<&str>,
+) -> Result<()> {
+    let pushing = |src| Error::Pushing {
+        repo: name.to_owned(),
+        remote: remote.to_owned(),
+        src,
+    };
+
+    let head = repo.head().map_err(pushing)?;
+    let branch = head.name().ok_or_else(|| Error::Detached {
+        repo: name.to_owned(),
+    })?;
+
+    let refspecs = [
+        Some(branch.to_owned()),
+        tag.map(|t| format!("refs/tags/{t}")),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|reference| format!("{reference}:{reference}"))
+    .collect::<Vec<_>>();
+
+    let config = repo.config().map_err(pushing)?;
+    let mut tried = CredentialType::empty();
+    let mut rejected = Vec::new();
+
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(|url, username, allowed| {
+        credentials(&config, &mut tried, url, username, allowed)
+    });
+    callbacks.push_update_reference(|reference, status| {
+        if let Some(status) = status {
+            rejected.push(format!("`{reference}` ({status})"));
+        }
+
+        Ok(())
+    });
+    let mut options = PushOptions::new();
+    options.remote_callbacks(callbacks);
+
+    repo.find_remote(remote)
+        .and_then(|mut remote| remote.push(&refspecs, Some(&mut options)))
+        .map_err(pushing)?;
+    drop(options);
+
+    if !rejected.is_empty() {
+        return Err(pushing(git2::Error::from_str(&format!(
+            "rejected {}",
+            rejected.join(", ")
+        ))));
+    }
+
+    info!("pushed `{name}` to `{remote}`");
+
+    Ok(())
+}
+
+/// Credentials from the ssh agent or git's credential helpers, each tried
+/// once, since libgit2 keeps asking for as long as authentication fails.
+fn credentials(
+    config: &git2::Config,
+    tried: &mut CredentialType,
+    url: &str,
+    username: Option<&str>,
+    allowed: CredentialType,
+) -> StdResult<Cred, git2::Error> {
+    let untried = allowed - *tried;
+
+    if untried.contains(CredentialType::USERNAME) {
+        tried.insert(CredentialType::USERNAME);
+        Cred::username(username.unwrap_or("git"))
+    } else if untried.contains(CredentialType::SSH_KEY) {
+        tried.insert(CredentialType::SSH_KEY);
+        Cred::ssh_key_from_agent(username.unwrap_or("git"))
+    } else if untried.contains(CredentialType::USER_PASS_PLAINTEXT) {
+        tried.insert(CredentialType::USER_PASS_PLAINTEXT);
+        Cred::credential_helper(config, url, username)
+    } else if untried.contains(CredentialType::DEFAULT) {
+        tried.insert(CredentialType::DEFAULT);
+        Cred::default()
+    } else {
+        Err(git2::Error::from_str("no credentials accepted"))
+    }
+}
+
+/// Fills in `{schemes}`, `{ports}` and `{version}`, joining several schemes
+/// or ports with `separator`.
+fn expand(
+    template: &str,
+    scheme_names: &[&str],
+    port_names: &[&str],
+    separator: &str,
+) -> String {
+    template
+        .replace("{schemes}", &scheme_names.join(separator))
+        .replace("{ports}", &port_names.join(separator))
+        .replace("{version}", VERSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+
+    fn written(path: &Path) -> Written {
+        Written {
+            path: path.to_path_buf(),
+            template: "ports/fish/SCHEME.theme.jinja".to_owned(),
+            schemes: vec!["cutiepro".to_owned()],
+        }
+    }
+
+    fn clone(remote: &Path, path: &Path) -> Repository {
+        let repo = Repository::clone(&remote.display().to_string(), path)
+            .expect("cloned");
+        let mut config = repo.config().expect("config");
+        config.set_str("user.name", "they").expect("set");
+        config
+            .set_str("user.email", "they@example.com")
+            .expect("set");
+
+        repo
+    }
+
+    #[test]
+    fn written_files_are_committed_tagged_and_pushed() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let remote_path = dir.path().join("remote.git");
+        let work_path = dir.path().join("work");
+
+        let remote = Repository::init_bare(&remote_path).expect("bare repo");
+        let work = clone(&remote_path, &work_path);
+
+        let file = work_path.join("fish/cutiepro.theme");
+        fs::create_dir_all(file.parent().expect("parent")).expect("dir");
+        fs::write(&file, "fish_color_normal fff").expect("written");
+
+        let settings = Settings {
+            message: "Update {ports} for {schemes}".to_owned(),
+            tag: Some("{schemes}-{version}".to_owned()),
+            push: Some("origin".to_owned()),
+        };
+
+        all(&[written(&file)], &settings).expect("committed");
+
+        let branch = work.head().expect("head");
+        let pushed = remote
+            .find_reference(branch.name().expect("branch"))
+            .and_then(|reference| reference.peel_to_commit())
+            .expect("pushed");
+        assert_eq!(pushed.message(), Some("Update fish for cutiepro"));
+        remote
+            .find_reference(&format!("refs/tags/cutiepro-{VERSION}"))
+            .expect("tag pushed");
+
+        // the tag exists now
+        fs::write(&file, "fish_color_normal 000").expect("written");
+        assert!(matches!(
+            all(&[written(&file)], &settings),
+            Err(Error::TagExists { .. })
+        ));
+
+        fs::write(work_path.join("notes.txt"), "mine").expect("written");
+        let settings = Settings {
+            tag: None,
+            ..settings
+        };
+        assert!(matches!(
+            all(&[written(&file)], &settings),
+            Err(Error::Dirty { paths, .. }) if paths == ["notes.txt"]
+        ));
+    }
+
+    #[test]
+    fn rejected_pushes_are_errors() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let remote_path = dir.path().join("remote.git");
+        let work_path = dir.path().join("work");
+
+        Repository::init_bare(&remote_path).expect("bare repo");
+        let work = clone(&remote_path, &work_path);
+
+        let file = work_path.join("fish/cutiepro.theme");
+        fs::create_dir_all(file.parent().expect("parent")).expect("dir");
+        fs::write(&file, "fish_color_normal fff").expect("written");
+
+        let settings = Settings {
+            message: "Update {ports} for {schemes}".to_owned(),
+            tag: None,
+            push: Some("origin".to_owned()),
+        };
+        all(&[written(&file)], &settings).expect("pushed");
+
+        // the remote refuses to update a locked ref
+        let head = work.head().expect("head");
+        let branch = head.name().expect("branch");
+        fs::write(remote_path.join(format!("{branch}.lock")), "")
+            .expect("locked");
+
+        fs::write(&file, "fish_color_normal 000").expect("written");
+        assert!(matches!(
+            all(&[written(&file)], &settings),
+            Err(Error::Pushing { src, .. })
+                if src.message().starts_with(&format!("rejected `{branch}`"))
+        ));
+    }
+}
@@ -0,0 +1,75 @@
+//! Plain-text and markdown tables for command output.
+
+use std::iter;
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{Error, Result};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Format {
+    #[default]
+    Table,
+    Markdown,
+    Json,
+}
+
+/// Lays out `rows` as left-aligned columns, the first row being the header.
+pub(crate) fn columns<R: AsRef<[String]>>(rows: &[R]) -> String {
+    let mut widths = Vec::new();
+
+    for row in rows {
+        for (i, cell) in row.as_ref().iter().enumerate() {
+            let len = cell.chars().count();
+
+            match widths.get_mut(i) {
+                Some(width) => *width = len.max(*width),
+                None => widths.push(len),
+            }
+        }
+    }
+
+    let mut out = String::new();
+
+    for row in rows {
+        let line = row
+            .as_ref()
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+
+    out
+}
+
+/// Lays out `rows` as a markdown table, the first row being the header.
+pub(crate) fn markdown<R: AsRef<[String]>>(rows: &[R]) -> String {
+    let line = |cells: &[String]| format!("| {} |", cells.join(" | "));
+
+    let mut rows = rows.iter().map(AsRef::as_ref);
+    let Some(header) = rows.next() else {
+        return String::new();
+    };
+
+    iter::once(line(header))
+        .chain(iter::once(line(&vec!["---".to_owned(); header.len()])))
+        .chain(rows.map(line))
+        .map(|l| l + "\n")
+        .collect()
+}
+
+/// Serializes `value` as pretty-printed json, with a trailing newline.
+pub(crate) fn json<T: Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string_pretty(value)
+        .map(|json| json + "\n")
+        .map_err(|err| Error::InternalBug {
+            module: "table",
+            reason: format!("failed to serialize output: {err}"),
+        })
+}
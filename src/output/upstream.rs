@@ -30,14 +30,33 @@ pub(crate) enum Error {
 
 pub(crate) type Result<T> = StdResult<T, Error>;
 
+const DEFAULT_REMOTE: &str = "origin";
+
 #[derive(Debug, Clone)]
 pub(crate) struct Upstream {
     pub root: PathBuf,
     pub url: GitUrl,
-    pub branch: String,
+    /// The remote's default branch, if it's known.
+    pub branch: Option<String>,
+    /// The commit checked out, unless the url is static.
+    pub commit: Option<String>,
+    /// A tag pointing at the commit checked out.
+    pub tag: Option<String>,
 }
 
 impl Upstream {
+    /// An upstream for a directory that may not be a git repo at all, from a
+    /// url set in the config.
+    pub(crate) fn from_static(root: PathBuf, url: &str) -> Result<Self> {
+        Ok(Self {
+            root,
+            url: Self::parse(url)?,
+            branch: None,
+            commit: None,
+            tag: None,
+        })
+    }
+
     fn parse(remote: &str) -> Result<GitUrl> {
         let parsed =
             GitUrl::parse(remote).map_err(|src| Error::ParsingUrl {
@@ -48,52 +67,101 @@ impl Upstream {
         Ok(parsed)
     }
 
-    fn get_remote_url(repo: &Repository) -> Result<GitUrl> {
-        let remote = repo.find_remote("origin").or_else(|_| {
-            let remotes = repo
-                .remotes()
-                .map_err(|src| Error::FetchingRemotes { src })?;
-
-            let name = remotes.get(0).ok_or(Error::NoRemote)?;
-
-            // TODO: add hint about setting branch in config
-            info!(
-                "`origin` not found, defaulting to first found branch: \
-                 `{name}`"
-            );
-
+    /// The url of `name`, or of `origin` or the first remote without one,
+    /// with the remote's name.
+    fn get_remote_url(
+        repo: &Repository,
+        name: Option<&str>,
+    ) -> Result<(String, GitUrl)> {
+        let remote = if let Some(name) = name {
             repo.find_remote(name).map_err(|src| Error::FetchingUrl {
                 remote: name.to_owned(),
                 src,
-            })
-        })?;
+            })?
+        } else {
+            repo.find_remote(DEFAULT_REMOTE).or_else(|_| {
+                let remotes = repo
+                    .remotes()
+                    .map_err(|src| Error::FetchingRemotes { src })?;
+
+                let name = remotes.get(0).ok_or(Error::NoRemote)?;
+
+                info!(
+                    "`{DEFAULT_REMOTE}` not found, defaulting to first found \
+                     remote: `{name}` (set `remote` in `[upstream]` to pick \
+                     another)"
+                );
+
+                repo.find_remote(name).map_err(|src| Error::FetchingUrl {
+                    remote: name.to_owned(),
+                    src,
+                })
+            })?
+        };
 
+        let name = remote.name().unwrap_or(DEFAULT_REMOTE).to_owned();
         let raw_url = remote.url().ok_or(Error::InvalidUrl)?;
 
-        Self::parse(raw_url)
+        Ok((name, Self::parse(raw_url)?))
     }
 
-    fn detect_default_branch(repo: &Repository) -> String {
-        if let Ok(reference) = repo.find_reference("refs/remotes/origin/HEAD")
-            && let Some(target) = reference.symbolic_target()
-            && let Some(branch) = target.strip_prefix("refs/remotes/origin/")
-        {
-            return branch.to_owned();
-        }
+    fn detect_default_branch(
+        repo: &Repository,
+        remote: &str,
+    ) -> Option<String> {
+        let prefix = format!("refs/remotes/{remote}/");
+
+        let reference = repo.find_reference(&format!("{prefix}HEAD")).ok()?;
+        let target = reference.symbolic_target()?;
 
-        "main".to_owned()
+        target.strip_prefix(&prefix).map(str::to_owned)
     }
 
-    fn from_repo(repo: &Repository, root: PathBuf) -> Result<Self> {
-        let url = Self::get_remote_url(repo)?;
-        let branch = Self::detect_default_branch(repo);
+    /// The commit checked out and a tag pointing at it, if any.
+    fn detect_head(repo: &Repository) -> (Option<String>, Option<String>) {
+        let Some(commit) =
+            repo.head().ok().and_then(|head| head.peel_to_commit().ok())
+        else {
+            return (None, None);
+        };
+
+        let tag = repo.tag_names(None).ok().and_then(|names| {
+            names
+                .iter()
+                .flatten()
+                .find(|name| {
+                    repo.revparse_single(&format!("refs/tags/{name}"))
+                        .and_then(|object| object.peel_to_commit())
+                        .is_ok_and(|tagged| tagged.id() == commit.id())
+                })
+                .map(str::to_owned)
+        });
+
+        (Some(commit.id().to_string()), tag)
+    }
 
-        Ok(Self { root, url, branch })
+    fn from_repo(
+        repo: &Repository,
+        root: PathBuf,
+        remote: Option<&str>,
+    ) -> Result<Self> {
+        let (remote, url) = Self::get_remote_url(repo, remote)?;
+        let branch = Self::detect_default_branch(repo, &remote);
+        let (commit, tag) = Self::detect_head(repo);
+
+        Ok(Self {
+            root,
+            url,
+            branch,
+            commit,
+            tag,
+        })
     }
 }
 
+/// Upstreams by repo root and the remote they were detected from.
 #[derive(Debug, Default)]
-pub(crate) struct Cache(IndexMap<PathBuf, Option<Upstream>>);
+pub(crate) struct Cache(IndexMap<(PathBuf, Option<String>), Option<Upstream>>);
 
 impl Cache {
     #[must_use]
@@ -101,9 +169,11 @@ impl Cache {
         Self(IndexMap::new())
     }
 
+    /// The upstream of the repo `render_path` is in, from `remote` if set.
     pub(crate) fn get_or_detect(
         &mut self,
         render_path: &Path,
+        remote: Option<&str>,
     ) -> Option<Upstream> {
         let Ok(repo) = Repository::discover(render_path) else {
             warn!(
@@ -118,19 +188,19 @@ impl Cache {
             return None;
         };
 
-        let root = root.to_path_buf();
+        let key = (root.to_path_buf(), remote.map(str::to_owned));
 
-        if let Some(cached) = self.0.get(&root) {
+        if let Some(cached) = self.0.get(&key) {
             return cached.clone();
         }
 
-        let info = Upstream::from_repo(&repo, root.clone()).ok();
+        let info = Upstream::from_repo(&repo, key.0.clone(), remote).ok();
 
         if info.is_none() {
             warn!("failed to extract info from repo at `{}`", root.display());
         }
 
-        self.0.insert(root, info.clone());
+        self.0.insert(key, info.clone());
 
         info
     }
@@ -141,4 +211,10 @@ impl Cache {
 pub(crate) struct Special {
     pub upstream_repo: Option<String>,
     pub upstream_file: Option<String>,
+    /// The raw file behind `upstream_file`.
+    pub upstream_raw: Option<String>,
+    pub commit: Option<String>,
+    pub tag: Option<String>,
+    pub owner: Option<String>,
+    pub repo_name: Option<String>,
 }
@@ -1,104 +1,195 @@
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 
 use anyhow::Context as _;
-use indexmap::IndexMap;
+use indexmap::{IndexMap, IndexSet};
 use log::{debug, info, warn};
 
+use crate::config::RepoUpstream;
 use crate::output::upstream::{Cache, Special};
 use crate::output::{Decision, Upstream, WriteMode, format, strategy};
+use crate::templates::output::Values;
+use crate::templates::providers::Refs;
 use crate::templates::{
     Directives, JINJA_TEMPLATE_SUFFIX, Loader, ResolvedProvider,
     SET_TEST_OBJECT, SKIP_RENDERING_PREFIX, providers,
 };
-use crate::{Config, Error, Result, Scheme};
+use crate::{Config, Error, Result, Scheme, manifest};
 
+mod aggregate;
 mod context;
 mod index;
+mod iteration;
 mod objects;
 
-use self::index::Index;
-use self::objects::Color;
+use self::index::{Index, Inputs, Schemes};
+use self::iteration::{Current, Error as IterationError, Item, Iteration};
+use self::objects::{Color, Text};
+
+pub(crate) mod selection;
+
+pub(crate) use self::selection::{Error as SelectionError, Selection};
 
 const SCHEME_MARKER: &str = "SCHEME";
-const SWATCH_MARKER: &str = "SWATCH";
-const SWATCH_VARIABLE: &str = "swatch";
 
 #[non_exhaustive]
 #[derive(Debug)]
 pub(crate) struct Session {
     pub index: Index,
     pub providers: Vec<ResolvedProvider>,
+    pub fingerprints: IndexMap<String, String>,
+    pub features: IndexSet<String>,
+    /// Scheme hashes by scheme name, so each is only hashed once.
+    pub scheme_hashes: HashMap<String, String>,
     pub git_cache: Cache,
     pub write_mode: WriteMode,
     pub dry_run: bool,
+    /// Every file written so far.
+    pub written: Vec<Written>,
+}
+
+/// A file written during a render, and what it was rendered from.
+#[derive(Debug, Clone)]
+pub(crate) struct Written {
+    pub path: PathBuf,
+    pub template: String,
+    pub schemes: Vec<String>,
 }
 
 impl Session {
     fn new(
-        providers: Vec<ResolvedProvider>,
+        templates: &Loader,
         write_mode: WriteMode,
         dry_run: bool,
     ) -> Result<Self> {
         Ok(Self {
             index: Index::load_or_create()?,
-            providers,
+            providers: templates.providers.clone(),
+            fingerprints: templates.fingerprints.clone(),
+            features: templates.features.clone(),
+            scheme_hashes: HashMap::new(),
             git_cache: Cache::new(),
             write_mode,
             dry_run,
+            written: Vec::new(),
         })
     }
 
-    fn save(self) -> Result<()> {
+    fn fingerprint(&self, template_name: &str) -> anyhow::Result<&str> {
+        self.fingerprints
+            .get(template_name)
+            .map(String::as_str)
+            .ok_or_else(|| {
+                Error::InternalBug {
+                    module: "render",
+                    reason: format!(
+                        "template `{template_name}` missing from fingerprints"
+                    ),
+                }
+                .into()
+            })
+    }
+
+    fn scheme_hash(&mut self, scheme: &Scheme) -> anyhow::Result<String> {
+        let name = scheme.name.as_str();
+
+        if let Some(hash) = self.scheme_hashes.get(name) {
+            return Ok(hash.clone());
+        }
+
+        let hash = index::hash_scheme(scheme)?;
+        self.scheme_hashes.insert(name.to_owned(), hash.clone());
+
+        Ok(hash)
+    }
+
+    fn save(self) -> Result<Vec<Written>> {
         if !self.dry_run {
             self.index.save()?;
         }
 
-        Ok(())
+        Ok(self.written)
     }
 }
 
-fn uses_swatch_iteration(template_name: &str) -> bool {
-    template_name.contains(SWATCH_MARKER)
+/// One file a template renders to.
+struct Target<'a> {
+    path: PathBuf,
+    current: Option<Current<'a>>,
 }
 
 fn resolve_path(
     template_name: &str,
-    scheme_name: &str,
+    scheme: &Scheme,
+    directives: &Directives,
     config: &Config,
-    swatch_name: Option<&str>,
+    current: Option<&Current<'_>>,
 ) -> anyhow::Result<PathBuf> {
+    let render_dir = Path::new(&config.dirs.render);
+    let scheme_name = scheme.name.as_str();
+
+    if let Some(output) = &directives.output {
+        let appearance = scheme.appearance.to_string();
+        let variant = scheme
+            .meta
+            .custom
+            .get("variant")
+            .and_then(toml::Value::as_str)
+            .unwrap_or(&appearance);
+        let template_dir =
+            template_name.rsplit_once('/').map_or("", |(dir, _)| dir);
+
+        let swatch = current.and_then(|current| match current.item {
+            Item::Swatch(swatch) => Some(swatch),
+            _ => None,
+        });
+        let index = current.map(|current| current.index.to_string());
+
+        let values = Values {
+            scheme: scheme_name,
+            scheme_ascii: scheme.name_ascii.as_str(),
+            swatch: swatch.map(|swatch| swatch.name.as_str()),
+            swatch_ascii: swatch.map(|swatch| swatch.ascii.as_str()),
+            role: current.and_then(|current| match current.item {
+                Item::Role(name, _) => Some(name.as_str()),
+                _ => None,
+            }),
+            group: current.and_then(|current| match current.item {
+                Item::Group(group) => Some(group),
+                _ => None,
+            }),
+            index: index.as_deref(),
+            variant,
+            appearance: &appearance,
+            template_dir,
+        };
+
+        return Ok(render_dir.join(output.expand(&values)?));
+    }
+
     let relative_path = template_name
         .strip_suffix(JINJA_TEMPLATE_SUFFIX)
         .unwrap_or(template_name);
 
-    let filename = Path::new(relative_path)
-        .file_name()
-        .ok_or_else(|| Error::InternalBug {
+    if Path::new(relative_path).file_name().is_none() {
+        return Err(Error::InternalBug {
             module: "render",
             reason: format!(
                 "attempted to render to corrupted path `{relative_path}`"
             ),
-        })?
-        .to_string_lossy();
-
-    let parent_dirs = Path::new(relative_path)
-        .parent()
-        .unwrap_or_else(|| Path::new(""));
-
-    let output = swatch_name.map_or_else(
-        || filename.replace(SCHEME_MARKER, scheme_name),
-        |swatch| {
-            filename
-                .replace(SCHEME_MARKER, scheme_name)
-                .replace(SWATCH_MARKER, swatch)
-        },
-    );
-
-    Ok(Path::new(&config.dirs.render)
-        .join(scheme_name)
-        .join(parent_dirs)
-        .join(output))
+        }
+        .into());
+    }
+
+    // markers work in directories too, e.g. `SCHEME/colors.toml.jinja`
+    let output = relative_path.replace(SCHEME_MARKER, scheme_name);
+    let output = match current {
+        Some(current) => current.fill(&output),
+        None => output,
+    };
+
+    Ok(render_dir.join(scheme_name).join(output))
 }
 
 fn strip_prefix(path: &Path, prefix: &Path, context: &str) -> Option<PathBuf> {
@@ -118,10 +209,11 @@ fn strip_prefix(path: &Path, prefix: &Path, context: &str) -> Option<PathBuf> {
 
 fn git_info_with(
     target_path: &Path,
+    remote: Option<&str>,
     context: &str,
     git_cache: &mut Cache,
 ) -> Option<(Upstream, PathBuf)> {
-    let git_info = git_cache.get_or_detect(target_path)?;
+    let git_info = git_cache.get_or_detect(target_path, remote)?;
 
     let rel_path = strip_prefix(
         target_path,
@@ -134,6 +226,7 @@ fn git_info_with(
 
 fn resolve_with_autodetect(
     render_path: &Path,
+    remote: Option<&str>,
     git_cache: &mut Cache,
 ) -> Option<(Upstream, PathBuf)> {
     let abs_path = render_path.canonicalize().ok().or_else(|| {
@@ -146,7 +239,20 @@ fn resolve_with_autodetect(
         None
     })?;
 
-    git_info_with(&abs_path, "auto-detect mode", git_cache)
+    git_info_with(&abs_path, remote, "auto-detect mode", git_cache)
+}
+
+fn resolve_static(
+    render_path: &Path,
+    root: &Path,
+    url: &str,
+) -> Option<(Upstream, PathBuf)> {
+    let upstream = Upstream::from_static(root.to_path_buf(), url)
+        .map_err(|err| warn!("invalid upstream url `{url}`: {err}"))
+        .ok()?;
+    let rel_path = strip_prefix(render_path, root, "static mode")?;
+
+    Some((upstream, rel_path))
 }
 
 fn build_upstream(
@@ -155,21 +261,42 @@ fn build_upstream(
     session: &mut Session,
     config: &Config,
 ) -> Special {
-    let Some((git_info, path)) =
-        resolve_with_autodetect(render_path, &mut session.git_cache)
-    else {
+    let repo = config.upstream.repo(render_path);
+    let scheme = config.upstream.schemes.get(scheme_name);
+    let repo_overrides = repo.map(|(_, overrides)| overrides);
+
+    // scheme overrides win over repo ones
+    let scheme_remote = scheme.and_then(|scheme| scheme.remote.as_deref());
+    let repo_remote = repo_overrides.and_then(|repo| repo.remote.as_deref());
+    let scheme_branch = scheme.and_then(|scheme| scheme.branch.as_deref());
+    let repo_branch = repo_overrides.and_then(|repo| repo.branch.as_deref());
+    let remote = scheme_remote.or(repo_remote);
+
+    let resolved = match repo {
+        Some((root, RepoUpstream { url: Some(url), .. })) => {
+            resolve_static(render_path, root, url)
+        }
+        _ => {
+            resolve_with_autodetect(render_path, remote, &mut session.git_cache)
+        }
+    };
+    let Some((git_info, path)) = resolved else {
         return Special::default();
     };
 
     // investigate where replacing backslashes is needed here
     let file_path = path.to_string_lossy().replace('\\', "/");
 
-    let branch = &git_info.branch;
+    let refs = Refs {
+        branch: scheme_branch.or(repo_branch),
+        default_branch: git_info.branch.as_deref(),
+        sha: git_info.commit.as_deref(),
+    };
 
     let Ok(blob) = providers::build_blob(
         &git_info.url,
         &file_path,
-        branch,
+        &refs,
         &session.providers,
     ) else {
         // FIXME: error handling
@@ -178,11 +305,21 @@ fn build_upstream(
         return Special::default();
     };
 
-    let repo = providers::extract_repo_url(&blob).ok().flatten();
+    let repo_url = providers::extract_repo_url(&blob, &session.providers).ok();
+    let raw = providers::resolve_blob(&blob, &session.providers)
+        .map_err(|err| debug!("no raw url for `{blob}`: {err}"))
+        .ok();
+    let (owner, repo_name) =
+        providers::owner_and_repo(&git_info.url).ok().unzip();
 
     Special {
         upstream_file: Some(blob),
-        upstream_repo: repo,
+        upstream_repo: repo_url,
+        upstream_raw: raw,
+        commit: git_info.commit,
+        tag: git_info.tag,
+        owner,
+        repo_name,
     }
 }
 
@@ -199,10 +336,9 @@ fn prepare(
     template: &minijinja::Template<'_, '_>,
     directives: &Directives,
     special: &Special,
-    current_swatch: Option<&str>,
+    current: Option<&Current<'_>>,
 ) -> anyhow::Result<String> {
-    let context =
-        context::build(scheme, special, &directives.style, current_swatch)?;
+    let context = context::build(scheme, special, &directives.style, current)?;
 
     if !context.contains_key(SET_TEST_OBJECT) {
         return Err(Error::InternalBug {
@@ -228,13 +364,26 @@ fn prepare(
     Ok(format!("{header}{rendered}"))
 }
 
+/// Renders and writes `path` if the index and write mode say so. Skipped
+/// outputs are never rendered, since nothing they depend on changed.
+fn update(
+    path: &Path,
+    inputs: &Inputs<'_>,
+    session: &mut Session,
+    render: impl FnOnce() -> anyhow::Result<String>,
+) -> anyhow::Result<()> {
+    let status = session.index.check(path, inputs)?;
+    let decision = strategy::decide(status, session.write_mode);
+
+    execute(decision, path, inputs, session, render)
+}
+
 fn execute(
     decision: Decision,
     path: &Path,
-    output: &str,
-    scheme: &Scheme,
-    template: &minijinja::Template<'_, '_>,
+    inputs: &Inputs<'_>,
     session: &mut Session,
+    render: impl FnOnce() -> anyhow::Result<String>,
 ) -> anyhow::Result<()> {
     match decision {
         // TODO: add interactive mode (possibly as default behavior?)
@@ -246,6 +395,8 @@ fn execute(
             );
         }
         _ if decision.should_write() => {
+            let output = render()?;
+
             if session.dry_run {
                 info!(
                     "would write `{}` ({})",
@@ -270,10 +421,20 @@ fn execute(
                         format!("reading file `{}` for hashing", path.display())
                     })?;
 
-                let entry =
-                    Index::create_entry(path, template, scheme, &formatted)?;
-
-                session.index.insert(entry);
+                session
+                    .index
+                    .insert(Index::create_entry(path, inputs, &formatted));
+                session.written.push(Written {
+                    path: path.to_path_buf(),
+                    template: inputs.template.to_owned(),
+                    schemes: match &inputs.schemes {
+                        Schemes::One(scheme) => vec![(*scheme).to_owned()],
+                        Schemes::All(schemes) => schemes
+                            .iter()
+                            .map(|&scheme| scheme.to_owned())
+                            .collect(),
+                    },
+                });
 
                 info!("generated `{}`", path.display());
             }
@@ -293,27 +454,33 @@ fn write(
     directives: &Directives,
     config: &Config,
     session: &mut Session,
-    current_swatch: Option<&str>,
+    target: &Target<'_>,
 ) -> anyhow::Result<()> {
     let scheme_name = scheme.name.as_str();
-    let path =
-        resolve_path(template_name, scheme_name, config, current_swatch)?;
-    let special = build_upstream(scheme_name, &path, session, config);
-
-    let output = prepare(
-        &path,
-        scheme,
-        template_name,
-        template,
-        directives,
-        &special,
-        current_swatch,
-    )?;
-
-    let status = session.index.check(&path, scheme, template)?;
-    let decision = strategy::decide(status, session.write_mode);
+    let path = &target.path;
+    let special = build_upstream(scheme_name, path, session, config);
+
+    let inputs = Inputs {
+        template: template.name(),
+        schemes: Schemes::One(scheme_name),
+        dependency_hash: index::hash_dependencies(
+            session.fingerprint(template_name)?,
+            &special,
+        ),
+        scheme_hash: session.scheme_hash(scheme)?,
+    };
 
-    execute(decision, &path, &output, scheme, template, session)?;
+    update(path, &inputs, session, || {
+        prepare(
+            path,
+            scheme,
+            template_name,
+            template,
+            directives,
+            &special,
+            target.current.as_ref(),
+        )
+    })?;
 
     Ok(())
 }
@@ -338,26 +505,72 @@ fn apply_internal(
     config: &Config,
     session: &mut Session,
 ) -> anyhow::Result<()> {
-    if uses_swatch_iteration(template_name) {
-        if !template.source().contains(SWATCH_VARIABLE) {
-            warn!(
-                "template `{template_name}` has `{SWATCH_MARKER}` in filename \
-                 but doesn't use {SWATCH_VARIABLE} inside template",
-            );
-        }
-
-        for swatch in &scheme.palette {
-            write(
-                scheme,
+    let Some(iteration) =
+        Iteration::detect(template_name, directives, &session.features)?
+    else {
+        let target = Target {
+            path: resolve_path(
                 template_name,
-                template,
+                scheme,
                 directives,
                 config,
-                session,
-                Some(swatch.name.as_str()),
-            )?;
+                None,
+            )?,
+            current: None,
+        };
+
+        return write(
+            scheme,
+            template_name,
+            template,
+            directives,
+            config,
+            session,
+            &target,
+        );
+    };
+
+    let variable = iteration.variable();
+    if !template.source().contains(variable) {
+        warn!(
+            "template `{template_name}` has `{}` in its path but doesn't use \
+             {variable} inside template",
+            iteration.marker()
+        );
+    }
+
+    // resolved up front, so no output overwrites another
+    let mut paths = HashSet::new();
+    let mut targets = Vec::new();
+    for (index, item) in iteration
+        .items(scheme, template_name)?
+        .into_iter()
+        .enumerate()
+    {
+        let current = Current { item, index };
+        let path = resolve_path(
+            template_name,
+            scheme,
+            directives,
+            config,
+            Some(&current),
+        )?;
+
+        if !paths.insert(path.clone()) {
+            return Err(IterationError::Collision {
+                template: template_name.to_owned(),
+                path: path.display().to_string(),
+                scheme: scheme.name.as_str().to_owned(),
+            }
+            .into());
         }
-    } else {
+        targets.push(Target {
+            path,
+            current: Some(current),
+        });
+    }
+
+    for target in &targets {
         write(
             scheme,
             template_name,
@@ -365,7 +578,7 @@ fn apply_internal(
             directives,
             config,
             session,
-            None,
+            target,
         )?;
     }
 
@@ -377,8 +590,9 @@ pub(crate) fn all_with(
     templates: &Loader,
     config: &Config,
     session: &mut Session,
+    include: impl Fn(&str) -> bool,
 ) -> Result<()> {
-    all_with_internal(scheme, templates, config, session)
+    all_with_internal(scheme, templates, config, session, include)
         .map_err(Error::rendering)
 }
 
@@ -387,11 +601,15 @@ fn all_with_internal(
     templates: &Loader,
     config: &Config,
     session: &mut Session,
+    include: impl Fn(&str) -> bool,
 ) -> anyhow::Result<()> {
     for (template_name, (template, directives)) in
         templates.with_directives()?
     {
-        if !should_render(template_name) {
+        if directives.aggregate
+            || !should_render(template_name)
+            || !include(template_name)
+        {
             continue;
         }
 
@@ -408,32 +626,109 @@ fn all_with_internal(
     Ok(())
 }
 
-pub(crate) fn all(
+/// Renders only the scheme and template pairs `include` accepts, given the
+/// scheme's key in `schemes` and the template name. Returns every file it
+/// wrote.
+pub(crate) fn selected(
     templates: &Loader,
     schemes: &IndexMap<String, Scheme>,
     config: &Config,
     write_mode: WriteMode,
     dry_run: bool,
+    include: impl Fn(&str, &str) -> bool,
+) -> Result<Vec<Written>> {
+    selected_internal(templates, schemes, config, write_mode, dry_run, include)
+        .map_err(Error::rendering)
+}
+
+/// Removes the outputs of the removed scheme `scheme_name` along with their
+/// index entries, and any directories under the render directory left empty.
+/// Outputs changed since they were rendered are kept.
+pub(crate) fn prune_scheme(
+    scheme_name: &str,
+    config: &Config,
+    dry_run: bool,
 ) -> Result<()> {
-    all_internal(templates, schemes, config, write_mode, dry_run)
+    prune_scheme_internal(scheme_name, config, dry_run)
         .map_err(Error::rendering)
 }
 
-fn all_internal(
-    templates: &Loader,
-    schemes: &IndexMap<String, Scheme>,
+fn prune_scheme_internal(
+    scheme_name: &str,
     config: &Config,
-    write_mode: WriteMode,
     dry_run: bool,
 ) -> anyhow::Result<()> {
-    let mut session =
-        Session::new(templates.providers.clone(), write_mode, dry_run)?;
+    let render_dir = Path::new(&config.dirs.render).canonicalize().ok();
+    let mut index = Index::load_or_create()?;
+
+    let orphans = index
+        .entries
+        .values()
+        .filter(|entry| entry.scheme == scheme_name)
+        .map(|entry| (entry.path.clone(), entry.hash.clone()))
+        .collect::<Vec<_>>();
+    if orphans.is_empty() {
+        return Ok(());
+    }
+
+    for (path, hash) in &orphans {
+        if path.exists() && manifest::hash_file(path)? != *hash {
+            warn!(
+                "kept `{}` of removed scheme `{scheme_name}`, it changed \
+                 since it was rendered",
+                path.display()
+            );
+        } else if dry_run {
+            info!("would remove `{}`", path.display());
+        } else {
+            if path.exists() {
+                fs::remove_file(path).with_context(|| {
+                    format!("failed to remove `{}`", path.display())
+                })?;
+            }
+            info!("removed `{}`", path.display());
+
+            let mut dir = path.parent();
+            while let Some(parent) = dir
+                && render_dir
+                    .as_deref()
+                    .is_some_and(|render| parent.starts_with(render))
+                && Some(parent) != render_dir.as_deref()
+                && fs::remove_dir(parent).is_ok()
+            {
+                dir = parent.parent();
+            }
+        }
 
-    for scheme_ref in schemes.values() {
-        all_with(scheme_ref, templates, config, &mut session)?;
+        index.remove(path);
     }
 
-    session.save()?;
+    if !dry_run {
+        index.save()?;
+    }
 
     Ok(())
 }
+
+fn selected_internal(
+    templates: &Loader,
+    schemes: &IndexMap<String, Scheme>,
+    config: &Config,
+    write_mode: WriteMode,
+    dry_run: bool,
+    include: impl Fn(&str, &str) -> bool,
+) -> anyhow::Result<Vec<Written>> {
+    let mut session = Session::new(templates, write_mode, dry_run)?;
+
+    for (key, scheme_ref) in schemes {
+        all_with(scheme_ref, templates, config, &mut session, |template| {
+            include(key, template)
+        })?;
+    }
+
+    aggregate::all(templates, schemes, config, &mut session, |template| {
+        schemes.keys().any(|key| include(key, template))
+    })?;
+
+    Ok(session.save()?)
+}
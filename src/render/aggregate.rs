@@ -0,0 +1,125 @@
+//! Templates rendered once with every scheme, like a gallery.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+use indexmap::IndexMap;
+
+use super::index::{self, Inputs, Schemes};
+use super::{Session, build_upstream, context, should_render, update};
+use crate::output::upstream::Special;
+use crate::templates::output::Values;
+use crate::templates::{
+    AGGREGATE_MARKER, Directives, JINJA_TEMPLATE_SUFFIX, Loader,
+};
+use crate::{Config, Scheme};
+
+/// Renders every aggregate template `include` accepts.
+pub(super) fn all(
+    templates: &Loader,
+    schemes: &IndexMap<String, Scheme>,
+    config: &Config,
+    session: &mut Session,
+    include: impl Fn(&str) -> bool,
+) -> anyhow::Result<()> {
+    for (template_name, (template, directives)) in
+        templates.with_directives()?
+    {
+        if !directives.aggregate
+            || !should_render(template_name)
+            || !include(template_name)
+        {
+            continue;
+        }
+
+        let path = resolve_path(template_name, directives, config)?;
+        let special = build_upstream("", &path, session, config);
+
+        let mut scheme_hashes = Vec::with_capacity(schemes.len());
+        for scheme in schemes.values() {
+            scheme_hashes
+                .push((scheme.name.as_str(), session.scheme_hash(scheme)?));
+        }
+
+        let inputs = Inputs {
+            template: template_name,
+            schemes: Schemes::All(
+                schemes
+                    .values()
+                    .map(|scheme| scheme.name.as_str())
+                    .collect(),
+            ),
+            dependency_hash: index::hash_dependencies(
+                session.fingerprint(template_name)?,
+                &special,
+            ),
+            scheme_hash: index::hash_schemes(&scheme_hashes),
+        };
+
+        update(&path, &inputs, session, || {
+            prepare(
+                &path,
+                template_name,
+                &template,
+                directives,
+                schemes,
+                &special,
+            )
+        })?;
+    }
+
+    Ok(())
+}
+
+fn resolve_path(
+    template_name: &str,
+    directives: &Directives,
+    config: &Config,
+) -> anyhow::Result<PathBuf> {
+    let render_dir = Path::new(&config.dirs.render);
+
+    if let Some(output) = &directives.output {
+        let values = Values {
+            scheme: "",
+            scheme_ascii: "",
+            swatch: None,
+            swatch_ascii: None,
+            role: None,
+            group: None,
+            index: None,
+            variant: "",
+            appearance: "",
+            template_dir: template_name
+                .rsplit_once('/')
+                .map_or("", |(dir, _)| dir),
+        };
+
+        return Ok(render_dir.join(output.expand(&values)?));
+    }
+
+    let relative_path = template_name
+        .strip_suffix(JINJA_TEMPLATE_SUFFIX)
+        .unwrap_or(template_name);
+
+    Ok(render_dir.join(relative_path.replace(AGGREGATE_MARKER, "schemes")))
+}
+
+fn prepare(
+    path: &Path,
+    template_name: &str,
+    template: &minijinja::Template<'_, '_>,
+    directives: &Directives,
+    schemes: &IndexMap<String, Scheme>,
+    special: &Special,
+) -> anyhow::Result<String> {
+    let context =
+        context::build_aggregate(schemes, special, &directives.style)?;
+
+    let rendered = template.render(&context).with_context(|| {
+        format!("rendering aggregate template `{template_name}`")
+    })?;
+
+    let header = directives.make_header(path);
+
+    Ok(format!("{header}{rendered}"))
+}
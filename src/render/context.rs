@@ -1,19 +1,24 @@
 use std::collections::BTreeMap;
+use std::iter;
 use std::sync::Arc;
 
 use indexmap::IndexMap;
 
-use super::Color;
+use super::iteration::{Current, Item};
+use super::{Color, Text};
+use crate::output::Style;
 use crate::output::upstream::Special;
-use crate::output::{Style, TextStyle};
-use crate::schemes::{Meta, ResolvedExtra, ResolvedRole, RoleName, Swatch};
+use crate::schemes::{
+    ResolvedExtra, ResolvedFeature, ResolvedRole, RoleName, Swatch, meta,
+};
+use crate::templates::SET_TEST_OBJECT;
 use crate::{Result, Scheme};
 
 pub(crate) fn build(
     scheme: &Scheme,
     special: &Special,
     style: &Arc<Style>,
-    current_swatch: Option<&str>,
+    current: Option<&Current<'_>>,
 ) -> Result<BTreeMap<String, minijinja::Value>> {
     let mut ctx = BTreeMap::new();
 
@@ -45,8 +50,8 @@ pub(crate) fn build(
         insert_extra(&mut ctx, resolved_extra, style);
     }
 
-    if let Some(name) = current_swatch {
-        insert_current_swatch(&mut ctx, scheme, name, &swatch_roles, style)?;
+    if let Some(current) = current {
+        insert_current(&mut ctx, scheme, current, &swatch_roles, style)?;
     }
 
     insert_special(&mut ctx, special);
@@ -56,13 +61,28 @@ pub(crate) fn build(
     Ok(ctx)
 }
 
-fn ascii_fallback(
-    unicode: Option<&String>,
-    ascii: Option<&String>,
-) -> Option<String> {
-    ascii
-        .cloned()
-        .or_else(|| unicode.map(|s| deunicode::deunicode(s)))
+/// The context of an aggregate template: every scheme's own context, in
+/// `schemes`.
+pub(crate) fn build_aggregate(
+    schemes: &IndexMap<String, Scheme>,
+    special: &Special,
+    style: &Arc<Style>,
+) -> Result<BTreeMap<String, minijinja::Value>> {
+    let mut ctx = BTreeMap::new();
+
+    let schemes = schemes
+        .values()
+        .map(|scheme| build(scheme, special, style, None).map(Into::into))
+        .collect::<Result<Vec<minijinja::Value>>>()?;
+
+    ctx.insert("schemes".to_owned(), minijinja::Value::from(schemes));
+
+    insert_special(&mut ctx, special);
+
+    // `is set` has no single scheme to check, see each scheme's `_set`
+    ctx.insert(SET_TEST_OBJECT.to_owned(), minijinja::Value::from(()));
+
+    Ok(ctx)
 }
 
 fn insert_meta(
@@ -80,37 +100,90 @@ fn insert_meta(
         minijinja::Value::from_serialize(&scheme.name_ascii),
     );
 
-    let author_ascii = ascii_fallback(
-        scheme.meta.author.as_ref(),
-        scheme.meta.author_ascii.as_ref(),
-    );
+    let meta = &scheme.meta;
+    let text = |text: &meta::Text| {
+        minijinja::Value::from_object(Text::new(
+            text.name.clone(),
+            text.ascii.clone(),
+            Arc::clone(style),
+        ))
+    };
 
-    let license_ascii = ascii_fallback(
-        scheme.meta.license.as_ref(),
-        scheme.meta.license_ascii.as_ref(),
-    );
+    let mut meta_ctx = BTreeMap::new();
+
+    for (key, value) in [
+        ("author", &meta.author),
+        ("license", &meta.license),
+        ("blurb", &meta.blurb),
+    ] {
+        if let Some(value) = value {
+            meta_ctx.insert(key.to_owned(), text(value));
+            meta_ctx.insert(
+                format!("{key}_ascii"),
+                minijinja::Value::from(&value.ascii),
+            );
+        }
+    }
 
-    let blurb_ascii = ascii_fallback(
-        scheme.meta.blurb.as_ref(),
-        scheme.meta.blurb_ascii.as_ref(),
-    );
+    if !meta.authors.is_empty() {
+        let authors = meta
+            .authors
+            .iter()
+            .map(|author| {
+                let links = [("url", &author.url), ("email", &author.email)]
+                    .into_iter()
+                    .filter_map(|(key, value)| {
+                        Some((key, minijinja::Value::from(value.as_ref()?)))
+                    });
+
+                minijinja::Value::from(
+                    iter::once(("name", text(&author.name)))
+                        .chain(links)
+                        .collect::<BTreeMap<_, _>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        meta_ctx.insert("authors".to_owned(), minijinja::Value::from(authors));
+    }
 
-    let meta_ctx = if style.text == TextStyle::Ascii {
-        Meta {
-            author: author_ascii.clone(),
-            author_ascii,
-            license: license_ascii.clone(),
-            license_ascii,
-            blurb: blurb_ascii.clone(),
-            blurb_ascii,
+    for (key, value) in [
+        ("homepage", &meta.homepage),
+        ("version", &meta.version),
+        ("upstream_original", &meta.upstream_original),
+    ] {
+        if let Some(value) = value {
+            meta_ctx.insert(key.to_owned(), minijinja::Value::from(value));
         }
-    } else {
-        scheme.meta.clone()
-    };
+    }
+
+    if let Some(appearance) = meta.appearance {
+        meta_ctx.insert(
+            "appearance".to_owned(),
+            minijinja::Value::from(appearance.to_string()),
+        );
+    }
+
+    if !meta.custom.is_empty() {
+        meta_ctx.insert(
+            "custom".to_owned(),
+            minijinja::Value::from_serialize(&meta.custom),
+        );
+    }
+
+    ctx.insert("meta".to_owned(), minijinja::Value::from(meta_ctx));
 
     ctx.insert(
-        "meta".to_owned(),
-        minijinja::Value::from_serialize(&meta_ctx),
+        "appearance".to_owned(),
+        minijinja::Value::from(scheme.appearance.to_string()),
+    );
+    ctx.insert(
+        "is_dark".to_owned(),
+        minijinja::Value::from(scheme.appearance.is_dark()),
+    );
+    ctx.insert(
+        "is_light".to_owned(),
+        minijinja::Value::from(!scheme.appearance.is_dark()),
     );
 }
 
@@ -228,28 +301,76 @@ fn insert_role(
     Ok(())
 }
 
+fn role_object(role: &ResolvedRole, style: &Arc<Style>) -> minijinja::Value {
+    minijinja::Value::from_object(Color::role(
+        role.hex.clone(),
+        role.swatch.clone(),
+        role.ascii.clone(),
+        role.rgb,
+        Arc::clone(style),
+    ))
+}
+
 fn insert_extra(
     ctx: &mut BTreeMap<String, minijinja::Value>,
     resolved_extra: &ResolvedExtra,
     style: &Arc<Style>,
 ) {
-    let rainbow: Vec<minijinja::Value> = resolved_extra
-        .rainbow
-        .iter()
-        .map(|r| {
-            let color = Color::role(
-                r.hex.clone(),
-                r.swatch.clone(),
-                r.ascii.clone(),
-                r.rgb,
-                Arc::clone(style),
+    let object = |r: &ResolvedRole| role_object(r, style);
+
+    for (name, feature) in &resolved_extra.features {
+        let value = match feature {
+            ResolvedFeature::List(list) => {
+                list.iter().map(object).collect::<minijinja::Value>()
+            }
+            ResolvedFeature::Map(map) => map
+                .iter()
+                .map(|(key, r)| (key.clone(), object(r)))
+                .collect::<minijinja::Value>(),
+        };
+
+        ctx.insert(name.clone(), value);
+    }
+}
+
+/// Adds the item a template fanning out is rendered for, e.g. `role` and
+/// `role_name` for `ROLE`, and its `index`.
+fn insert_current(
+    ctx: &mut BTreeMap<String, minijinja::Value>,
+    scheme: &Scheme,
+    current: &Current<'_>,
+    swatch_roles: &IndexMap<String, Vec<String>>,
+    style: &Arc<Style>,
+) -> Result<()> {
+    match current.item {
+        Item::Swatch(swatch) => insert_current_swatch(
+            ctx,
+            scheme,
+            swatch.name.as_str(),
+            swatch_roles,
+            style,
+        )?,
+        Item::Role(name, role) => {
+            ctx.insert("role".to_owned(), role_object(role, style));
+            ctx.insert(
+                "role_name".to_owned(),
+                minijinja::Value::from(name.as_str()),
             );
+        }
+        Item::Group(group) => {
+            let roles = ctx.get(group).cloned().unwrap_or_default();
 
-            minijinja::Value::from_object(color)
-        })
-        .collect();
+            ctx.insert("group".to_owned(), roles);
+            ctx.insert("group_name".to_owned(), minijinja::Value::from(group));
+        }
+        Item::Extra(_, role) => {
+            ctx.insert("item".to_owned(), role_object(role, style));
+        }
+    }
 
-    ctx.insert("rainbow".to_owned(), minijinja::Value::from(rainbow));
+    ctx.insert("index".to_owned(), minijinja::Value::from(current.index));
+
+    Ok(())
 }
 
 fn insert_current_swatch(
@@ -292,10 +413,13 @@ fn insert_set_test_roles(
     let mut set_roles: Vec<String> =
         scheme.roles.keys().map(ToString::to_string).collect();
 
-    if let Some(r) = &scheme.resolved_extra
-        && !r.rainbow.is_empty()
-    {
-        set_roles.push("rainbow".to_owned());
+    if let Some(r) = &scheme.resolved_extra {
+        set_roles.extend(
+            r.features
+                .iter()
+                .filter(|(_, feature)| !feature.is_empty())
+                .map(|(name, _)| name.clone()),
+        );
     }
 
     ctx.insert("_set".to_owned(), minijinja::Value::from(set_roles));
@@ -305,17 +429,119 @@ fn insert_special(
     ctx: &mut BTreeMap<String, minijinja::Value>,
     special: &Special,
 ) {
-    let mut special_map = BTreeMap::new();
+    let special_map = [
+        ("upstream_file", &special.upstream_file),
+        ("upstream_repo", &special.upstream_repo),
+        ("upstream_raw", &special.upstream_raw),
+        ("commit", &special.commit),
+        ("tag", &special.tag),
+        ("owner", &special.owner),
+        ("repo_name", &special.repo_name),
+    ]
+    .into_iter()
+    .map(|(key, value)| {
+        (
+            key.to_owned(),
+            minijinja::Value::from(value.as_deref().unwrap_or("")),
+        )
+    })
+    .collect::<BTreeMap<_, _>>();
 
-    special_map.insert(
-        "upstream_file".to_owned(),
-        minijinja::Value::from(special.upstream_file.as_deref().unwrap_or("")),
-    );
+    ctx.insert("special".to_owned(), minijinja::Value::from(special_map));
+}
 
-    special_map.insert(
-        "upstream_repo".to_owned(),
-        minijinja::Value::from(special.upstream_repo.as_deref().unwrap_or("")),
-    );
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::process::Command;
 
-    ctx.insert("special".to_owned(), minijinja::Value::from(special_map));
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::schemes::{self, RoleKind, roles};
+    use crate::templates::Loader;
+
+    const ISOLATED: &str = "THEYMER_ISOLATED_TEST";
+
+    /// Registering custom roles swaps them for the whole process, so tests
+    /// doing it run `body` alone, in a child process.
+    fn isolated(test: &str, body: impl FnOnce()) {
+        if env::var_os(ISOLATED).is_some() {
+            return body();
+        }
+
+        let output = Command::new(env::current_exe().expect("test binary"))
+            .args([test, "--exact", "--test-threads=1"])
+            .env(ISOLATED, "1")
+            .output()
+            .expect("failed to run isolated test");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(
+            output.status.success() && stdout.contains("1 passed"),
+            "isolated test `{test}` failed:\n{stdout}{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    fn render(scheme: &Scheme, template: &str) -> String {
+        let mut env = minijinja::Environment::new();
+        env.add_test("set", Loader::create_set_test);
+
+        let ctx = build(scheme, &Special::default(), &Arc::default(), None)
+            .expect("context builds");
+
+        env.render_str(template, ctx)
+            .unwrap_or_else(|e| panic!("`{template}` failed to render: {e}"))
+    }
+
+    #[test]
+    fn custom_roles_resolve_and_nest() {
+        isolated(
+            "render::context::tests::custom_roles_resolve_and_nest",
+            || {
+                roles::register(&IndexMap::from([
+                    ("scroll".to_owned(), "bg".to_owned()),
+                    ("vcs.added".to_owned(), "syntax.string".to_owned()),
+                ]))
+                .expect("custom roles register");
+
+                let added =
+                    "vcs.added".parse::<RoleName>().expect("registered");
+                let RoleKind::Optional { base } = added.classify() else {
+                    panic!("custom roles are optional");
+                };
+                assert_eq!(base.as_str(), "syntax.string");
+
+                let content = include_str!("../../schemes/cutiepro.toml");
+                let scheme =
+                    schemes::parse("cutiepro", content, "cutiepro.toml")
+                        .expect("scheme resolves custom roles");
+
+                assert_eq!(
+                    render(
+                        &scheme,
+                        "{{ scroll.swatch }} {{ vcs.added.swatch }}"
+                    ),
+                    "blackboard sour_apple"
+                );
+                assert_eq!(
+                    render(&scheme, r#"{{ "vcs.added" is set }}"#),
+                    "false"
+                );
+
+                let content =
+                    format!("{content}\n[roles.vcs]\nadded = \"$lemon\"\n");
+                let scheme =
+                    schemes::parse("cutiepro", &content, "cutiepro.toml")
+                        .expect("scheme sets custom roles");
+
+                assert_eq!(render(&scheme, "{{ vcs.added.swatch }}"), "lemon");
+                assert_eq!(
+                    render(&scheme, r#"{{ "vcs.added" is set }}"#),
+                    "true"
+                );
+            },
+        );
+    }
 }
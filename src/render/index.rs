@@ -1,43 +1,71 @@
 use std::path::{Path, PathBuf};
 
+use itertools::Itertools as _;
 use serde::{Deserialize, Serialize};
 
 use crate::output::FileStatus;
+use crate::output::upstream::Special;
 use crate::{Manifest, ManifestEntry, Scheme, manifest};
 
 pub(super) type Index = Manifest<Entry>;
 
+/// What an output is rendered from, as recorded in the index.
+#[derive(Debug)]
+pub(crate) struct Inputs<'a> {
+    pub template: &'a str,
+    pub schemes: Schemes<'a>,
+    /// Covers the template, what it includes, its directives and the
+    /// upstream urls and metadata from the configured providers.
+    pub dependency_hash: String,
+    /// For aggregate templates, covers every scheme.
+    pub scheme_hash: String,
+}
+
+#[derive(Debug)]
+pub(crate) enum Schemes<'a> {
+    One(&'a str),
+    /// Every scheme, for aggregate templates.
+    All(Vec<&'a str>),
+}
+
 impl Index {
     pub(crate) fn check(
         &self,
         path: &Path,
-        scheme: &Scheme,
-        template: &minijinja::Template<'_, '_>,
+        inputs: &Inputs<'_>,
     ) -> anyhow::Result<FileStatus> {
         let Some(entry) = self.get(path) else {
             return Ok(FileStatus::NotTracked);
         };
 
         manifest::check_status(path, &entry.hash, || {
-            Ok(hash_template(template) != entry.template_hash
-                || hash_scheme(scheme)? != entry.scheme_hash)
+            Ok(inputs.dependency_hash != entry.dependency_hash
+                || inputs.scheme_hash != entry.scheme_hash)
         })
     }
 
     pub(crate) fn create_entry(
         path: &Path,
-        template: &minijinja::Template<'_, '_>,
-        scheme: &Scheme,
+        inputs: &Inputs<'_>,
         content: &str,
-    ) -> anyhow::Result<Entry> {
-        Ok(Entry {
+    ) -> Entry {
+        let (scheme, schemes) = match &inputs.schemes {
+            Schemes::One(scheme) => ((*scheme).to_owned(), Vec::new()),
+            Schemes::All(schemes) => (
+                String::new(),
+                schemes.iter().map(|&scheme| scheme.to_owned()).collect(),
+            ),
+        };
+
+        Entry {
             path: path.to_path_buf(),
-            template: template.name().to_owned(),
-            scheme: scheme.name.as_str().to_owned(),
+            template: inputs.template.to_owned(),
+            scheme,
+            schemes,
             hash: manifest::hash(content),
-            template_hash: hash_template(template),
-            scheme_hash: hash_scheme(scheme)?,
-        })
+            dependency_hash: inputs.dependency_hash.clone(),
+            scheme_hash: inputs.scheme_hash.clone(),
+        }
     }
 }
 
@@ -46,9 +74,16 @@ impl Index {
 pub(crate) struct Entry {
     pub path: PathBuf,
     pub template: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub scheme: String,
+    /// The schemes an aggregate template was rendered with.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schemes: Vec<String>,
     pub hash: String,
-    pub template_hash: String,
+    /// Missing from indexes written before it existed, which renders their
+    /// outputs again once.
+    #[serde(default)]
+    pub dependency_hash: String,
     pub scheme_hash: String,
 }
 
@@ -65,12 +100,69 @@ impl ManifestEntry for Entry {
     }
 }
 
-fn hash_template(template: &minijinja::Template<'_, '_>) -> String {
-    manifest::hash(template.source())
+pub(crate) fn hash_dependencies(
+    fingerprint: &str,
+    special: &Special,
+) -> String {
+    let special = [
+        &special.upstream_file,
+        &special.upstream_repo,
+        &special.upstream_raw,
+        &special.commit,
+        &special.tag,
+        &special.owner,
+        &special.repo_name,
+    ]
+    .into_iter()
+    .map(|value| value.as_deref().unwrap_or_default())
+    .join("\0");
+
+    manifest::hash(&format!("{fingerprint}\0{special}"))
 }
 
-fn hash_scheme(scheme: &Scheme) -> anyhow::Result<String> {
+/// Combines the hashes of every scheme an aggregate template renders with.
+pub(crate) fn hash_schemes(hashes: &[(&str, String)]) -> String {
+    let combined = hashes
+        .iter()
+        .map(|(name, hash)| format!("{name}\0{hash}"))
+        .join("\0");
+
+    manifest::hash(&combined)
+}
+
+pub(crate) fn hash_scheme(scheme: &Scheme) -> anyhow::Result<String> {
     let json = serde_json::to_string_pretty(scheme)?;
 
     Ok(manifest::hash(&json))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upstream_urls_change_the_dependency_hash() {
+        let special = Special {
+            upstream_repo: Some("https://example.com/repo".to_owned()),
+            ..Special::default()
+        };
+
+        assert_ne!(
+            hash_dependencies("template", &Special::default()),
+            hash_dependencies("template", &special)
+        );
+        assert_ne!(
+            hash_dependencies("template", &special),
+            hash_dependencies("other", &special)
+        );
+    }
+
+    #[test]
+    fn aggregate_hashes_cover_every_scheme() {
+        let hashes = [("a", "1".to_owned()), ("b", "2".to_owned())];
+        let changed = [("a", "1".to_owned()), ("b", "3".to_owned())];
+
+        assert_ne!(hash_schemes(&hashes), hash_schemes(&changed));
+        assert_ne!(hash_schemes(&hashes), hash_schemes(&hashes[..1]));
+    }
+}
@@ -0,0 +1,306 @@
+//! Templates that render once per swatch, role, group or `extra` item.
+
+use std::result::Result as StdResult;
+
+use indexmap::{IndexSet, indexset};
+use itertools::Itertools as _;
+
+use crate::Scheme;
+use crate::schemes::roles::Group;
+use crate::schemes::{ResolvedFeature, ResolvedRole, RoleName, Swatch};
+use crate::templates::Directives;
+
+pub(crate) const SWATCH_MARKER: &str = "SWATCH";
+const ROLE_MARKER: &str = "ROLE";
+const GROUP_MARKER: &str = "GROUP";
+/// What splits template paths into the tokens an array's marker has to be.
+/// Not `_`, which array names can contain.
+const TOKEN_SEPARATORS: [char; 3] = ['/', '.', '-'];
+
+type Result<T> = StdResult<T, Error>;
+
+#[non_exhaustive]
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum Error {
+    #[error(
+        "template `{template}` can only render once per one kind of item, \
+         but is marked with {}",
+        .markers.iter().map(|m| format!("`{m}`")).join(", ")
+    )]
+    Ambiguous {
+        template: String,
+        markers: Vec<String>,
+    },
+
+    #[error(
+        "template `{template}` renders once per item of `{feature}`, which \
+         isn't an array in scheme `{scheme}`"
+    )]
+    NotAnArray {
+        template: String,
+        feature: String,
+        scheme: String,
+    },
+
+    #[error(
+        "template `{template}` renders more than one output to `{path}` for \
+         scheme `{scheme}`"
+    )]
+    Collision {
+        template: String,
+        path: String,
+        scheme: String,
+    },
+}
+
+/// What a template renders once per.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum Iteration {
+    Swatches,
+    Roles,
+    Groups,
+    /// Items of the named `extra` array.
+    Extra(String),
+}
+
+/// The item a single output is rendered for.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Item<'a> {
+    Swatch(&'a Swatch),
+    Role(&'a RoleName, &'a ResolvedRole),
+    Group(&'a str),
+    /// An item of the named `extra` array.
+    Extra(&'a str, &'a ResolvedRole),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Current<'a> {
+    pub item: Item<'a>,
+    pub index: usize,
+}
+
+impl Iteration {
+    /// How `template_name` fans out, if it does. `features` are the features
+    /// of every scheme, since a scheme missing an array still shouldn't
+    /// render its marker literally.
+    pub(crate) fn detect(
+        template_name: &str,
+        directives: &Directives,
+        features: &IndexSet<String>,
+    ) -> Result<Option<Self>> {
+        let uses = |placeholders: &[&str]| {
+            directives.output.as_ref().is_some_and(|output| {
+                placeholders.iter().any(|&name| output.uses(name))
+            })
+        };
+
+        let mut found = indexset![];
+
+        if template_name.contains(SWATCH_MARKER)
+            || uses(&["swatch", "swatch_ascii"])
+        {
+            found.insert(Self::Swatches);
+        }
+        if template_name.contains(ROLE_MARKER) || uses(&["role"]) {
+            found.insert(Self::Roles);
+        }
+        if template_name.contains(GROUP_MARKER) || uses(&["group"]) {
+            found.insert(Self::Groups);
+        }
+        found.extend(
+            features
+                .iter()
+                .filter(|name| has_token(template_name, &name.to_uppercase()))
+                .map(|name| Self::Extra(name.clone())),
+        );
+
+        if found.len() > 1 {
+            return Err(Error::Ambiguous {
+                template: template_name.to_owned(),
+                markers: found.iter().map(Self::marker).collect(),
+            });
+        }
+
+        Ok(found.pop())
+    }
+
+    /// What marks the iteration in template paths.
+    #[must_use]
+    pub(crate) fn marker(&self) -> String {
+        match self {
+            Self::Swatches => SWATCH_MARKER.to_owned(),
+            Self::Roles => ROLE_MARKER.to_owned(),
+            Self::Groups => GROUP_MARKER.to_owned(),
+            Self::Extra(name) => name.to_uppercase(),
+        }
+    }
+
+    /// The context variable holding the current item.
+    #[must_use]
+    pub(crate) const fn variable(&self) -> &'static str {
+        match self {
+            Self::Swatches => "swatch",
+            Self::Roles => "role",
+            Self::Groups => "group",
+            Self::Extra(_) => "item",
+        }
+    }
+
+    /// Every item `scheme` has. Schemes without the array have none.
+    pub(crate) fn items<'a>(
+        &'a self,
+        scheme: &'a Scheme,
+        template_name: &str,
+    ) -> Result<Vec<Item<'a>>> {
+        Ok(match self {
+            Self::Swatches => scheme.palette.iter().map(Item::Swatch).collect(),
+            Self::Roles => scheme
+                .resolved_roles
+                .iter()
+                .map(|(name, role)| Item::Role(name, role))
+                .collect(),
+            Self::Groups => scheme
+                .resolved_roles
+                .keys()
+                .filter_map(|name| match name.group() {
+                    Group::Named(group) => Some(group),
+                    Group::Root => None,
+                })
+                .unique()
+                .map(Item::Group)
+                .collect(),
+            Self::Extra(feature) => {
+                match scheme
+                    .resolved_extra
+                    .as_ref()
+                    .and_then(|extra| extra.features.get(feature))
+                {
+                    Some(ResolvedFeature::List(list)) => list
+                        .iter()
+                        .map(|role| Item::Extra(feature, role))
+                        .collect(),
+                    Some(ResolvedFeature::Map(_)) => {
+                        return Err(Error::NotAnArray {
+                            template: template_name.to_owned(),
+                            feature: feature.clone(),
+                            scheme: scheme.name.as_str().to_owned(),
+                        });
+                    }
+                    None => Vec::new(),
+                }
+            }
+        })
+    }
+}
+
+impl Current<'_> {
+    #[must_use]
+    pub(crate) fn marker(&self) -> String {
+        match self.item {
+            Item::Swatch(_) => SWATCH_MARKER.to_owned(),
+            Item::Role(..) => ROLE_MARKER.to_owned(),
+            Item::Group(_) => GROUP_MARKER.to_owned(),
+            Item::Extra(feature, _) => feature.to_uppercase(),
+        }
+    }
+
+    /// `path` with the marker replaced by the key. Arrays' markers are only
+    /// replaced where they're a whole token, like they're detected.
+    #[must_use]
+    pub(crate) fn fill(&self, path: &str) -> String {
+        let (marker, key) = (self.marker(), self.key());
+
+        match self.item {
+            Item::Extra(..) => path
+                .split_inclusive(TOKEN_SEPARATORS)
+                .map(|piece| {
+                    let token = piece.trim_end_matches(TOKEN_SEPARATORS);
+                    if token == marker {
+                        piece.replacen(token, &key, 1)
+                    } else {
+                        piece.to_owned()
+                    }
+                })
+                .collect(),
+            _ => path.replace(&marker, &key),
+        }
+    }
+
+    /// What replaces the marker in output paths.
+    #[must_use]
+    pub(crate) fn key(&self) -> String {
+        match self.item {
+            Item::Swatch(swatch) => swatch.name.as_str().to_owned(),
+            Item::Role(name, _) => name.as_str().to_owned(),
+            Item::Group(group) => group.to_owned(),
+            Item::Extra(..) => self.index.to_string(),
+        }
+    }
+}
+
+fn has_token(path: &str, token: &str) -> bool {
+    path.split(TOKEN_SEPARATORS).any(|part| part == token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markers_are_detected() {
+        let directives = Directives::default();
+        let features =
+            IndexSet::from(["rainbow".to_owned(), "heading_levels".to_owned()]);
+
+        let detect = |name: &str| {
+            Iteration::detect(name, &directives, &features)
+                .map_err(|err| err.to_string())
+        };
+
+        assert_eq!(detect("ports/fish/SCHEME.theme.jinja"), Ok(None));
+        assert_eq!(
+            detect("assets/circles/SWATCH.svg.jinja"),
+            Ok(Some(Iteration::Swatches))
+        );
+        assert_eq!(detect("roles/ROLE.txt.jinja"), Ok(Some(Iteration::Roles)));
+        assert_eq!(detect("GROUP/colors.jinja"), Ok(Some(Iteration::Groups)));
+        assert_eq!(
+            detect("RAINBOW.svg.jinja"),
+            Ok(Some(Iteration::Extra("rainbow".to_owned())))
+        );
+        assert_eq!(detect("RAINBOWS/colors.jinja"), Ok(None));
+        assert_eq!(detect("ports/TRAINBOW.txt.jinja"), Ok(None));
+        assert_eq!(
+            detect("ports/rainbow-RAINBOW.txt.jinja"),
+            Ok(Some(Iteration::Extra("rainbow".to_owned())))
+        );
+        assert_eq!(
+            detect("headings/HEADING_LEVELS.txt.jinja"),
+            Ok(Some(Iteration::Extra("heading_levels".to_owned())))
+        );
+        assert_eq!(detect("HEADING/LEVELS.txt.jinja"), Ok(None));
+        assert!(matches!(
+            detect("ROLE/SWATCH.svg.jinja"),
+            Err(message) if message.contains("SWATCH")
+        ));
+    }
+
+    #[test]
+    fn array_markers_are_filled_as_whole_tokens() {
+        let role = ResolvedRole {
+            swatch: "coral".to_owned(),
+            ascii: "coral".to_owned(),
+            hex: "#ff7f50".to_owned(),
+            rgb: (255, 127, 80),
+        };
+        let current = Current {
+            item: Item::Extra("heading_levels", &role),
+            index: 2,
+        };
+
+        assert_eq!(
+            current.fill("MY_HEADING_LEVELS/HEADING_LEVELS-x.txt"),
+            "MY_HEADING_LEVELS/2-x.txt"
+        );
+    }
+}
@@ -184,3 +184,49 @@ impl minijinja::value::Object for Color {
         }
     }
 }
+
+/// Metadata text that renders as unicode or ascii depending on the
+/// template's text style.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct Text {
+    name: String,
+    ascii: String,
+    #[serde(skip)]
+    style: Arc<Style>,
+}
+
+impl Text {
+    pub(crate) const fn new(
+        name: String,
+        ascii: String,
+        style: Arc<Style>,
+    ) -> Self {
+        Self { name, ascii, style }
+    }
+}
+
+impl minijinja::value::Object for Text {
+    fn render(self: &Arc<Self>, f: &mut Formatter<'_>) -> FmtResult {
+        let text = match self.style.text {
+            TextStyle::Unicode => &self.name,
+            TextStyle::Ascii => &self.ascii,
+        };
+
+        write!(f, "{text}")
+    }
+
+    fn get_value(
+        self: &Arc<Self>,
+        key: &minijinja::Value,
+    ) -> Option<minijinja::Value> {
+        match key.as_str()? {
+            "name" => Some(minijinja::Value::from(&self.name)),
+            "ascii" => Some(minijinja::Value::from(&self.ascii)),
+            _ => None,
+        }
+    }
+
+    fn enumerate(self: &Arc<Self>) -> Enumerator {
+        Enumerator::Str(&["name", "ascii"])
+    }
+}
@@ -0,0 +1,132 @@
+//! Narrowing a render down to some schemes and templates.
+
+use std::result::Result as StdResult;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+use crate::templates;
+
+type Result<T> = StdResult<T, Error>;
+
+#[non_exhaustive]
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum Error {
+    #[error("invalid pattern `{pattern}`: {src}")]
+    ParsingGlob {
+        pattern: String,
+        src: globset::Error,
+    },
+}
+
+/// Which scheme and template pairs to render. Empty filters match
+/// everything.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Selection {
+    schemes: Option<GlobSet>,
+    templates: Option<GlobSet>,
+    ports: Vec<String>,
+}
+
+impl Selection {
+    pub(crate) fn new(
+        schemes: &[String],
+        templates: &[String],
+        ports: &[String],
+    ) -> Result<Self> {
+        Ok(Self {
+            schemes: glob_set(schemes)?,
+            templates: glob_set(templates)?,
+            ports: ports.to_vec(),
+        })
+    }
+
+    #[must_use]
+    pub(crate) const fn is_everything(&self) -> bool {
+        self.schemes.is_none()
+            && self.templates.is_none()
+            && self.ports.is_empty()
+    }
+
+    /// Whether the template `template` is rendered for the scheme with the
+    /// key `scheme`.
+    #[must_use]
+    pub(crate) fn includes(&self, scheme: &str, template: &str) -> bool {
+        self.schemes.as_ref().is_none_or(|set| set.is_match(scheme))
+            && self
+                .templates
+                .as_ref()
+                .is_none_or(|set| set.is_match(template))
+            && (self.ports.is_empty()
+                || templates::port(template).is_some_and(|port| {
+                    self.ports.iter().any(|selected| selected == port)
+                }))
+    }
+}
+
+fn glob_set(patterns: &[String]) -> Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern).map_err(|src| Error::ParsingGlob {
+            pattern: pattern.clone(),
+            src,
+        })?;
+        builder.add(glob);
+    }
+
+    builder.build().map(Some).map_err(|src| Error::ParsingGlob {
+        pattern: patterns.join(", "),
+        src,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|&value| value.to_owned()).collect()
+    }
+
+    #[test]
+    fn filters_combine() {
+        let selection = Selection::new(
+            &strings(&["cutie*"]),
+            &[],
+            &strings(&["fish", "kitty"]),
+        )
+        .expect("valid patterns");
+
+        assert!(
+            selection.includes("cutiepro", "ports/fish/SCHEME.theme.jinja")
+        );
+        assert!(
+            selection.includes("cutiepro", "ports/kitty/SCHEME.conf.jinja")
+        );
+        assert!(!selection.includes("other", "ports/fish/SCHEME.theme.jinja"));
+        assert!(!selection.includes("cutiepro", "ports/fishy/a.jinja"));
+        assert!(!selection.includes("cutiepro", "assets/circles/a.svg.jinja"));
+        assert!(!selection.is_everything());
+
+        let assets =
+            Selection::new(&[], &[], &strings(&["circles"])).expect("valid");
+        assert!(assets.includes("any", "assets/circles/SWATCH.svg.jinja"));
+    }
+
+    #[test]
+    fn templates_match_by_path() {
+        let selection =
+            Selection::new(&[], &strings(&["assets/**"]), &[]).expect("valid");
+
+        assert!(selection.includes("any", "assets/circles/SWATCH.svg.jinja"));
+        assert!(!selection.includes("any", "ports/fish/SCHEME.theme.jinja"));
+        assert!(Selection::default().includes("any", "anything"));
+        assert!(matches!(
+            Selection::new(&strings(&["[a"]), &[], &[]),
+            Err(Error::ParsingGlob { .. })
+        ));
+    }
+}
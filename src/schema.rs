@@ -0,0 +1,639 @@
+//! JSON Schemas for scheme files and `theymer.toml`.
+
+use clap::ValueEnum;
+use indexmap::IndexSet;
+use serde_json::{Map, Value, json};
+
+use crate::config::Commit;
+use crate::schemes::roles::{self, Group, Kind as RoleKind};
+use crate::schemes::{Appearance, RoleName};
+
+const DRAFT: &str = "http://json-schema.org/draft-07/schema#";
+
+const HEX_PATTERN: &str = "^#([0-9a-fA-F]{3,4}|[0-9a-fA-F]{6}|[0-9a-fA-F]{8})$";
+// names may be any unicode letters, which not every regex dialect supports
+// matching, so these are deliberately loose
+const NAME_PATTERN: &str = r"^[^\s.$]+$";
+const SWATCH_REFERENCE_PATTERN: &str = r"^\$[^\s.$]+$";
+
+/// What the built-in root roles and groups are for.
+const DESCRIPTIONS: &[(&str, &str)] = &[
+    ("bg", "Main background."),
+    ("fg", "Main text."),
+    ("toolbar", "Status bars, tab bars and other chrome."),
+    ("select", "Selected text and items."),
+    ("accent", "Highlights that draw attention, like the cursor."),
+    ("inactive", "Disabled or unfocused elements."),
+    ("focus", "The focused element."),
+    ("guide", "Guides, rulers, line numbers and whitespace."),
+    ("match", "Search matches and matching brackets."),
+    ("error", "Error diagnostics."),
+    ("warning", "Warning diagnostics."),
+    ("info", "Informational diagnostics."),
+    ("hint", "Hints and inlay text."),
+    ("debug", "Debugger state."),
+    ("mode", "Editor modes, like in modal editors."),
+    ("syntax", "Syntax highlighting."),
+    ("diff", "Added, removed and changed lines."),
+    ("markup", "Markdown and other markup."),
+    ("ansi", "The 16 terminal colors."),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Kind {
+    /// Scheme files
+    Scheme,
+    /// `theymer.toml`
+    Config,
+}
+
+#[must_use]
+pub(crate) fn generate(kind: Kind) -> Value {
+    match kind {
+        Kind::Scheme => scheme(),
+        Kind::Config => config(),
+    }
+}
+
+fn scheme() -> Value {
+    let role_names = roles::iter().map(|r| r.as_str()).collect::<Vec<_>>();
+
+    json!({
+        "$schema": DRAFT,
+        "title": "theymer scheme",
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["palette", "roles"],
+        "properties": {
+            "scheme": {
+                "description": "Display name [default: the file name].",
+                "type": "string",
+                "pattern": NAME_PATTERN,
+            },
+            "scheme_ascii": {
+                "description": "Ascii name [default: generated from `scheme`].",
+                "type": "string",
+                "pattern": "^[A-Za-z0-9_-]+$",
+            },
+            "meta": meta(),
+            "palette": {
+                "description": "Named colors that roles refer to as `$name`.",
+                "type": "object",
+                "propertyNames": { "pattern": NAME_PATTERN },
+                "additionalProperties": { "$ref": "#/definitions/swatch" },
+            },
+            "roles": roles(),
+            "extra": features(),
+            "features": features(),
+        },
+        "definitions": {
+            "hex": {
+                "type": "string",
+                "pattern": HEX_PATTERN,
+            },
+            "swatch": {
+                "oneOf": [
+                    { "$ref": "#/definitions/hex" },
+                    {
+                        "type": "object",
+                        "additionalProperties": false,
+                        "required": ["hex"],
+                        "properties": {
+                            "hex": { "$ref": "#/definitions/hex" },
+                            "ascii": {
+                                "description": "Ascii name of the swatch.",
+                                "type": "string",
+                                "pattern": "^[A-Za-z0-9_-]+$",
+                            },
+                        },
+                    },
+                ],
+            },
+            "reference": {
+                "description": "A swatch (`$name`) or another role.",
+                "type": "string",
+                "anyOf": [
+                    { "pattern": SWATCH_REFERENCE_PATTERN },
+                    { "enum": role_names },
+                ],
+            },
+            "text": {
+                "oneOf": [
+                    { "type": "string" },
+                    {
+                        "type": "object",
+                        "additionalProperties": false,
+                        "required": ["name"],
+                        "properties": {
+                            "name": { "type": "string" },
+                            "ascii": { "type": "string" },
+                        },
+                    },
+                ],
+            },
+        },
+    })
+}
+
+fn meta() -> Value {
+    let text = |description: &str| {
+        json!({ "description": description, "$ref": "#/definitions/text" })
+    };
+    let ascii = |field: &str| {
+        json!({
+            "description": format!("Ascii fallback for `{field}`."),
+            "type": "string",
+            "deprecated": true,
+        })
+    };
+
+    json!({
+        "description": "Descriptive metadata.",
+        "type": "object",
+        "additionalProperties": false,
+        "properties": {
+            "author": text("Author of the scheme."),
+            "author_ascii": ascii("author"),
+            "license": text("License of the scheme."),
+            "license_ascii": ascii("license"),
+            "blurb": text("Short description of the scheme."),
+            "blurb_ascii": ascii("blurb"),
+            "authors": {
+                "type": "array",
+                "items": {
+                    "oneOf": [
+                        { "type": "string" },
+                        {
+                            "type": "object",
+                            "additionalProperties": false,
+                            "required": ["name"],
+                            "properties": {
+                                "name": { "$ref": "#/definitions/text" },
+                                "url": { "type": "string", "format": "uri" },
+                                "email": {
+                                    "type": "string",
+                                    "format": "email",
+                                },
+                            },
+                        },
+                    ],
+                },
+            },
+            "homepage": { "type": "string", "format": "uri" },
+            "version": { "type": "string" },
+            "upstream_original": {
+                "description": "Where the scheme was ported from.",
+                "type": "string",
+            },
+            "appearance": {
+                "description": "Overrides the appearance detected from `bg` \
+                                and `fg`.",
+                "enum": [
+                    Appearance::Light.to_string(),
+                    Appearance::Dark.to_string(),
+                ],
+            },
+            "custom": {
+                "description": "Arbitrary metadata, passed to templates as is.",
+                "type": "object",
+                "properties": {
+                    "variant": {
+                        "description": "Fills in `{variant}` in output paths \
+                                        instead of the appearance, e.g. \
+                                        `frappe`. Can't contain `/`.",
+                        "type": "string",
+                    },
+                },
+            },
+        },
+    })
+}
+
+fn roles() -> Value {
+    let mut root = Map::new();
+    let mut groups: Map<String, Value> = Map::new();
+
+    for role in roles::iter() {
+        let group = role.group();
+        let key = match group {
+            Group::Root => role.as_str(),
+            Group::Named(name) => role
+                .as_str()
+                .strip_prefix(name)
+                .and_then(|key| key.strip_prefix('.'))
+                .unwrap_or(role.as_str()),
+        };
+
+        let schema = json!({
+            "description": describe(role),
+            "$ref": "#/definitions/reference",
+        });
+
+        match group {
+            Group::Root => {
+                root.insert(key.to_owned(), schema);
+            }
+            Group::Named(name) => {
+                let table = groups.entry(name).or_insert_with(|| {
+                    json!({
+                        "description": description(name).unwrap_or_default(),
+                        "type": "object",
+                        "additionalProperties": false,
+                        "required": [],
+                        "properties": {},
+                    })
+                });
+
+                if matches!(role.classify(), RoleKind::Base(_)) {
+                    push(table, "required", key.into());
+                }
+                insert(table, key, schema);
+            }
+        }
+    }
+
+    let required = roles::base()
+        .map(|role| match role.group() {
+            Group::Root => role.as_str(),
+            Group::Named(name) => name,
+        })
+        .collect::<IndexSet<_>>();
+
+    root.extend(groups);
+
+    json!({
+        "description": "Roles mapped to swatches or other roles.",
+        "type": "object",
+        "additionalProperties": false,
+        "required": required,
+        "properties": root,
+    })
+}
+
+fn features() -> Value {
+    let reference = json!({ "$ref": "#/definitions/reference" });
+
+    json!({
+        "description": "Named lists or tables of swatches and roles, like \
+                        `rainbow`.",
+        "type": "object",
+        "additionalProperties": {
+            "oneOf": [
+                { "type": "array", "items": reference },
+                { "type": "object", "additionalProperties": reference },
+            ],
+        },
+    })
+}
+
+fn config() -> Value {
+    let path = |description: &str, default: &str| {
+        json!({
+            "description": description,
+            "type": "string",
+            "default": default,
+        })
+    };
+    let url_template = |description: &str| {
+        json!({
+            "description": format!(
+                "{description}, with `{{host}}`, `{{owner}}`, `{{repo}}`, \
+                 `{{ref}}`, `{{sha}}` and `{{file}}` placeholders, which are \
+                 percent-encoded."
+            ),
+            "type": "string",
+        })
+    };
+
+    json!({
+        "$schema": DRAFT,
+        "title": "theymer project",
+        "type": "object",
+        "additionalProperties": false,
+        "properties": {
+            "strip_directives": {
+                "description": "Lines to remove from rendered output, as \
+                                lists of tokens.",
+                "type": "array",
+                "items": { "type": "array", "items": { "type": "string" } },
+            },
+            "dirs": {
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "schemes": path("Scheme files.", "schemes"),
+                    "templates": path("Templates.", "templates"),
+                    "render": path("Rendered output.", "render"),
+                },
+            },
+            "roles": {
+                "description": "Custom roles mapped to the role they fall \
+                                back to, or groups of them.",
+                "type": "object",
+                "additionalProperties": {
+                    "oneOf": [
+                        { "type": "string" },
+                        {
+                            "type": "object",
+                            "additionalProperties": { "type": "string" },
+                        },
+                    ],
+                },
+            },
+            "providers": {
+                "description": "Git hosts and how to link to files on them.",
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "additionalProperties": false,
+                    "required": ["host"],
+                    "properties": {
+                        "host": { "type": "string" },
+                        "blob_path": url_template("Link to a file"),
+                        "raw_path": url_template("Link to raw file content"),
+                        "branch": {
+                            "description": "Branch to link to [default: the \
+                                            current branch].",
+                            "type": "string",
+                        },
+                    },
+                },
+            },
+            "template_sources": template_sources(),
+            "builtin_templates": {
+                "description": "Render the templates bundled with theymer \
+                                under the project's own and its template \
+                                sources.",
+                "type": "boolean",
+                "default": true,
+            },
+            "commit": commit(),
+            "upstream": upstream(),
+        },
+    })
+}
+
+fn template_sources() -> Value {
+    json!({
+        "description": "Template directories to fall back to, in \
+                        order of precedence.",
+        "type": "array",
+        "items": {
+            "oneOf": [
+                {
+                    "type": "object",
+                    "additionalProperties": false,
+                    "required": ["path"],
+                    "properties": {
+                        "path": {
+                            "description": "Local directory.",
+                            "type": "string",
+                        },
+                        "dir": {
+                            "description": "Templates inside it.",
+                            "type": "string",
+                        },
+                    },
+                },
+                {
+                    "type": "object",
+                    "additionalProperties": false,
+                    "required": ["git"],
+                    "properties": {
+                        "git": {
+                            "description": "Repository url.",
+                            "type": "string",
+                        },
+                        "ref": {
+                            "description": "Branch, tag or commit \
+                                            [default: the default \
+                                            branch].",
+                            "type": "string",
+                        },
+                        "dir": {
+                            "description": "Templates inside it.",
+                            "type": "string",
+                        },
+                    },
+                },
+            ],
+        },
+    })
+}
+
+fn commit() -> Value {
+    let expanded = "`{schemes}`, `{ports}` and `{version}` are filled in.";
+
+    json!({
+        "description": "How `--commit` commits rendered files in their git \
+                        repos.",
+        "type": "object",
+        "additionalProperties": false,
+        "properties": {
+            "message": {
+                "description": format!("Commit message. {expanded}"),
+                "type": "string",
+                "default": Commit::default().message,
+            },
+            "tag": {
+                "description": format!("Tag for each commit. {expanded}"),
+                "type": "string",
+            },
+            "push": {
+                "description": "Remote to push commits and tags to.",
+                "type": "string",
+            },
+        },
+    })
+}
+
+fn upstream() -> Value {
+    let remote = json!({
+        "description": "Git remote to take the url from [default: \
+                        `origin`, or the first remote].",
+        "type": "string",
+    });
+    let branch = json!({
+        "description": "Branch to link to [default: the remote's default \
+                        branch].",
+        "type": "string",
+    });
+
+    json!({
+        "description": "Where upstream urls of rendered files point, \
+                        overriding what's detected from their git repos.",
+        "type": "object",
+        "additionalProperties": false,
+        "properties": {
+            "repos": {
+                "description": "By directory, relative to the project \
+                                root, like `render/cutiepro`.",
+                "type": "object",
+                "additionalProperties": {
+                    "type": "object",
+                    "additionalProperties": false,
+                    "properties": {
+                        "url": {
+                            "description": "Repository url, for \
+                                            directories that aren't git \
+                                            repos.",
+                            "type": "string",
+                        },
+                        "remote": remote,
+                        "branch": branch,
+                    },
+                },
+            },
+            "schemes": {
+                "description": "By scheme name. Takes precedence over \
+                                `repos`.",
+                "type": "object",
+                "additionalProperties": {
+                    "type": "object",
+                    "additionalProperties": false,
+                    "properties": {
+                        "remote": remote,
+                        "branch": branch,
+                    },
+                },
+            },
+        },
+    })
+}
+
+/// What `role` is for and what it falls back to.
+#[must_use]
+pub(crate) fn describe(role: RoleName) -> String {
+    let own = description(role.as_str());
+
+    match role.classify() {
+        RoleKind::Base(_) => own.unwrap_or("Required.").to_owned(),
+        RoleKind::Optional { base } => {
+            let fallback = format!("Falls back to `{base}`.");
+
+            own.map_or_else(
+                || fallback.clone(),
+                |own| format!("{own} {fallback}"),
+            )
+        }
+    }
+}
+
+fn description(name: &str) -> Option<&'static str> {
+    DESCRIPTIONS
+        .iter()
+        .find(|&&(key, _)| key == name)
+        .map(|&(_, description)| description)
+}
+
+fn insert(table: &mut Value, key: &str, value: Value) {
+    if let Some(properties) =
+        table.get_mut("properties").and_then(Value::as_object_mut)
+    {
+        properties.insert(key.to_owned(), value);
+    }
+}
+
+fn push(table: &mut Value, field: &str, value: Value) {
+    if let Some(items) = table.get_mut(field).and_then(Value::as_array_mut) {
+        items.push(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::result::Result as StdResult;
+
+    use serde::de::{self, Deserialize as _, Deserializer, Visitor};
+
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn every_role_has_a_key() {
+        let schema = generate(Kind::Scheme);
+        let roles = &schema["properties"]["roles"]["properties"];
+
+        for role in roles::iter() {
+            let path = role.as_str().split('.').collect::<Vec<_>>();
+            let found = match path.as_slice() {
+                [key] => roles.get(key),
+                [group, key] => roles
+                    .get(group)
+                    .and_then(|group| group["properties"].get(key)),
+                _ => None,
+            };
+
+            assert!(found.is_some(), "`{role}` missing from schema");
+        }
+    }
+
+    #[test]
+    fn base_roles_are_required() {
+        let schema = generate(Kind::Scheme);
+        let roles = &schema["properties"]["roles"];
+
+        let required = |value: &Value| {
+            value["required"]
+                .as_array()
+                .map(|items| items.iter().filter_map(Value::as_str).count())
+                .unwrap_or_default()
+        };
+
+        assert!(required(roles) > 0);
+        assert!(required(&roles["properties"]["syntax"]) > 0);
+        assert!(
+            roles["properties"]["bg_alt"]["description"]
+                .as_str()
+                .is_some_and(|d| d.contains("`bg`"))
+        );
+    }
+
+    /// Records the fields a derived `Deserialize` asks for.
+    struct Fields<'a>(&'a mut Vec<&'static str>);
+
+    impl<'de> Deserializer<'de> for Fields<'_> {
+        type Error = de::value::Error;
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str
+            string bytes byte_buf option unit unit_struct newtype_struct seq
+            tuple tuple_struct map enum identifier ignored_any
+        }
+
+        fn deserialize_any<V: Visitor<'de>>(
+            self,
+            _visitor: V,
+        ) -> StdResult<V::Value, Self::Error> {
+            Err(de::Error::custom("expected a struct"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> StdResult<V::Value, Self::Error> {
+            self.0.extend(fields);
+
+            Err(de::Error::custom("only the fields are needed"))
+        }
+    }
+
+    #[test]
+    fn config_schema_covers_every_field() {
+        let schema = generate(Kind::Config);
+
+        let mut fields = Vec::new();
+        Config::deserialize(Fields(&mut fields))
+            .expect_err("only the fields are recorded");
+        assert!(!fields.is_empty());
+
+        for field in fields {
+            assert!(
+                schema["properties"].get(field).is_some(),
+                "`{field}` missing"
+            );
+        }
+    }
+}
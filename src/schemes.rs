@@ -1,31 +1,40 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::result::Result as StdResult;
 use std::{fs, io};
 
 use indexmap::{IndexMap, IndexSet};
-use serde::{Deserialize, Serialize};
-use walkdir::WalkDir;
+use log::warn;
+use serde::Serialize;
+use walkdir::{DirEntry, WalkDir};
 
 use self::names::Validated;
 use crate::Result;
 use crate::extensions::PathExt as _;
 use crate::output::{Ascii, Unicode};
 
+pub(crate) mod derive;
+pub(crate) mod dictionary;
+pub(crate) mod extract;
+pub(crate) mod format;
+pub(crate) mod generate;
+pub(crate) mod meta;
 pub(crate) mod names;
+pub(crate) mod report;
 pub(crate) mod roles;
+pub(crate) mod scaffold;
 pub(crate) mod swatches;
 
+pub(crate) use self::dictionary::Namer;
+pub(crate) use self::meta::{Appearance, Meta};
 pub(crate) use self::names::Error as NameError;
 pub(crate) use self::roles::{
     Error as RoleError, Kind as RoleKind, Name as RoleName,
     Resolved as ResolvedRole, Value as RoleValue,
 };
 pub(crate) use self::swatches::{
-    Error as SwatchError, Name as SwatchName, Swatch,
+    Color, Error as SwatchError, Name as SwatchName, Swatch,
 };
 
-const MAX_META_FIELD_LENGTH: usize = 1000;
-
 pub(crate) type Name = Validated<"scheme", Unicode>;
 pub(crate) type AsciiName = Validated<"scheme", Ascii>;
 
@@ -57,6 +66,28 @@ pub(crate) enum Error {
 
     #[error("failed to read scheme `{path}`: {src}")]
     Reading { path: String, src: io::Error },
+
+    #[error("scheme `{name}` not found")]
+    NotFound { name: String },
+
+    #[error("scheme `{path}` already exists (use `--force` to overwrite it)")]
+    Exists { path: String },
+
+    #[error("failed to write scheme `{path}`: {src}")]
+    Writing { path: String, src: io::Error },
+
+    #[error("failed to decode image `{path}`: {src}")]
+    DecodingImage {
+        path: String,
+        src: Box<image::ImageError>,
+    },
+
+    #[error(
+        "{} not formatted: {}",
+        if .paths.len() > 1 { "schemes are" } else { "scheme is" },
+        .paths.iter().map(|p| format!("`{p}`")).collect::<Vec<_>>().join(", ")
+    )]
+    Unformatted { paths: Vec<String> },
 }
 
 #[non_exhaustive]
@@ -69,6 +100,8 @@ pub(crate) struct Scheme {
     pub name_ascii: AsciiName,
 
     pub meta: Meta,
+    /// `meta.appearance` if declared, otherwise detected from `bg` and `fg`.
+    pub appearance: Appearance,
     pub palette: IndexSet<Swatch>,
 
     #[serde(skip)]
@@ -102,11 +135,13 @@ impl Raw {
             })
             .transpose()?;
         let (scheme, scheme_ascii) = Self::names(&self, fallback_name)?;
+        let appearance = self.appearance(&scheme, &resolved_roles)?;
 
         Ok(Scheme {
             name: scheme,
             name_ascii: scheme_ascii,
             meta: self.meta.clone(),
+            appearance,
             palette: self.palette,
             roles: self.roles,
             resolved_roles,
@@ -115,6 +150,38 @@ impl Raw {
         })
     }
 
+    fn appearance(
+        &self,
+        name: &Name,
+        resolved_roles: &IndexMap<RoleName, ResolvedRole>,
+    ) -> Result<Appearance> {
+        let rgb = |role: &str| -> Result<(u8, u8, u8)> {
+            let role = role.parse::<RoleName>()?;
+
+            resolved_roles.get(&role).map(|r| r.rgb).ok_or_else(|| {
+                crate::Error::InternalBug {
+                    module: "schemes",
+                    reason: format!("base role `{role}` wasn't resolved"),
+                }
+            })
+        };
+
+        let detected = Appearance::detect(rgb("bg")?, rgb("fg")?);
+
+        match self.meta.appearance {
+            Some(declared) if declared != detected => {
+                warn!(
+                    "scheme `{name}` is declared {declared}, but its `bg` and \
+                     `fg` look {detected}"
+                );
+
+                Ok(declared)
+            }
+            Some(declared) => Ok(declared),
+            None => Ok(detected),
+        }
+    }
+
     fn resolve_roles(&self) -> Result<IndexMap<RoleName, ResolvedRole>> {
         let mut resolved_roles = IndexMap::new();
         let mut missing_roles: Vec<String> = Vec::new();
@@ -190,27 +257,47 @@ impl Raw {
         palette: &IndexSet<Swatch>,
         resolved_roles: &IndexMap<RoleName, ResolvedRole>,
     ) -> Result<ResolvedExtra> {
-        let rainbow = extra
-            .rainbow
+        let resolve = |path: String, reference: &String| {
+            let value = RoleValue::parse(reference).map_err(|_src| {
+                crate::Error::Role(RoleError::Undefined(path.clone()))
+            })?;
+
+            Self::resolve_value(
+                &value,
+                palette,
+                resolved_roles,
+                &format!("`{path}`"),
+            )
+        };
+
+        let features = extra
+            .features
             .iter()
-            .enumerate()
-            .map(|(i, s)| {
-                let value = RoleValue::parse(s).map_err(|_src| {
-                    crate::Error::Role(RoleError::Undefined(format!(
-                        "extra.rainbow[{i}]"
-                    )))
-                })?;
+            .map(|(name, feature)| {
+                let resolved = match feature {
+                    Feature::List(references) => ResolvedFeature::List(
+                        references
+                            .iter()
+                            .enumerate()
+                            .map(|(i, r)| resolve(format!("{name}[{i}]"), r))
+                            .collect::<Result<_>>()?,
+                    ),
+                    Feature::Map(references) => ResolvedFeature::Map(
+                        references
+                            .iter()
+                            .map(|(key, r)| {
+                                resolve(format!("{name}.{key}"), r)
+                                    .map(|resolved| (key.clone(), resolved))
+                            })
+                            .collect::<Result<_>>()?,
+                    ),
+                };
 
-                Self::resolve_value(
-                    &value,
-                    palette,
-                    resolved_roles,
-                    &format!("`extra.rainbow[{i}]`"),
-                )
+                Ok((name.clone(), resolved))
             })
-            .collect::<Result<Vec<_>>>()?;
+            .collect::<Result<_>>()?;
 
-        Ok(ResolvedExtra { rainbow })
+        Ok(ResolvedExtra { features })
     }
 
     fn resolved_role_from(swatch: &Swatch) -> ResolvedRole {
@@ -312,41 +399,77 @@ impl Raw {
         Ok(())
     }
 
+    fn parse_features(root: &toml::Table, path: &str) -> Result<Option<Extra>> {
+        match (root.get("extra"), root.get("features")) {
+            (Some(_), Some(_)) => Err(Error::InvalidStructure {
+                path: path.to_owned(),
+                reason: "`extra` and `features` can't both be defined"
+                    .to_owned(),
+            }
+            .into()),
+            (Some(val), None) | (None, Some(val)) => {
+                Self::parse_extra(val, path).map(Some)
+            }
+            (None, None) => Ok(None),
+        }
+    }
+
     fn parse_extra(val: &toml::Value, path: &str) -> Result<Extra> {
-        let table = val.as_table().ok_or_else(|| Error::InvalidStructure {
+        let invalid = |reason: String| Error::InvalidStructure {
             path: path.to_owned(),
-            reason: "`roles` must be a table".to_owned(),
+            reason,
+        };
+
+        let table = val.as_table().ok_or_else(|| {
+            invalid("`extra`/`features` must be a table".to_owned())
         })?;
 
-        let rainbow = match table.get("rainbow") {
-            Some(val) => {
-                let arr =
-                    val.as_array().ok_or_else(|| Error::InvalidStructure {
-                        path: path.to_owned(),
-                        reason: "`extra.rainbow` must be an array".to_owned(),
-                    })?;
+        let reference = |name: String, v: &toml::Value| {
+            v.as_str()
+                .map(ToString::to_string)
+                .ok_or_else(|| invalid(format!("`{name}` must be a string")))
+        };
 
-                arr.iter()
-                    .enumerate()
-                    .map(|(i, v)| {
-                        v.as_str()
-                            .ok_or_else(|| {
-                                Error::InvalidStructure {
-                                    path: path.to_owned(),
-                                    reason: format!(
-                                        "`extra.rainbow[{i}]` must be a string"
-                                    ),
-                                }
-                                .into()
-                            })
-                            .map(ToString::to_string)
-                    })
-                    .collect::<Result<Vec<_>>>()?
+        let mut features = IndexMap::new();
+
+        for (name, val) in table {
+            if roles::RESERVED.contains(&name.as_str())
+                || name.parse::<RoleName>().is_ok()
+                || roles::groups().any(|g| g.as_str() == name)
+            {
+                return Err(invalid(format!(
+                    "feature `{name}` clashes with another name in templates"
+                ))
+                .into());
             }
-            None => Vec::new(),
-        };
 
-        Ok(Extra { rainbow })
+            let feature = if let Some(arr) = val.as_array() {
+                Feature::List(
+                    arr.iter()
+                        .enumerate()
+                        .map(|(i, v)| reference(format!("{name}[{i}]"), v))
+                        .collect::<StdResult<_, _>>()?,
+                )
+            } else if let Some(map) = val.as_table() {
+                Feature::Map(
+                    map.iter()
+                        .map(|(key, v)| {
+                            reference(format!("{name}.{key}"), v)
+                                .map(|r| (key.clone(), r))
+                        })
+                        .collect::<StdResult<_, _>>()?,
+                )
+            } else {
+                return Err(invalid(format!(
+                    "feature `{name}` must be an array or a table"
+                ))
+                .into());
+            };
+
+            features.insert(name.clone(), feature);
+        }
+
+        Ok(Extra { features })
     }
 
     fn parse_palette(
@@ -423,26 +546,44 @@ impl Raw {
     }
 }
 
-#[non_exhaustive]
-#[derive(Debug, Clone, Default, Deserialize, Serialize)]
-pub(crate) struct Meta {
-    pub author: Option<String>,
-    pub author_ascii: Option<String>,
-    pub license: Option<String>,
-    pub license_ascii: Option<String>,
-    pub blurb: Option<String>,
-    pub blurb_ascii: Option<String>,
+/// Named arrays and tables of role or swatch references, from `[extra]` or
+/// `[features]`, like `rainbow` for rainbow brackets.
+#[derive(Debug, Clone, Serialize)]
+#[serde(transparent)]
+pub(crate) struct Extra {
+    pub features: IndexMap<String, Feature>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub(crate) struct Extra {
-    #[serde(default)]
-    pub rainbow: Vec<String>,
+#[non_exhaustive]
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub(crate) enum Feature {
+    List(Vec<String>),
+    Map(IndexMap<String, String>),
 }
 
 #[derive(Debug, Clone, Serialize)]
+#[serde(transparent)]
 pub(crate) struct ResolvedExtra {
-    pub rainbow: Vec<ResolvedRole>,
+    pub features: IndexMap<String, ResolvedFeature>,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub(crate) enum ResolvedFeature {
+    List(Vec<ResolvedRole>),
+    Map(IndexMap<String, ResolvedRole>),
+}
+
+impl ResolvedFeature {
+    #[must_use]
+    pub(crate) fn is_empty(&self) -> bool {
+        match self {
+            Self::List(list) => list.is_empty(),
+            Self::Map(map) => map.is_empty(),
+        }
+    }
 }
 
 pub(crate) fn load(name: &str, path: &Path) -> Result<Scheme> {
@@ -454,8 +595,16 @@ pub(crate) fn load(name: &str, path: &Path) -> Result<Scheme> {
             src,
         })?;
 
+    parse(name, &content, &path_str)
+}
+
+/// Parses scheme `content` that isn't necessarily on disk yet, like an
+/// unsaved editor buffer. `path` is only used in errors.
+pub(crate) fn parse(name: &str, content: &str, path: &str) -> Result<Scheme> {
+    let path_str = path.to_owned();
+
     let root: toml::Table =
-        toml::from_str(&content).map_err(|src| Error::ParsingRaw {
+        toml::from_str(content).map_err(|src| Error::ParsingRaw {
             path: path_str.clone(),
             src: Box::new(src),
         })?;
@@ -500,25 +649,12 @@ pub(crate) fn load(name: &str, path: &Path) -> Result<Scheme> {
         })
         .transpose()?;
 
-    let meta: Meta = root
+    let meta = root
         .get("meta")
-        .map(|v| {
-            v.clone().try_into().map_err(|src| Error::Deserializing {
-                section: "meta".to_owned(),
-                path: path_str.clone(),
-                src: Box::new(src),
-            })
-        })
+        .map(|v| Meta::parse(v, &path_str))
         .transpose()?
         .unwrap_or_default();
 
-    validate_meta_field("author", meta.author.as_ref())?;
-    validate_meta_field("author_ascii", meta.author_ascii.as_ref())?;
-    validate_meta_field("license", meta.license.as_ref())?;
-    validate_meta_field("license_ascii", meta.license_ascii.as_ref())?;
-    validate_meta_field("blurb", meta.blurb.as_ref())?;
-    validate_meta_field("blurb_ascii", meta.blurb_ascii.as_ref())?;
-
     let palette_val =
         root.get("palette").ok_or_else(|| Error::Deserializing {
             section: "palette".to_owned(),
@@ -540,10 +676,7 @@ pub(crate) fn load(name: &str, path: &Path) -> Result<Scheme> {
 
     let roles = Raw::parse_roles(roles_val, &path_str)?;
 
-    let extra = match root.get("extra") {
-        Some(val) => Some(Raw::parse_extra(val, &path_str)?),
-        None => None,
-    };
+    let extra = Raw::parse_features(&root, &path_str)?;
 
     let raw = Raw {
         scheme,
@@ -557,47 +690,46 @@ pub(crate) fn load(name: &str, path: &Path) -> Result<Scheme> {
     raw.into_scheme(name)
 }
 
+pub(crate) fn find(dir: &str) -> Vec<PathBuf> {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(StdResult::ok)
+        .map(DirEntry::into_path)
+        .filter(|path| path.is_toml())
+        .collect()
+}
+
+/// Returns the names of every feature defined by any of `schemes`.
+pub(crate) fn features<'a, I: IntoIterator<Item = &'a Scheme>>(
+    schemes: I,
+) -> IndexSet<String> {
+    schemes
+        .into_iter()
+        .filter_map(|scheme| scheme.extra.as_ref())
+        .flat_map(|extra| extra.features.keys().cloned())
+        .collect()
+}
+
 pub(crate) fn load_all(dir: &str) -> Result<IndexMap<String, Scheme>> {
     let mut schemes = IndexMap::new();
 
-    for entry in WalkDir::new(dir).into_iter().filter_map(StdResult::ok) {
-        let path = entry.path();
-        if path.is_toml() {
-            let name = path
-                .file_stem()
-                .and_then(|stem| stem.to_str())
-                .ok_or_else(|| crate::Error::InternalBug {
-                    module: "schemes",
-                    reason: format!(
-                        "attempted to load scheme with corrupted path `{}`",
-                        path.display(),
-                    ),
-                })?;
-            let scheme = load(name, path)?;
-            schemes.insert(name.to_owned(), scheme);
-        }
+    for path in find(dir) {
+        let name = path.file_stem().and_then(|stem| stem.to_str()).ok_or_else(
+            || crate::Error::InternalBug {
+                module: "schemes",
+                reason: format!(
+                    "attempted to load scheme with corrupted path `{}`",
+                    path.display(),
+                ),
+            },
+        )?;
+        let scheme = load(name, &path)?;
+        schemes.insert(name.to_owned(), scheme);
     }
 
     Ok(schemes)
 }
 
-fn validate_meta_field(name: &str, value: Option<&String>) -> Result<()> {
-    if let Some(text) = value
-        && text.len() > MAX_META_FIELD_LENGTH
-    {
-        return Err(Error::InvalidMeta {
-            field: name.to_owned(),
-            reason: format!(
-                "too long ({} characters; max is {MAX_META_FIELD_LENGTH})",
-                text.len()
-            ),
-        }
-        .into());
-    }
-
-    Ok(())
-}
-
 #[cfg(test)]
 mod tests {
     use std::collections::BTreeMap;
@@ -689,4 +821,77 @@ mod tests {
             white = "$white"
             "##}
     }
+
+    fn cutiepro_with(extra: &str) -> String {
+        let content = include_str!("../schemes/cutiepro.toml");
+        let start = content.find("[extra]").expect("cutiepro has `[extra]`");
+
+        format!("{}{extra}", content.get(..start).unwrap_or_default())
+    }
+
+    #[test]
+    fn features_resolve_lists_and_maps() {
+        let scheme = scheme_from_toml(
+            "test",
+            &cutiepro_with(indoc! {r#"
+                [features]
+                heading_levels = ["$lemon", "syntax.keyword"]
+                bracket_pairs = { open = "$sky", close = "fg" }
+            "#}),
+        )
+        .unwrap_or_else(|e| panic!("valid features rejected: {e}"));
+
+        let features = &scheme
+            .resolved_extra
+            .as_ref()
+            .expect("features are resolved")
+            .features;
+
+        let Some(ResolvedFeature::List(levels)) =
+            features.get("heading_levels")
+        else {
+            panic!("`heading_levels` isn't a list");
+        };
+        let swatches =
+            levels.iter().map(|r| r.swatch.as_str()).collect::<Vec<_>>();
+        assert_eq!(swatches, ["lemon", "strawberry"]);
+
+        let Some(ResolvedFeature::Map(pairs)) = features.get("bracket_pairs")
+        else {
+            panic!("`bracket_pairs` isn't a map");
+        };
+        assert_eq!(
+            pairs.get("close").map(|r| r.swatch.as_str()),
+            Some("chalk")
+        );
+    }
+
+    #[test]
+    fn features_are_validated() {
+        for extra in [
+            "[features]\nbg = [\"$lemon\"]\n",
+            "[features]\nlevels = [\"$nope\"]\n",
+            "[features]\nlevels = 1\n",
+            "[extra]\na = []\n[features]\nb = []\n",
+        ] {
+            assert!(
+                scheme_from_toml("test", &cutiepro_with(extra)).is_err(),
+                "{extra}"
+            );
+        }
+    }
+
+    #[test]
+    fn declared_appearance_overrides_detection() {
+        let content = include_str!("../schemes/cutiepro.toml");
+        let scheme = scheme_from_toml("test", content).expect("valid scheme");
+
+        assert_eq!(scheme.appearance, Appearance::Dark);
+
+        let declared =
+            content.replacen("[meta]\n", "[meta]\nappearance = \"light\"\n", 1);
+        let scheme = scheme_from_toml("test", &declared).expect("valid scheme");
+
+        assert_eq!(scheme.appearance, Appearance::Light);
+    }
 }
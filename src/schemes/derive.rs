@@ -0,0 +1,427 @@
+//! Light companions for dark schemes.
+
+use std::collections::HashSet;
+
+use palette::Oklch;
+
+use super::format::{self, key as toml_key};
+use super::meta::Text;
+use super::roles::{self, Kind as RoleKind};
+use super::{Color, Feature, RoleName, RoleValue, Scheme, Swatch};
+use crate::Result;
+
+/// Appended to every derived swatch name.
+const SUFFIX: &str = "_light";
+
+/// Base roles that are drawn behind text rather than as text.
+const SURFACES: &[&str] = &["bg", "toolbar", "select"];
+
+const MAX_CHROMA: f32 = 0.4;
+const SEARCH_STEPS: usize = 24;
+
+/// Renders a light scheme derived from `scheme`.
+pub(crate) fn light(scheme: &Scheme, path: &str) -> Result<String> {
+    let bg = scheme
+        .resolved_roles
+        .get(&"bg".parse::<RoleName>()?)
+        .and_then(|bg| scheme.palette.get(bg.swatch.as_str()))
+        .ok_or_else(|| crate::Error::InternalBug {
+            module: "derive",
+            reason: format!("`{}` has no resolved `bg`", scheme.name),
+        })?
+        .color;
+    let range = Range::of(&scheme.palette);
+    let light_bg = range.mirror(bg);
+
+    let foreground = foreground_swatches(scheme);
+
+    let mut lines = meta(scheme);
+
+    lines.push(String::new());
+    lines.push("[palette]".to_owned());
+    for swatch in &scheme.palette {
+        let color = if foreground.contains(swatch.name.as_str()) {
+            keep_contrast(swatch.color, bg, light_bg)
+        } else {
+            range.mirror(swatch.color)
+        };
+
+        lines.push(palette_entry(swatch, color)?);
+    }
+
+    lines.push(String::new());
+    lines.push("[roles]".to_owned());
+    lines.extend(roles::iter().filter_map(|role| {
+        let value = match scheme.roles.get(&role)? {
+            RoleValue::Swatch(name) => format!("${name}{SUFFIX}"),
+            RoleValue::Role(target) => target.to_string(),
+        };
+
+        Some(format!("{role} = {}", toml::Value::from(value)))
+    }));
+
+    if let Some(extra) = &scheme.extra {
+        lines.push(String::new());
+        lines.push("[features]".to_owned());
+        lines.extend(extra.features.iter().map(|(name, feature)| {
+            format!("{} = {}", toml_key(name), self::feature(feature))
+        }));
+    }
+
+    lines.push(String::new());
+
+    format::format(&lines.join("\n"), path, false)
+}
+
+/// The original's meta, pointing `upstream_original` at it. Its
+/// `custom.variant` describes the original, so it's left out.
+fn meta(scheme: &Scheme) -> Vec<String> {
+    let meta = &scheme.meta;
+    let mut lines = vec!["[meta]".to_owned()];
+
+    for (key, text) in [
+        ("author", &meta.author),
+        ("license", &meta.license),
+        ("blurb", &meta.blurb),
+    ] {
+        if let Some(text) = text {
+            lines.push(format!("{key} = {}", self::text(text)));
+        }
+    }
+
+    if !meta.authors.is_empty() {
+        let authors = meta
+            .authors
+            .iter()
+            .map(|author| {
+                let mut table = toml::Table::new();
+                table.insert("name".to_owned(), text(&author.name));
+                for (key, value) in
+                    [("url", &author.url), ("email", &author.email)]
+                {
+                    if let Some(value) = value {
+                        table.insert(key.to_owned(), value.as_str().into());
+                    }
+                }
+
+                toml::Value::Table(table)
+            })
+            .collect::<Vec<_>>();
+
+        lines.push(format!("authors = {}", toml::Value::Array(authors)));
+    }
+
+    for (key, value) in
+        [("homepage", &meta.homepage), ("version", &meta.version)]
+    {
+        if let Some(value) = value {
+            lines
+                .push(format!("{key} = {}", toml::Value::from(value.as_str())));
+        }
+    }
+
+    lines.push(format!(
+        "upstream_original = {}",
+        toml::Value::from(scheme.name.as_str())
+    ));
+    lines.push("appearance = \"light\"".to_owned());
+
+    let custom = meta
+        .custom
+        .iter()
+        .filter(|(key, _)| key.as_str() != "variant")
+        .map(|(key, value)| format!("{} = {value}", toml_key(key)))
+        .collect::<Vec<_>>();
+    if !custom.is_empty() {
+        lines.push(String::new());
+        lines.push("[meta.custom]".to_owned());
+        lines.extend(custom);
+    }
+
+    lines
+}
+
+/// A plain string if the ascii fallback is the generated one, otherwise a
+/// `{ name, ascii }` table.
+fn text(text: &Text) -> toml::Value {
+    if deunicode::deunicode(&text.name) == text.ascii {
+        return text.name.as_str().into();
+    }
+
+    let mut table = toml::Table::new();
+    table.insert("name".to_owned(), text.name.as_str().into());
+    table.insert("ascii".to_owned(), text.ascii.as_str().into());
+
+    toml::Value::Table(table)
+}
+
+fn palette_entry(swatch: &Swatch, color: Color) -> Result<String> {
+    let name = toml_key(&format!("{}{SUFFIX}", swatch.name));
+
+    // only spell out the ascii name if it differs from the generated one
+    if swatch.name.to_ascii()?.as_str() == swatch.ascii.as_str() {
+        Ok(format!("{name} = \"{}\"", color.hex()))
+    } else {
+        Ok(format!(
+            "{name} = {{ hex = \"{}\", ascii = \"{}{SUFFIX}\" }}",
+            color.hex(),
+            swatch.ascii
+        ))
+    }
+}
+
+fn feature(feature: &Feature) -> toml::Value {
+    let reference = |value: &String| {
+        if value.starts_with('$') {
+            format!("{value}{SUFFIX}")
+        } else {
+            value.clone()
+        }
+    };
+
+    match feature {
+        Feature::List(values) => toml::Value::Array(
+            values.iter().map(|value| reference(value).into()).collect(),
+        ),
+        Feature::Map(values) => toml::Value::Table(
+            values
+                .iter()
+                .map(|(key, value)| (key.clone(), reference(value).into()))
+                .collect(),
+        ),
+    }
+}
+
+/// Swatches only used by roles drawn as text on a surface.
+fn foreground_swatches(scheme: &Scheme) -> HashSet<&str> {
+    let (surfaces, foreground): (Vec<_>, Vec<_>) = scheme
+        .resolved_roles
+        .iter()
+        .partition(|(role, _)| is_surface(**role));
+
+    let surfaces = surfaces
+        .into_iter()
+        .map(|(_, resolved)| resolved.swatch.as_str())
+        .collect::<HashSet<_>>();
+
+    foreground
+        .into_iter()
+        .map(|(_, resolved)| resolved.swatch.as_str())
+        .filter(|swatch| !surfaces.contains(swatch))
+        .collect()
+}
+
+fn is_surface(mut role: RoleName) -> bool {
+    while let RoleKind::Optional { base } = role.classify() {
+        role = base;
+    }
+
+    SURFACES.contains(&role.as_str())
+}
+
+/// The span of OKLCH lightness in a palette.
+#[derive(Debug, Clone, Copy)]
+struct Range {
+    darkest: f32,
+    lightest: f32,
+}
+
+impl Range {
+    fn of<'a, I: IntoIterator<Item = &'a Swatch>>(palette: I) -> Self {
+        palette
+            .into_iter()
+            .map(|swatch| swatch.color.oklch().l)
+            .fold(
+                Self {
+                    darkest: 1.0,
+                    lightest: 0.0,
+                },
+                |range, l| Self {
+                    darkest: range.darkest.min(l),
+                    lightest: range.lightest.max(l),
+                },
+            )
+    }
+
+    /// Mirrors lightness within the range, keeping hue and relative chroma,
+    /// so the darkest swatch becomes as light as the lightest one was.
+    fn mirror(self, color: Color) -> Color {
+        let lch = color.oklch();
+
+        with_lightness(lch, self.darkest + self.lightest - lch.l)
+    }
+}
+
+/// Finds the lightest version of `color` that's at least as far from
+/// `light_bg` as `color` was from `dark_bg`.
+fn keep_contrast(color: Color, dark_bg: Color, light_bg: Color) -> Color {
+    let target = color.contrast(dark_bg);
+    let lch = color.oklch();
+
+    let (mut darker, mut lighter) = (0.0, light_bg.oklch().l);
+
+    for _ in 0..SEARCH_STEPS {
+        let l = f32::midpoint(darker, lighter);
+
+        if with_lightness(lch, l).contrast(light_bg) >= target {
+            darker = l;
+        } else {
+            lighter = l;
+        }
+    }
+
+    with_lightness(lch, darker)
+}
+
+fn with_lightness(lch: Oklch, l: f32) -> Color {
+    let l = l.clamp(0.0, 1.0);
+
+    let relative =
+        (lch.chroma / max_chroma(lch, lch.l).max(f32::EPSILON)).min(1.0);
+    let chroma = relative * max_chroma(lch, l);
+
+    let target = Oklch::new(l, chroma, lch.hue);
+
+    // the chroma bound is found by search, so nudge back into gamut if
+    // rounding pushed it just outside
+    Color::from_oklch(target)
+        .or_else(|| Color::from_oklch(Oklch::new(l, 0.0, lch.hue)))
+        .unwrap_or_else(|| gray(l))
+}
+
+pub(crate) fn gray(l: f32) -> Color {
+    let level = (l.clamp(0.0, 1.0) * 255.0).round();
+    #[expect(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        reason = "clamped to 0..=255"
+    )]
+    let level = level as u8;
+
+    hex_color::HexColor::rgb(level, level, level).into()
+}
+
+/// Most saturated chroma at lightness `l` that's still inside sRGB.
+fn max_chroma(lch: Oklch, l: f32) -> f32 {
+    let (mut inside, mut outside) = (0.0, MAX_CHROMA);
+
+    for _ in 0..SEARCH_STEPS {
+        let chroma = f32::midpoint(inside, outside);
+
+        if Color::from_oklch(Oklch::new(l, chroma, lch.hue)).is_some() {
+            inside = chroma;
+        } else {
+            outside = chroma;
+        }
+    }
+
+    inside
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write as _;
+    use std::path::Path;
+
+    use tempfile::NamedTempFile;
+
+    use super::*;
+    use crate::schemes::{self, Appearance};
+
+    /// Loads cutiepro and its derived light scheme.
+    fn cutiepro() -> (Scheme, Scheme) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("schemes")
+            .join("cutiepro.toml");
+        let scheme =
+            schemes::load("cutiepro", &path).expect("valid repo scheme");
+
+        let content = light(&scheme, "test").expect("derivable scheme");
+        let mut temp =
+            NamedTempFile::new().expect("failed to create temp file");
+        temp.write_all(content.as_bytes())
+            .expect("failed to write temp file");
+
+        let derived = schemes::load("cutiepro-light", temp.path())
+            .unwrap_or_else(|e| panic!("invalid derived scheme: {e}"));
+
+        (scheme, derived)
+    }
+
+    fn color(scheme: &Scheme, role: &str) -> Color {
+        let role = role.parse::<RoleName>().expect("valid role");
+        let resolved = scheme.resolved_roles.get(&role).expect("resolved role");
+
+        scheme
+            .palette
+            .get(resolved.swatch.as_str())
+            .expect("swatch")
+            .color
+    }
+
+    fn contrast(scheme: &Scheme, role: &str) -> f64 {
+        color(scheme, role).contrast(color(scheme, "bg"))
+    }
+
+    #[test]
+    fn derived_scheme_is_light_and_keeps_roles() {
+        let (scheme, derived) = cutiepro();
+
+        assert_eq!(derived.appearance, Appearance::Light);
+        assert_eq!(derived.palette.len(), scheme.palette.len());
+
+        for (role, resolved) in &scheme.resolved_roles {
+            assert_eq!(
+                derived.resolved_roles.get(role).map(|r| r.swatch.as_str()),
+                Some(format!("{}{SUFFIX}", resolved.swatch).as_str()),
+            );
+        }
+    }
+
+    #[test]
+    fn meta_is_carried_over() {
+        let (scheme, derived) = cutiepro();
+
+        assert_eq!(derived.meta.author, scheme.meta.author);
+        assert_eq!(derived.meta.license, scheme.meta.license);
+        assert_eq!(derived.meta.blurb, scheme.meta.blurb);
+        assert_eq!(
+            derived.meta.upstream_original.as_deref(),
+            Some(scheme.name.as_str())
+        );
+    }
+
+    #[test]
+    fn foreground_keeps_its_contrast() {
+        let (scheme, derived) = cutiepro();
+
+        // the new `bg` may not allow the original contrast at all
+        let black = Color::parse("#000").expect("valid color");
+        let reachable = black.contrast(color(&derived, "bg"));
+
+        for role in ["fg", "syntax.keyword", "syntax.comment"] {
+            let (before, after) =
+                (contrast(&scheme, role), contrast(&derived, role));
+
+            assert!(
+                after >= before.min(reachable) - 0.1,
+                "`{role}`: {before} vs {after}"
+            );
+        }
+    }
+
+    #[test]
+    fn mirroring_keeps_hue() {
+        let color = Color::parse("#4b2c85").expect("valid color");
+        let range = Range {
+            darkest: 0.2,
+            lightest: 0.9,
+        };
+        let (before, after) = (color.oklch(), range.mirror(color).oklch());
+
+        assert!(after.l > before.l);
+        assert!(
+            (after.hue.into_degrees() - before.hue.into_degrees()).abs() < 2.0
+        );
+    }
+}
@@ -0,0 +1,327 @@
+//! Built-in color names for swatches that don't have one yet.
+
+use indexmap::IndexSet;
+use palette::color_difference::EuclideanDistance as _;
+
+use super::swatches::{Color, Name as SwatchName, Swatch};
+use crate::{Error, Result};
+
+const SUFFIX_SEPARATOR: char = '_';
+
+const COLORS: &[(&str, &str)] = &[
+    ("alice_blue", "#f0f8ff"),
+    ("amber", "#ffbf00"),
+    ("antique_white", "#faebd7"),
+    ("apricot", "#fbceb1"),
+    ("aquamarine", "#7fffd4"),
+    ("ash", "#b2beb5"),
+    ("azure", "#f0ffff"),
+    ("beige", "#f5f5dc"),
+    ("bisque", "#ffe4c4"),
+    ("black", "#000000"),
+    ("blanched_almond", "#ffebcd"),
+    ("blue", "#0000ff"),
+    ("blue_violet", "#8a2be2"),
+    ("blush", "#de5d83"),
+    ("bone", "#e3dac9"),
+    ("brown", "#a52a2a"),
+    ("burgundy", "#800020"),
+    ("burlywood", "#deb887"),
+    ("cadet_blue", "#5f9ea0"),
+    ("café_au_lait", "#a67b5b"),
+    ("carmine", "#960018"),
+    ("celadon", "#ace1af"),
+    ("cerulean", "#007ba7"),
+    ("champagne", "#f7e7ce"),
+    ("charcoal", "#36454f"),
+    ("chartreuse", "#7fff00"),
+    ("cherry_blossom", "#ffb7c5"),
+    ("chocolate", "#d2691e"),
+    ("cobalt", "#0047ab"),
+    ("copper", "#b87333"),
+    ("coral", "#ff7f50"),
+    ("cornflower_blue", "#6495ed"),
+    ("cornsilk", "#fff8dc"),
+    ("cream", "#fffdd0"),
+    ("crimson", "#dc143c"),
+    ("cyan", "#00ffff"),
+    ("dark_blue", "#00008b"),
+    ("dark_cyan", "#008b8b"),
+    ("dark_goldenrod", "#b8860b"),
+    ("dark_gray", "#a9a9a9"),
+    ("dark_green", "#006400"),
+    ("dark_khaki", "#bdb76b"),
+    ("dark_magenta", "#8b008b"),
+    ("dark_olive_green", "#556b2f"),
+    ("dark_orange", "#ff8c00"),
+    ("dark_orchid", "#9932cc"),
+    ("dark_red", "#8b0000"),
+    ("dark_salmon", "#e9967a"),
+    ("dark_sea_green", "#8fbc8f"),
+    ("dark_slate_blue", "#483d8b"),
+    ("dark_slate_gray", "#2f4f4f"),
+    ("dark_turquoise", "#00ced1"),
+    ("dark_violet", "#9400d3"),
+    ("deep_pink", "#ff1493"),
+    ("deep_sky_blue", "#00bfff"),
+    ("denim", "#1560bd"),
+    ("dim_gray", "#696969"),
+    ("dodger_blue", "#1e90ff"),
+    ("ebony", "#555d50"),
+    ("eggplant", "#614051"),
+    ("emerald", "#50c878"),
+    ("firebrick", "#b22222"),
+    ("flax", "#eedc82"),
+    ("floral_white", "#fffaf0"),
+    ("forest_green", "#228b22"),
+    ("gainsboro", "#dcdcdc"),
+    ("ghost_white", "#f8f8ff"),
+    ("gold", "#ffd700"),
+    ("goldenrod", "#daa520"),
+    ("gray", "#808080"),
+    ("green", "#008000"),
+    ("green_yellow", "#adff2f"),
+    ("honeydew", "#f0fff0"),
+    ("hot_pink", "#ff69b4"),
+    ("indian_red", "#cd5c5c"),
+    ("indigo", "#4b0082"),
+    ("ivory", "#fffff0"),
+    ("jade", "#00a86b"),
+    ("jet", "#343434"),
+    ("khaki", "#f0e68c"),
+    ("lavender", "#e6e6fa"),
+    ("lavender_blush", "#fff0f5"),
+    ("lawn_green", "#7cfc00"),
+    ("lemon", "#fff44f"),
+    ("lemon_chiffon", "#fffacd"),
+    ("licorice", "#1a1110"),
+    ("light_blue", "#add8e6"),
+    ("light_coral", "#f08080"),
+    ("light_cyan", "#e0ffff"),
+    ("light_goldenrod_yellow", "#fafad2"),
+    ("light_gray", "#d3d3d3"),
+    ("light_green", "#90ee90"),
+    ("light_pink", "#ffb6c1"),
+    ("light_salmon", "#ffa07a"),
+    ("light_sea_green", "#20b2aa"),
+    ("light_sky_blue", "#87cefa"),
+    ("light_slate_gray", "#778899"),
+    ("light_steel_blue", "#b0c4de"),
+    ("light_yellow", "#ffffe0"),
+    ("lilac", "#c8a2c8"),
+    ("lime", "#00ff00"),
+    ("lime_green", "#32cd32"),
+    ("linen", "#faf0e6"),
+    ("magenta", "#ff00ff"),
+    ("mahogany", "#c04000"),
+    ("maroon", "#800000"),
+    ("mauve", "#e0b0ff"),
+    ("medium_aquamarine", "#66cdaa"),
+    ("medium_blue", "#0000cd"),
+    ("medium_orchid", "#ba55d3"),
+    ("medium_purple", "#9370db"),
+    ("medium_sea_green", "#3cb371"),
+    ("medium_slate_blue", "#7b68ee"),
+    ("medium_spring_green", "#00fa9a"),
+    ("medium_turquoise", "#48d1cc"),
+    ("medium_violet_red", "#c71585"),
+    ("midnight_blue", "#191970"),
+    ("mint", "#3eb489"),
+    ("mint_cream", "#f5fffa"),
+    ("misty_rose", "#ffe4e1"),
+    ("moccasin", "#ffe4b5"),
+    ("mustard", "#ffdb58"),
+    ("navajo_white", "#ffdead"),
+    ("navy", "#000080"),
+    ("ochre", "#cc7722"),
+    ("old_lace", "#fdf5e6"),
+    ("olive", "#808000"),
+    ("olive_drab", "#6b8e23"),
+    ("onyx", "#353839"),
+    ("orange", "#ffa500"),
+    ("orange_red", "#ff4500"),
+    ("orchid", "#da70d6"),
+    ("pale_goldenrod", "#eee8aa"),
+    ("pale_green", "#98fb98"),
+    ("pale_turquoise", "#afeeee"),
+    ("pale_violet_red", "#db7093"),
+    ("papaya_whip", "#ffefd5"),
+    ("peach", "#ffe5b4"),
+    ("peach_puff", "#ffdab9"),
+    ("periwinkle", "#ccccff"),
+    ("peru", "#cd853f"),
+    ("pink", "#ffc0cb"),
+    ("pistachio", "#93c572"),
+    ("plum", "#dda0dd"),
+    ("powder_blue", "#b0e0e6"),
+    ("purple", "#800080"),
+    ("raspberry", "#e30b5c"),
+    ("rebecca_purple", "#663399"),
+    ("red", "#ff0000"),
+    ("rosy_brown", "#bc8f8f"),
+    ("royal_blue", "#4169e1"),
+    ("ruby", "#e0115f"),
+    ("rust", "#b7410e"),
+    ("saddle_brown", "#8b4513"),
+    ("saffron", "#f4c430"),
+    ("sage", "#bcb88a"),
+    ("salmon", "#fa8072"),
+    ("sandy_brown", "#f4a460"),
+    ("sangria", "#92000a"),
+    ("sapphire", "#0f52ba"),
+    ("scarlet", "#ff2400"),
+    ("sea_green", "#2e8b57"),
+    ("seashell", "#fff5ee"),
+    ("sepia", "#704214"),
+    ("sienna", "#a0522d"),
+    ("silver", "#c0c0c0"),
+    ("sky_blue", "#87ceeb"),
+    ("slate_blue", "#6a5acd"),
+    ("slate_gray", "#708090"),
+    ("snow", "#fffafa"),
+    ("spring_green", "#00ff7f"),
+    ("steel_blue", "#4682b4"),
+    ("tan", "#d2b48c"),
+    ("taupe", "#483c32"),
+    ("teal", "#008080"),
+    ("thistle", "#d8bfd8"),
+    ("tomato", "#ff6347"),
+    ("turquoise", "#40e0d0"),
+    ("umber", "#635147"),
+    ("vermilion", "#e34234"),
+    ("violet", "#ee82ee"),
+    ("wheat", "#f5deb3"),
+    ("white", "#ffffff"),
+    ("white_smoke", "#f5f5f5"),
+    ("wine", "#722f37"),
+    ("yellow", "#ffff00"),
+    ("yellow_green", "#9acd32"),
+];
+
+/// Hands out unique swatch names for unnamed colors.
+///
+/// Names handed out by earlier calls to [`Namer::name`] or marked with
+/// [`Namer::reserve`] are never reused, in either their display or ascii form,
+/// so the result always passes the palette collision checks.
+#[derive(Debug, Default)]
+pub(crate) struct Namer {
+    taken: IndexSet<String>,
+    taken_ascii: IndexSet<String>,
+}
+
+impl Namer {
+    #[must_use]
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn name(&mut self, color: Color) -> Result<Swatch> {
+        let base = nearest(color);
+
+        // at most `taken.len()` candidates can collide, so this always
+        // finds a free one
+        for n in 1..=self.taken.len() + 1 {
+            let candidate = if n == 1 {
+                base.to_owned()
+            } else {
+                format!("{base}{SUFFIX_SEPARATOR}{n}")
+            };
+
+            let name = SwatchName::parse(&candidate)?;
+            let ascii = name.to_ascii()?;
+
+            if self.is_taken(name.as_str(), ascii.as_str()) {
+                continue;
+            }
+
+            let swatch = Swatch { name, color, ascii };
+            self.reserve(&swatch);
+
+            return Ok(swatch);
+        }
+
+        Err(Error::InternalBug {
+            module: "dictionary",
+            reason: format!("ran out of names for `{}`", color.hex()),
+        })
+    }
+
+    /// Marks the names of `swatch` as taken, e.g. for a swatch named by hand.
+    pub(crate) fn reserve(&mut self, swatch: &Swatch) {
+        self.taken.insert(swatch.name.as_str().to_lowercase());
+        self.taken_ascii
+            .insert(swatch.ascii.as_str().to_lowercase());
+    }
+
+    pub(crate) fn is_taken(&self, name: &str, ascii: &str) -> bool {
+        self.taken.contains(&name.to_lowercase())
+            || self.taken_ascii.contains(&ascii.to_lowercase())
+    }
+}
+
+/// Returns the dictionary name perceptually closest to `color`.
+#[must_use]
+pub(crate) fn nearest(color: Color) -> &'static str {
+    let target = color.oklab();
+
+    COLORS
+        .iter()
+        .filter_map(|&(name, hex)| {
+            let lab = Color::parse(hex).ok()?.oklab();
+
+            Some((name, lab.distance_squared(target)))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map_or("color", |(name, _)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn color(hex: &str) -> Color {
+        Color::parse(hex).expect("valid hex")
+    }
+
+    #[test]
+    fn dictionary_is_valid() {
+        for &(name, hex) in COLORS {
+            let parsed = SwatchName::parse(name)
+                .unwrap_or_else(|e| panic!("invalid dictionary name: {e}"));
+            parsed
+                .to_ascii()
+                .unwrap_or_else(|e| panic!("no ascii for `{name}`: {e}"));
+            Color::parse(hex)
+                .unwrap_or_else(|e| panic!("invalid hex for `{name}`: {e}"));
+        }
+    }
+
+    #[test]
+    fn exact_matches_use_their_own_name() {
+        assert_eq!(nearest(color("#ff7f50")), "coral");
+        assert_eq!(nearest(color("#000")), "black");
+    }
+
+    #[test]
+    fn repeated_names_get_suffixes() {
+        let mut namer = Namer::new();
+
+        let first = namer.name(color("#fe7f50")).expect("first name");
+        let second = namer.name(color("#ff7f51")).expect("second name");
+
+        assert_eq!(first.name.as_str(), "coral");
+        assert_eq!(second.name.as_str(), "coral_2");
+    }
+
+    #[test]
+    fn unicode_names_get_ascii_fallbacks() {
+        let mut namer = Namer::new();
+
+        let cafe = namer.name(color("#a67b5b")).expect("name");
+
+        assert_eq!(cafe.name.as_str(), "café_au_lait");
+        assert_eq!(cafe.ascii.as_str(), "cafe_au_lait");
+    }
+}
@@ -0,0 +1,295 @@
+//! Seed colors extracted from an image.
+
+use std::path::Path;
+
+use palette::color_difference::EuclideanDistance as _;
+use palette::{IntoColor as _, Oklab, Oklch, Srgb};
+
+use super::generate::{Seeds, in_gamut};
+use super::{Color, Error};
+use crate::Result;
+
+/// Pixels sampled from an image, spread evenly across it.
+const MAX_SAMPLES: usize = 1 << 14;
+const ITERATIONS: usize = 24;
+/// Clusters less saturated than this are never accents.
+const MIN_ACCENT_CHROMA: f32 = 0.04;
+const SEARCH_STEPS: usize = 24;
+/// Clustering always starts the same way, so extraction is reproducible.
+const CLUSTER_SEED: u64 = 0;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Options {
+    pub clusters: usize,
+    /// Minimum WCAG contrast ratio of `fg` and accents against `bg`.
+    pub min_contrast: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Cluster {
+    center: Oklab,
+    weight: usize,
+}
+
+impl Cluster {
+    fn color(self) -> Color {
+        in_gamut(self.center.into_color())
+    }
+}
+
+/// Picks `bg`, `fg` and accent seeds from the image at `path`.
+pub(crate) fn seeds(path: &Path, options: Options) -> Result<Seeds> {
+    let pixels = sample(path)?;
+
+    let mut clusters = kmeans(&pixels, options.clusters.max(2));
+    clusters.sort_by(|a, b| b.weight.cmp(&a.weight));
+
+    let mut clusters = clusters.into_iter().map(Cluster::color);
+    let bg = clusters.next().ok_or_else(|| Error::InvalidStructure {
+        path: path.display().to_string(),
+        reason: "image has no pixels".to_owned(),
+    })?;
+
+    let rest = clusters.collect::<Vec<_>>();
+
+    let fg = rest
+        .iter()
+        .copied()
+        .max_by(|a, b| a.contrast(bg).total_cmp(&b.contrast(bg)))
+        .unwrap_or(bg);
+
+    let accents = rest
+        .into_iter()
+        .filter(|&color| color != fg)
+        .filter(|color| color.oklch().chroma >= MIN_ACCENT_CHROMA)
+        .map(|color| with_contrast(color, bg, options.min_contrast))
+        .collect();
+
+    Ok(Seeds {
+        bg,
+        fg: with_contrast(fg, bg, options.min_contrast),
+        accents,
+        seed: CLUSTER_SEED,
+    })
+}
+
+fn sample(path: &Path) -> Result<Vec<Oklab>> {
+    let image = image::open(path)
+        .map_err(|src| Error::DecodingImage {
+            path: path.display().to_string(),
+            src: Box::new(src),
+        })?
+        .to_rgb8();
+
+    let total = image.pixels().len();
+    let step = total.div_ceil(MAX_SAMPLES).max(1);
+
+    Ok(image
+        .pixels()
+        .step_by(step)
+        .map(|pixel| {
+            let [r, g, b] = pixel.0;
+
+            Srgb::new(r, g, b).into_format::<f32>().into_color()
+        })
+        .collect())
+}
+
+/// Groups `pixels` into at most `k` clusters, seeded with k-means++.
+fn kmeans(pixels: &[Oklab], k: usize) -> Vec<Cluster> {
+    let mut rng = fastrand::Rng::with_seed(CLUSTER_SEED);
+
+    let Some(&first) = pixels.get(rng.usize(..pixels.len().max(1))) else {
+        return Vec::new();
+    };
+
+    let mut centers = vec![first];
+    while centers.len() < k {
+        let distances = pixels
+            .iter()
+            .map(|pixel| nearest(&centers, *pixel).1)
+            .collect::<Vec<_>>();
+        let total = distances.iter().sum::<f32>();
+
+        if total <= f32::EPSILON {
+            break;
+        }
+
+        let mut target = rng.f32() * total;
+        let next = pixels
+            .iter()
+            .zip(&distances)
+            .find(|&(_, distance)| {
+                target -= distance;
+                target <= 0.0
+            })
+            .map_or(first, |(pixel, _)| *pixel);
+
+        centers.push(next);
+    }
+
+    let mut weights = vec![0; centers.len()];
+    for _ in 0..ITERATIONS {
+        let mut sums = vec![(0.0, 0.0, 0.0); centers.len()];
+        weights.fill(0);
+
+        for pixel in pixels {
+            let (i, _) = nearest(&centers, *pixel);
+
+            if let (Some(sum), Some(weight)) =
+                (sums.get_mut(i), weights.get_mut(i))
+            {
+                sum.0 += pixel.l;
+                sum.1 += pixel.a;
+                sum.2 += pixel.b;
+                *weight += 1;
+            }
+        }
+
+        for ((center, sum), &weight) in
+            centers.iter_mut().zip(sums).zip(&weights)
+        {
+            if weight > 0 {
+                #[expect(clippy::cast_precision_loss, reason = "sample count")]
+                let weight = weight as f32;
+
+                *center =
+                    Oklab::new(sum.0 / weight, sum.1 / weight, sum.2 / weight);
+            }
+        }
+    }
+
+    centers
+        .into_iter()
+        .zip(weights)
+        .filter(|&(_, weight)| weight > 0)
+        .map(|(center, weight)| Cluster { center, weight })
+        .collect()
+}
+
+/// The closest center to `pixel`, with its squared distance.
+fn nearest(centers: &[Oklab], pixel: Oklab) -> (usize, f32) {
+    centers
+        .iter()
+        .map(|center| pixel.distance_squared(*center))
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .unwrap_or((0, f32::INFINITY))
+}
+
+/// Moves `color` away from `bg` in lightness until it has `min` contrast, or
+/// as close as possible.
+fn with_contrast(color: Color, bg: Color, min: f64) -> Color {
+    if color.contrast(bg) >= min {
+        return color;
+    }
+
+    let lch = color.oklch();
+    let at = |l: f32| in_gamut(Oklch::new(l, lch.chroma, lch.hue));
+
+    let mut near = lch.l;
+    let mut far = if bg.oklch().l < 0.5 { 1.0 } else { 0.0 };
+
+    if at(far).contrast(bg) < min {
+        return at(far);
+    }
+
+    for _ in 0..SEARCH_STEPS {
+        let l = f32::midpoint(near, far);
+
+        if at(l).contrast(bg) >= min {
+            far = l;
+        } else {
+            near = l;
+        }
+    }
+
+    at(far)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use image::{Rgb, RgbImage};
+    use tempfile::TempDir;
+
+    use super::*;
+
+    const BG: [u8; 3] = [0x1b, 0x1a, 0x24];
+    const FG: [u8; 3] = [0xf4, 0xed, 0xe6];
+    const ACCENT: [u8; 3] = [0xf7, 0x6f, 0x80];
+
+    /// A mostly dark image with a light stripe and a pink square.
+    fn image(dir: &TempDir, name: &str) -> PathBuf {
+        let image = RgbImage::from_fn(64, 64, |x, y| {
+            if y < 8 {
+                Rgb(FG)
+            } else if x < 16 && y > 48 {
+                Rgb(ACCENT)
+            } else {
+                Rgb(BG)
+            }
+        });
+
+        let path = dir.path().join(name);
+        image.save(&path).expect("writable image");
+
+        path
+    }
+
+    fn options() -> Options {
+        Options {
+            clusters: 4,
+            min_contrast: 4.5,
+        }
+    }
+
+    /// Summed channel difference, from 0 to 3.
+    fn distance(color: Color, rgb: [u8; 3]) -> f32 {
+        let (r, g, b) = color.rgb();
+
+        [r, g, b]
+            .iter()
+            .zip(rgb)
+            .map(|(&a, b)| f32::from(a.abs_diff(b)) / 255.0)
+            .sum()
+    }
+
+    #[test]
+    fn roles_are_picked_from_clusters() {
+        let dir = TempDir::new().expect("temp dir");
+
+        for name in ["wallpaper.png", "wallpaper.jpg"] {
+            let seeds = seeds(&image(&dir, name), options()).expect("seeds");
+
+            assert!(distance(seeds.bg, BG) < 0.1, "{name}: bg");
+            assert!(distance(seeds.fg, FG) < 0.1, "{name}: fg");
+            assert!(
+                seeds.accents.iter().any(|&a| distance(a, ACCENT) < 0.1),
+                "{name}: accents"
+            );
+        }
+    }
+
+    #[test]
+    fn low_contrast_colors_are_adjusted() {
+        let bg = Color::parse("#1b1a24").expect("valid color");
+        let dim = Color::parse("#3a2a30").expect("valid color");
+
+        assert!(with_contrast(dim, bg, 4.5).contrast(bg) >= 4.5);
+    }
+
+    #[test]
+    fn invalid_images_are_rejected() {
+        let dir = TempDir::new().expect("temp dir");
+        let path = dir.path().join("not-an-image.png");
+        fs::write(&path, "nope").expect("writable file");
+
+        assert!(matches!(
+            seeds(&path, options()),
+            Err(crate::Error::Scheme(Error::DecodingImage { .. }))
+        ));
+    }
+}
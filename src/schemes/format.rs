@@ -0,0 +1,746 @@
+//! Canonical, comment-preserving layout for scheme files.
+
+use std::path::Path;
+use std::{fs, mem};
+
+use indexmap::IndexMap;
+use log::{debug, info};
+
+use super::Error;
+use super::roles::{self, Kind as RoleKind, Name as RoleName};
+use crate::Result;
+
+const ROOT_KEYS: &[&str] = &["scheme", "scheme_ascii"];
+const ROLES_TABLE: &str = "roles";
+const SECTION_ORDER: &[&str] =
+    &["meta", "palette", ROLES_TABLE, "extra", "features"];
+const KEY_SEPARATOR: char = '.';
+
+/// What to do with a scheme file that isn't formatted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Mode {
+    /// Only report it.
+    Check,
+    /// Rewrite it in place.
+    Write,
+}
+
+/// Formats the scheme at `path`, returning whether it was (or, in
+/// [`Mode::Check`], would be) changed.
+pub(crate) fn file(path: &Path, stubs: bool, mode: Mode) -> Result<bool> {
+    let path_str = path.display().to_string();
+
+    let content = fs::read_to_string(path).map_err(|src| Error::Reading {
+        path: path_str.clone(),
+        src,
+    })?;
+
+    let formatted = format(&content, &path_str, stubs)?;
+
+    if formatted == content {
+        debug!("formatting unnecessary for `{path_str}`");
+
+        return Ok(false);
+    }
+
+    if mode == Mode::Write {
+        fs::write(path, formatted).map_err(|src| Error::Writing {
+            path: path_str.clone(),
+            src,
+        })?;
+
+        info!("formatted `{path_str}`");
+    }
+
+    Ok(true)
+}
+
+/// Quotes `name` if it can't be used as a bare toml key.
+#[must_use]
+pub(crate) fn key(name: &str) -> String {
+    if name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        name.to_owned()
+    } else {
+        format!("\"{name}\"")
+    }
+}
+
+pub(crate) fn format(content: &str, path: &str, stubs: bool) -> Result<String> {
+    toml::from_str::<toml::Table>(content).map_err(|src| {
+        Error::ParsingRaw {
+            path: path.to_owned(),
+            src: Box::new(src),
+        }
+    })?;
+
+    let (root, tables) = parse(content);
+    let roles = Roles::collect(&tables, path)?;
+
+    let mut blocks = vec![root.render_root()];
+
+    for &table in SECTION_ORDER {
+        if table == ROLES_TABLE {
+            blocks.extend(roles.render(stubs));
+        } else {
+            blocks.extend(
+                tables
+                    .iter()
+                    .filter(|s| s.table() == table)
+                    .map(Section::render),
+            );
+        }
+    }
+
+    blocks.extend(
+        tables
+            .iter()
+            .filter(|s| !SECTION_ORDER.contains(&s.table()))
+            .map(Section::render),
+    );
+
+    let mut formatted = blocks
+        .into_iter()
+        .filter(|b| !b.is_empty())
+        .map(|b| b.join("\n"))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    formatted.push('\n');
+
+    Ok(formatted)
+}
+
+#[derive(Debug, Default)]
+struct Section {
+    header: Option<Header>,
+    entries: Vec<Entry>,
+    trailing: Vec<String>,
+}
+
+impl Section {
+    fn table(&self) -> &str {
+        self.header
+            .as_ref()
+            .and_then(|h| h.path.first())
+            .map_or("", String::as_str)
+    }
+
+    fn render_root(&self) -> Vec<String> {
+        let mut lines = self.trailing.clone();
+
+        let known = ROOT_KEYS
+            .iter()
+            .filter_map(|&k| self.entries.iter().find(|e| e.key == [k]));
+        let other = self
+            .entries
+            .iter()
+            .filter(|e| !ROOT_KEYS.iter().any(|&k| e.key == [k]));
+
+        for entry in known.chain(other) {
+            entry.render(&mut lines, false);
+        }
+
+        lines
+    }
+
+    fn render(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        if let Some(header) = &self.header {
+            header.render(&mut lines);
+        }
+
+        for (i, entry) in self.entries.iter().enumerate() {
+            entry.render(&mut lines, i > 0);
+        }
+
+        lines.extend(self.trailing.iter().cloned());
+
+        lines
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Header {
+    comments: Vec<String>,
+    line: String,
+    path: Vec<String>,
+}
+
+impl Header {
+    fn render(&self, lines: &mut Vec<String>) {
+        lines.extend(self.comments.iter().cloned());
+        lines.push(self.line.clone());
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    blank_before: bool,
+    comments: Vec<String>,
+    key: Vec<String>,
+    lines: Vec<String>,
+}
+
+impl Entry {
+    fn render(&self, lines: &mut Vec<String>, keep_blank: bool) {
+        if keep_blank && self.blank_before {
+            lines.push(String::new());
+        }
+
+        lines.extend(self.comments.iter().cloned());
+        lines.extend(self.lines.iter().cloned());
+    }
+
+    /// Replaces the entry's key, keeping its value and any trailing comment.
+    fn rekey(&mut self, key: &str) {
+        if let Some(first) = self.lines.first_mut()
+            && let Some(eq) = find_unquoted(first, '=')
+            && let Some(rest) = first.get(eq..)
+        {
+            *first = format!("{key} {rest}");
+        }
+
+        self.key = vec![key.to_owned()];
+    }
+
+    /// Splits an inline table like `syntax = { keyword = "$red" }` into one
+    /// entry per key, the way the loader reads it. Its comments go with the
+    /// first key.
+    fn expand_inline(&self) -> Vec<Self> {
+        let source = self.lines.join("\n");
+        let Some(table) = toml::from_str::<toml::Table>(&source)
+            .ok()
+            .and_then(|root| {
+                self.key
+                    .iter()
+                    .try_fold(toml::Value::Table(root), |value, key| {
+                        value.as_table()?.get(key).cloned()
+                    })
+            })
+            .and_then(|value| value.as_table().cloned())
+        else {
+            return vec![self.clone()];
+        };
+
+        let mut comments = self.comments.clone();
+        if let Some(last) = self.lines.last()
+            && let Some(hash) = find_unquoted(last, '#')
+            && let Some(comment) = last.get(hash..)
+        {
+            comments.push(comment.to_owned());
+        }
+
+        table
+            .into_iter()
+            .enumerate()
+            .map(|(i, (name, value))| Self {
+                blank_before: i == 0 && self.blank_before,
+                comments: if i == 0 {
+                    mem::take(&mut comments)
+                } else {
+                    Vec::new()
+                },
+                key: self.key.iter().cloned().chain([name.clone()]).collect(),
+                lines: vec![format!("{} = {value}", key(&name))],
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Default)]
+struct Roles {
+    set: IndexMap<RoleName, Entry>,
+    placeholders: IndexMap<RoleName, String>,
+    headers: IndexMap<String, Header>,
+    trailing: IndexMap<String, Vec<String>>,
+}
+
+impl Roles {
+    fn collect(tables: &[Section], path: &str) -> Result<Self> {
+        let mut roles = Self::default();
+
+        for section in tables.iter().filter(|s| s.table() == ROLES_TABLE) {
+            let Some(header) = &section.header else {
+                continue;
+            };
+            let prefix = header.path.get(1..).unwrap_or_default();
+
+            for entry in section.entries.iter().flat_map(Entry::expand_inline) {
+                let full_key = prefix
+                    .iter()
+                    .chain(&entry.key)
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(".");
+
+                let role: RoleName = full_key.parse().map_err(|_src| {
+                    Error::InvalidStructure {
+                        path: path.to_owned(),
+                        reason: format!("invalid role name: `{full_key}`"),
+                    }
+                })?;
+
+                let mut entry = entry;
+                entry.rekey(&split_group(role).1);
+
+                roles.set.insert(role, entry);
+            }
+
+            let group = prefix.join(".");
+            roles.headers.insert(group.clone(), header.clone());
+            roles
+                .trailing
+                .entry(group)
+                .or_default()
+                .extend(section.trailing.iter().cloned());
+        }
+
+        roles.extract_placeholders();
+
+        Ok(roles)
+    }
+
+    /// Moves placeholder comments out of the comments they were found in.
+    fn extract_placeholders(&mut self) {
+        let entries = self
+            .set
+            .iter()
+            .map(|(role, entry)| (split_group(*role).0, &entry.comments));
+        let trailing = self.trailing.iter().map(|(g, c)| (g.clone(), c));
+
+        let mut found: IndexMap<(String, String), (RoleName, String)> =
+            IndexMap::new();
+
+        for (group, comments) in entries.chain(trailing) {
+            for comment in comments {
+                if let Some(placeholder) = self.placeholder(&group, comment) {
+                    found.insert((group.clone(), comment.clone()), placeholder);
+                }
+            }
+        }
+
+        let is_placeholder = |group: &str, comment: &String| {
+            found.contains_key(&(group.to_owned(), comment.clone()))
+        };
+
+        for (role, entry) in &mut self.set {
+            let group = split_group(*role).0;
+            entry.comments.retain(|c| !is_placeholder(&group, c));
+        }
+
+        for (group, comments) in &mut self.trailing {
+            comments.retain(|c| !is_placeholder(group, c));
+        }
+
+        self.placeholders = found.into_values().collect();
+    }
+
+    /// Returns the role a placeholder comment stands for, along with its
+    /// canonical (group-relative) text.
+    fn placeholder(
+        &self,
+        group: &str,
+        comment: &str,
+    ) -> Option<(RoleName, String)> {
+        let text = comment.strip_prefix('#')?.trim_start();
+        let word = text
+            .split(|c: char| c.is_whitespace() || c == '=')
+            .next()
+            .filter(|w| !w.is_empty())?;
+
+        let qualified = if group.is_empty() {
+            word.to_owned()
+        } else {
+            format!("{group}{KEY_SEPARATOR}{word}")
+        };
+
+        let role = [qualified.as_str(), word]
+            .into_iter()
+            .find_map(|c| c.parse::<RoleName>().ok())
+            .filter(|role| !self.set.contains_key(role))?;
+
+        let rest = text.strip_prefix(word).unwrap_or_default();
+
+        Some((role, format!("# {}{rest}", split_group(role).1)))
+    }
+
+    fn render(&self, stubs: bool) -> Vec<Vec<String>> {
+        let mut bodies: IndexMap<String, Vec<String>> = IndexMap::new();
+
+        for role in roles::iter() {
+            let (group, key) = split_group(role);
+            let body = bodies.entry(group).or_default();
+
+            if let Some(entry) = self.set.get(&role) {
+                entry.render(body, false);
+            } else if let Some(placeholder) = self.placeholders.get(&role) {
+                body.push(placeholder.clone());
+            } else if stubs
+                && matches!(role.classify(), RoleKind::Optional { .. })
+            {
+                body.push(format!("# {key}"));
+            }
+        }
+
+        bodies
+            .into_iter()
+            .filter_map(|(group, body)| {
+                let header = self.headers.get(&group);
+                let trailing = self.trailing.get(&group);
+
+                if body.is_empty() && header.is_none() {
+                    return None;
+                }
+
+                let mut lines = Vec::new();
+
+                if let Some(header) = header {
+                    header.render(&mut lines);
+                } else if group.is_empty() {
+                    lines.push(format!("[{ROLES_TABLE}]"));
+                } else {
+                    lines
+                        .push(format!("[{ROLES_TABLE}{KEY_SEPARATOR}{group}]"));
+                }
+
+                lines.extend(body);
+                lines.extend(trailing.into_iter().flatten().cloned());
+
+                Some(lines)
+            })
+            .collect()
+    }
+}
+
+fn split_group(role: RoleName) -> (String, String) {
+    let name = role.as_str();
+
+    name.split_once(KEY_SEPARATOR).map_or_else(
+        || (String::new(), name.to_owned()),
+        |(group, key)| (group.to_owned(), key.to_owned()),
+    )
+}
+
+fn parse(content: &str) -> (Section, Vec<Section>) {
+    let mut root = Section::default();
+    let mut tables: Vec<Section> = Vec::new();
+
+    let mut pending: Vec<String> = Vec::new();
+    let mut pending_blank = false;
+    let mut blank_before = false;
+
+    let mut lines = content.lines();
+
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim();
+        let current = tables.last_mut().unwrap_or(&mut root);
+
+        if trimmed.is_empty() {
+            pending_blank = !pending.is_empty();
+            blank_before = true;
+        } else if trimmed.starts_with('#') {
+            if mem::take(&mut pending_blank) {
+                pending.push(String::new());
+            }
+            pending.push(trimmed.to_owned());
+        } else if trimmed.starts_with('[') {
+            let comments = if mem::take(&mut pending_blank) {
+                current.trailing.append(&mut pending);
+                Vec::new()
+            } else {
+                mem::take(&mut pending)
+            };
+
+            tables.push(Section {
+                header: Some(Header {
+                    comments,
+                    line: trimmed.to_owned(),
+                    path: parse_header(trimmed),
+                }),
+                ..Section::default()
+            });
+
+            blank_before = false;
+        } else {
+            let eq = find_unquoted(line, '=');
+            let key = eq
+                .and_then(|eq| line.get(..eq))
+                .map(parse_key)
+                .unwrap_or_default();
+
+            let mut scanner = Scanner::default();
+            scanner.feed(eq.and_then(|eq| line.get(eq + 1..)).unwrap_or(""));
+
+            let mut entry_lines = vec![line.trim_end().to_owned()];
+            while scanner.is_open()
+                && let Some(next) = lines.next()
+            {
+                scanner.feed(next);
+                entry_lines.push(next.trim_end().to_owned());
+            }
+
+            current.entries.push(Entry {
+                blank_before: mem::take(&mut blank_before),
+                comments: mem::take(&mut pending),
+                key,
+                lines: entry_lines,
+            });
+
+            pending_blank = false;
+        }
+    }
+
+    tables
+        .last_mut()
+        .unwrap_or(&mut root)
+        .trailing
+        .append(&mut pending);
+
+    (root, tables)
+}
+
+fn parse_header(line: &str) -> Vec<String> {
+    let end = find_unquoted(line, '#').unwrap_or(line.len());
+    let inner = line
+        .get(..end)
+        .unwrap_or(line)
+        .trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+
+    parse_key(inner)
+}
+
+fn parse_key(key: &str) -> Vec<String> {
+    key.split(KEY_SEPARATOR)
+        .map(|part| part.trim().trim_matches(['"', '\'']).to_owned())
+        .collect()
+}
+
+fn find_unquoted(line: &str, needle: char) -> Option<usize> {
+    let mut quote: Option<char> = None;
+
+    for (i, c) in line.char_indices() {
+        match quote {
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == needle => return Some(i),
+            Some(q) if c == q => quote = None,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Quote {
+    Basic,
+    Literal,
+    MultiBasic,
+    MultiLiteral,
+}
+
+/// Tracks just enough toml syntax to know when a value continues on the next
+/// line.
+#[derive(Debug, Default)]
+struct Scanner {
+    depth: usize,
+    quote: Option<Quote>,
+}
+
+impl Scanner {
+    const fn is_open(&self) -> bool {
+        self.depth > 0
+            || matches!(
+                self.quote,
+                Some(Quote::MultiBasic | Quote::MultiLiteral)
+            )
+    }
+
+    fn feed(&mut self, line: &str) {
+        let mut chars = line.char_indices();
+
+        while let Some((i, c)) = chars.next() {
+            let rest = line.get(i..).unwrap_or_default();
+
+            match (self.quote, c) {
+                (Some(Quote::Basic | Quote::MultiBasic), '\\') => {
+                    chars.next();
+                }
+                (Some(Quote::MultiBasic), '"')
+                    if rest.starts_with(r#"""""#) =>
+                {
+                    self.quote = None;
+                    chars.nth(1);
+                }
+                (Some(Quote::MultiLiteral), '\'')
+                    if rest.starts_with("'''") =>
+                {
+                    self.quote = None;
+                    chars.nth(1);
+                }
+                (Some(Quote::Basic), '"') | (Some(Quote::Literal), '\'') => {
+                    self.quote = None;
+                }
+                (None, '"') if rest.starts_with(r#"""""#) => {
+                    self.quote = Some(Quote::MultiBasic);
+                    chars.nth(1);
+                }
+                (None, '\'') if rest.starts_with("'''") => {
+                    self.quote = Some(Quote::MultiLiteral);
+                    chars.nth(1);
+                }
+                (None, '"') => self.quote = Some(Quote::Basic),
+                (None, '\'') => self.quote = Some(Quote::Literal),
+                (None, '[' | '{') => self.depth += 1,
+                (None, ']' | '}') => self.depth = self.depth.saturating_sub(1),
+                (None, '#') => break,
+                _ => {}
+            }
+        }
+
+        // single-line strings can't continue past the end of the line
+        if matches!(self.quote, Some(Quote::Basic | Quote::Literal)) {
+            self.quote = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn fmt(content: &str, stubs: bool) -> String {
+        format(content, "test.toml", stubs).expect("formattable scheme")
+    }
+
+    #[test]
+    fn repo_schemes_are_formatted() {
+        let content = include_str!("../../schemes/cutiepro.toml");
+
+        assert_eq!(fmt(content, false), content);
+    }
+
+    #[test]
+    fn roles_are_reordered_with_their_comments() {
+        let content = indoc! {r##"
+            scheme = "Test"
+
+            [palette]
+            black = "#000000"
+
+            [roles]
+            # the foreground
+            fg = "$black"
+            bg = "$black"
+
+            [roles.syntax]
+            keyword = "$black"
+            variable = "$black"
+        "##};
+
+        assert_eq!(fmt(content, false), indoc! {r##"
+                scheme = "Test"
+
+                [palette]
+                black = "#000000"
+
+                [roles]
+                bg = "$black"
+                # the foreground
+                fg = "$black"
+
+                [roles.syntax]
+                variable = "$black"
+                keyword = "$black"
+            "##});
+    }
+
+    #[test]
+    fn placeholders_move_to_their_role() {
+        let content = indoc! {r#"
+            [roles]
+            bg = "$black"
+            fg = "$black"
+            # bg_alt falls back to bg
+        "#};
+
+        assert_eq!(fmt(content, false), indoc! {r#"
+                [roles]
+                bg = "$black"
+                # bg_alt falls back to bg
+                fg = "$black"
+            "#});
+    }
+
+    #[test]
+    fn stubs_are_only_added_on_request() {
+        let content = indoc! {r#"
+            [roles]
+            bg = "$black"
+        "#};
+
+        assert_eq!(fmt(content, false), content);
+        assert!(fmt(content, true).contains("\n# bg_alt\n"));
+    }
+
+    #[test]
+    fn sections_are_ordered() {
+        let content = indoc! {r##"
+            [extra]
+            answer = 42
+
+            [palette]
+            black = "#000000"
+        "##};
+
+        let formatted = fmt(content, false);
+
+        assert!(
+            formatted.find("[palette]") < formatted.find("[extra]"),
+            "{formatted}"
+        );
+    }
+
+    #[test]
+    fn dotted_role_keys_move_to_their_group() {
+        let content = indoc! {r#"
+            [roles]
+            bg = "$black"
+            syntax.keyword = "$black" # bold
+        "#};
+
+        assert_eq!(fmt(content, false), indoc! {r#"
+                [roles]
+                bg = "$black"
+
+                [roles.syntax]
+                keyword = "$black" # bold
+            "#});
+    }
+
+    #[test]
+    fn inline_group_tables_are_expanded() {
+        let content = indoc! {r#"
+            [roles]
+            bg = "$black"
+            syntax = { keyword = "$black", variable = "$white" }
+        "#};
+
+        assert_eq!(fmt(content, false), indoc! {r#"
+                [roles]
+                bg = "$black"
+
+                [roles.syntax]
+                variable = "$white"
+                keyword = "$black"
+            "#});
+    }
+}
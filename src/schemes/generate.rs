@@ -0,0 +1,417 @@
+//! Schemes generated from a few seed colors.
+
+use std::iter;
+
+use indexmap::IndexMap;
+use palette::{OklabHue, Oklch};
+
+use super::derive::gray;
+use super::dictionary::Namer;
+use super::{Color, RoleName, Swatch};
+use crate::Result;
+
+const NEUTRAL_STEPS: usize = 8;
+
+/// Accent seeds further than this from every hue become extra accents.
+const MAX_HUE_DISTANCE: f32 = 30.0;
+const HUE_JITTER: f32 = 8.0;
+const LIGHTNESS_JITTER: f32 = 0.03;
+const BRIGHT_LIGHTNESS: f32 = 0.08;
+
+/// Lightness and chroma for generated accents when there are no seeds.
+const DEFAULT_LIGHTNESS: f32 = 0.75;
+const DEFAULT_CHROMA: f32 = 0.12;
+
+/// Bounds the chroma [`in_gamut`] starts from, above anything sRGB holds.
+const MAX_CHROMA: f32 = 0.4;
+const CHROMA_STEP: f32 = 0.005;
+
+/// Hues with a meaning, with their OKLCH hue angle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Hue {
+    Red,
+    Yellow,
+    Green,
+    Cyan,
+    Blue,
+    Magenta,
+}
+
+impl Hue {
+    const ALL: [Self; 6] = [
+        Self::Red,
+        Self::Yellow,
+        Self::Green,
+        Self::Cyan,
+        Self::Blue,
+        Self::Magenta,
+    ];
+
+    const fn degrees(self) -> f32 {
+        match self {
+            Self::Red => 29.0,
+            Self::Yellow => 100.0,
+            Self::Green => 142.0,
+            Self::Cyan => 195.0,
+            Self::Blue => 264.0,
+            Self::Magenta => 328.0,
+        }
+    }
+}
+
+/// A palette slot that roles are mapped to.
+#[derive(Debug, Clone, Copy)]
+enum Slot {
+    /// A step of the neutral ramp, from `bg` (0) to `fg`.
+    Neutral(usize),
+    Accent(Hue),
+    Bright(Hue),
+    /// The first accent seed, or blue.
+    Primary,
+    /// Another role, for roles that should follow it.
+    Alias(&'static str),
+}
+
+/// Where each base role, and some optional ones, are mapped.
+const MAPPING: &[(&str, Slot)] = &[
+    ("bg", Slot::Neutral(0)),
+    ("fg", Slot::Neutral(7)),
+    ("fg_alt", Slot::Neutral(5)),
+    ("toolbar", Slot::Neutral(1)),
+    ("toolbar_popup", Slot::Neutral(2)),
+    ("select", Slot::Neutral(2)),
+    ("accent", Slot::Primary),
+    ("inactive", Slot::Neutral(4)),
+    ("focus", Slot::Primary),
+    ("guide", Slot::Neutral(2)),
+    ("match", Slot::Accent(Hue::Yellow)),
+    ("error", Slot::Accent(Hue::Red)),
+    ("warning", Slot::Accent(Hue::Yellow)),
+    ("info", Slot::Accent(Hue::Blue)),
+    ("hint", Slot::Neutral(5)),
+    ("debug.active", Slot::Accent(Hue::Yellow)),
+    ("debug.breakpoint", Slot::Accent(Hue::Red)),
+    ("debug.frameline", Slot::Neutral(2)),
+    ("mode.normal", Slot::Primary),
+    ("mode.insert", Slot::Accent(Hue::Green)),
+    ("mode.select", Slot::Accent(Hue::Magenta)),
+    ("syntax.variable", Slot::Alias("fg")),
+    ("syntax.keyword", Slot::Accent(Hue::Magenta)),
+    ("syntax.type", Slot::Accent(Hue::Yellow)),
+    ("syntax.function", Slot::Accent(Hue::Blue)),
+    ("syntax.constant", Slot::Accent(Hue::Red)),
+    ("syntax.label", Slot::Accent(Hue::Cyan)),
+    ("syntax.constructor", Slot::Accent(Hue::Yellow)),
+    ("syntax.string", Slot::Accent(Hue::Green)),
+    ("syntax.attribute", Slot::Accent(Hue::Cyan)),
+    ("syntax.namespace", Slot::Accent(Hue::Blue)),
+    ("syntax.tag", Slot::Accent(Hue::Red)),
+    ("syntax.comment", Slot::Neutral(4)),
+    ("syntax.operator", Slot::Accent(Hue::Cyan)),
+    ("syntax.punctuation", Slot::Neutral(5)),
+    ("syntax.special", Slot::Accent(Hue::Magenta)),
+    ("diff.plus", Slot::Accent(Hue::Green)),
+    ("diff.minus", Slot::Accent(Hue::Red)),
+    ("diff.delta", Slot::Accent(Hue::Blue)),
+    ("markup.heading", Slot::Primary),
+    ("markup.list", Slot::Accent(Hue::Cyan)),
+    ("markup.link", Slot::Accent(Hue::Blue)),
+    ("markup.bold", Slot::Alias("fg")),
+    ("markup.italic", Slot::Alias("fg")),
+    ("markup.strikethrough", Slot::Neutral(4)),
+    ("markup.quote", Slot::Neutral(5)),
+    ("markup.raw", Slot::Accent(Hue::Green)),
+    ("ansi.black", Slot::Neutral(1)),
+    ("ansi.black_bright", Slot::Neutral(3)),
+    ("ansi.red", Slot::Accent(Hue::Red)),
+    ("ansi.red_bright", Slot::Bright(Hue::Red)),
+    ("ansi.green", Slot::Accent(Hue::Green)),
+    ("ansi.green_bright", Slot::Bright(Hue::Green)),
+    ("ansi.yellow", Slot::Accent(Hue::Yellow)),
+    ("ansi.yellow_bright", Slot::Bright(Hue::Yellow)),
+    ("ansi.blue", Slot::Accent(Hue::Blue)),
+    ("ansi.blue_bright", Slot::Bright(Hue::Blue)),
+    ("ansi.magenta", Slot::Accent(Hue::Magenta)),
+    ("ansi.magenta_bright", Slot::Bright(Hue::Magenta)),
+    ("ansi.cyan", Slot::Accent(Hue::Cyan)),
+    ("ansi.cyan_bright", Slot::Bright(Hue::Cyan)),
+    ("ansi.white", Slot::Neutral(6)),
+    ("ansi.white_bright", Slot::Neutral(7)),
+];
+
+/// The colors a scheme is generated from.
+#[derive(Debug, Clone)]
+pub(crate) struct Seeds {
+    pub bg: Color,
+    pub fg: Color,
+    pub accents: Vec<Color>,
+    pub seed: u64,
+}
+
+/// A generated palette and the raw toml values of the roles mapped to it.
+#[derive(Debug)]
+pub(crate) struct Generated {
+    pub palette: Vec<Swatch>,
+    pub roles: IndexMap<RoleName, String>,
+}
+
+pub(crate) fn generate(seeds: &Seeds) -> Result<Generated> {
+    let mut rng = fastrand::Rng::with_seed(seeds.seed);
+    let mut namer = Namer::new();
+
+    let neutrals = neutrals(seeds.bg, seeds.fg)
+        .into_iter()
+        .map(|color| namer.name(color))
+        .collect::<Result<Vec<_>>>()?;
+
+    let (hues, extras) = accents(&seeds.accents, &mut rng);
+
+    let mut accents = IndexMap::new();
+    let mut brights = IndexMap::new();
+    for (hue, color) in hues {
+        let lch = color.oklch();
+        let bright = in_gamut(Oklch::new(
+            (lch.l + BRIGHT_LIGHTNESS).min(1.0),
+            lch.chroma,
+            lch.hue,
+        ));
+
+        accents.insert(hue, namer.name(color)?);
+        brights.insert(hue, namer.name(bright)?);
+    }
+
+    let extras = extras
+        .into_iter()
+        .map(|color| namer.name(color))
+        .collect::<Result<Vec<_>>>()?;
+
+    let primary = seeds
+        .accents
+        .first()
+        .and_then(|seed| {
+            accents
+                .values()
+                .chain(&extras)
+                .find(|swatch| swatch.color == *seed)
+        })
+        .or_else(|| accents.get(&Hue::Blue));
+
+    let roles = MAPPING
+        .iter()
+        .map(|&(role, slot)| {
+            let swatch = match slot {
+                Slot::Neutral(step) => neutrals.get(step),
+                Slot::Accent(hue) => accents.get(&hue),
+                Slot::Bright(hue) => brights.get(&hue),
+                Slot::Primary => primary,
+                Slot::Alias(target) => {
+                    return Ok((role.parse()?, target.to_owned()));
+                }
+            };
+
+            let value = swatch.map_or_else(
+                || "fg".to_owned(),
+                |swatch| format!("${}", swatch.name),
+            );
+
+            Ok((role.parse()?, value))
+        })
+        .collect::<Result<IndexMap<_, _>>>()?;
+
+    let palette = neutrals
+        .into_iter()
+        .chain(accents.into_values())
+        .chain(extras)
+        .chain(brights.into_values())
+        .collect();
+
+    Ok(Generated { palette, roles })
+}
+
+/// Evenly spaced steps from `bg` to `fg` in OKLCH.
+fn neutrals(bg: Color, fg: Color) -> Vec<Color> {
+    let (from, to) = (bg.oklch(), fg.oklch());
+
+    #[expect(clippy::cast_precision_loss, reason = "small step counts")]
+    (0..NEUTRAL_STEPS)
+        .map(|step| {
+            if step == 0 {
+                return bg;
+            }
+            if step == NEUTRAL_STEPS - 1 {
+                return fg;
+            }
+
+            let t = step as f32 / (NEUTRAL_STEPS - 1) as f32;
+
+            in_gamut(Oklch::new(
+                (to.l - from.l).mul_add(t, from.l),
+                (to.chroma - from.chroma).mul_add(t, from.chroma),
+                from.hue,
+            ))
+        })
+        .collect()
+}
+
+/// Fills every hue with the closest seed, or a generated color, and returns
+/// the seeds that didn't fit any hue.
+fn accents(
+    seeds: &[Color],
+    rng: &mut fastrand::Rng,
+) -> (IndexMap<Hue, Color>, Vec<Color>) {
+    let mut remaining = seeds.to_vec();
+
+    #[expect(clippy::cast_precision_loss, reason = "few seeds")]
+    let (lightness, chroma) = if seeds.is_empty() {
+        (DEFAULT_LIGHTNESS, DEFAULT_CHROMA)
+    } else {
+        let count = seeds.len() as f32;
+        let (l, c) = seeds.iter().fold((0.0, 0.0), |(l, c), seed| {
+            let lch = seed.oklch();
+            (l + lch.l, c + lch.chroma)
+        });
+
+        (l / count, c / count)
+    };
+
+    let hues = Hue::ALL
+        .into_iter()
+        .map(|hue| {
+            let closest = remaining
+                .iter()
+                .enumerate()
+                .map(|(i, seed)| (i, hue_distance(*seed, hue.degrees())))
+                .filter(|&(_, distance)| distance <= MAX_HUE_DISTANCE)
+                .min_by(|(_, a), (_, b)| a.total_cmp(b));
+
+            if let Some((i, _)) = closest {
+                return (hue, remaining.remove(i));
+            }
+
+            let mut jitter =
+                |amount: f32| rng.f32().mul_add(2.0, -1.0) * amount;
+
+            let color = in_gamut(Oklch::new(
+                (lightness + jitter(LIGHTNESS_JITTER)).clamp(0.0, 1.0),
+                chroma,
+                OklabHue::from_degrees(hue.degrees() + jitter(HUE_JITTER)),
+            ));
+
+            (hue, color)
+        })
+        .collect();
+
+    (hues, remaining)
+}
+
+fn hue_distance(color: Color, degrees: f32) -> f32 {
+    let diff = (color.oklch().hue.into_positive_degrees() - degrees).abs();
+
+    diff.min(360.0 - diff)
+}
+
+/// Reduces chroma until `color` fits in sRGB, falling back to gray for
+/// colors no chroma fits, like ones with a NaN hue.
+pub(crate) fn in_gamut(color: Oklch) -> Color {
+    let l = color.l.clamp(0.0, 1.0);
+    let chroma = if color.chroma.is_nan() {
+        0.0
+    } else {
+        color.chroma.clamp(0.0, MAX_CHROMA)
+    };
+
+    iter::successors(Some(chroma), |&chroma| {
+        (chroma > 0.0).then(|| (chroma - CHROMA_STEP).max(0.0))
+    })
+    .find_map(|chroma| Color::from_oklch(Oklch::new(l, chroma, color.hue)))
+    .unwrap_or_else(|| gray(l))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schemes::roles;
+
+    fn seeds(seed: u64, accents: &[&str]) -> Seeds {
+        let color = |hex: &str| Color::parse(hex).expect("valid color");
+
+        Seeds {
+            bg: color("#1b1a24"),
+            fg: color("#f4ede6"),
+            accents: accents.iter().map(|hex| color(hex)).collect(),
+            seed,
+        }
+    }
+
+    fn hexes(generated: &Generated) -> Vec<String> {
+        generated
+            .palette
+            .iter()
+            .map(|swatch| swatch.hex().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn every_role_in_the_mapping_exists() {
+        for (role, slot) in MAPPING {
+            assert!(role.parse::<RoleName>().is_ok(), "`{role}`");
+
+            if let Slot::Alias(target) = slot {
+                assert!(target.parse::<RoleName>().is_ok(), "`{target}`");
+            }
+        }
+    }
+
+    #[test]
+    fn base_roles_are_mapped() {
+        let generated = generate(&seeds(0, &["#f76f80"])).expect("generated");
+
+        for role in roles::base() {
+            assert!(generated.roles.contains_key(&role), "`{role}` unmapped");
+        }
+    }
+
+    #[test]
+    fn seeds_are_kept_and_placed_by_hue() {
+        let generated =
+            generate(&seeds(0, &["#f76f80", "#8fd3ff"])).expect("generated");
+        let hexes = hexes(&generated);
+
+        assert!(hexes.contains(&"#f76f80".to_owned()));
+        assert!(hexes.contains(&"#8fd3ff".to_owned()));
+
+        let accent = "accent".parse::<RoleName>().expect("valid role");
+        let swatch = generated
+            .roles
+            .get(&accent)
+            .and_then(|value| value.strip_prefix('$'))
+            .and_then(|name| {
+                generated.palette.iter().find(|s| s.name.as_str() == name)
+            })
+            .expect("`accent` is set to a swatch");
+
+        assert_eq!(swatch.hex().to_string(), "#f76f80");
+    }
+
+    #[test]
+    fn generation_is_deterministic() {
+        let a = generate(&seeds(7, &["#f76f80"])).expect("generated");
+        let b = generate(&seeds(7, &["#f76f80"])).expect("generated");
+        let c = generate(&seeds(8, &["#f76f80"])).expect("generated");
+
+        assert_eq!(hexes(&a), hexes(&b));
+        assert_ne!(hexes(&a), hexes(&c));
+    }
+
+    #[test]
+    fn out_of_gamut_colors_settle() {
+        let white = Color::parse("#ffffff").expect("valid color");
+
+        assert_eq!(
+            in_gamut(Oklch::new(1.2, 0.3, 40.0)),
+            in_gamut(Oklch::new(1.0, 0.3, 40.0))
+        );
+        assert_eq!(in_gamut(Oklch::new(1.2, f32::NAN, 40.0)), white);
+        assert_eq!(in_gamut(Oklch::new(0.5, 0.2, f32::NAN)), gray(0.5));
+    }
+}
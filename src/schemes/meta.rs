@@ -0,0 +1,310 @@
+//! Descriptive scheme metadata from the `[meta]` table.
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use strum::Display;
+
+use super::Error;
+use super::swatches::relative_luminance;
+use crate::Result;
+
+const MAX_FIELD_LENGTH: usize = 1000;
+
+/// Text with an ascii fallback.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct Text {
+    pub name: String,
+    pub ascii: String,
+}
+
+impl Text {
+    fn parse(field: &str, raw: RawText, ascii: Option<String>) -> Result<Self> {
+        let (name, explicit_ascii) = match raw {
+            RawText::Plain(name) => (name, None),
+            RawText::Smart { name, ascii } => (name, ascii),
+        };
+
+        let ascii = explicit_ascii
+            .or(ascii)
+            .unwrap_or_else(|| deunicode::deunicode(&name));
+
+        validate(field, &name)?;
+        validate(&format!("{field}.ascii"), &ascii)?;
+
+        if !ascii.is_ascii() {
+            return Err(Error::InvalidMeta {
+                field: format!("{field}.ascii"),
+                reason: "contains non-ascii characters".to_owned(),
+            }
+            .into());
+        }
+
+        Ok(Self { name, ascii })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct Author {
+    pub name: Text,
+    pub url: Option<String>,
+    pub email: Option<String>,
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub(crate) enum Appearance {
+    Light,
+    Dark,
+}
+
+impl Appearance {
+    /// Dark when `bg` has a lower relative luminance than `fg`.
+    #[must_use]
+    pub(crate) fn detect(bg: (u8, u8, u8), fg: (u8, u8, u8)) -> Self {
+        if relative_luminance(bg) < relative_luminance(fg) {
+            Self::Dark
+        } else {
+            Self::Light
+        }
+    }
+
+    #[must_use]
+    pub(crate) const fn is_dark(self) -> bool {
+        matches!(self, Self::Dark)
+    }
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Default, Serialize)]
+pub(crate) struct Meta {
+    pub author: Option<Text>,
+    pub license: Option<Text>,
+    pub blurb: Option<Text>,
+    pub authors: Vec<Author>,
+    pub homepage: Option<String>,
+    pub version: Option<String>,
+    /// Where the scheme was ported from, if it isn't original.
+    pub upstream_original: Option<String>,
+    pub appearance: Option<Appearance>,
+    /// Arbitrary user metadata from `[meta.custom]`, passed through as is.
+    /// `variant` also fills in `{variant}` in output paths.
+    pub custom: toml::Table,
+}
+
+impl Meta {
+    pub(crate) fn parse(val: &toml::Value, path: &str) -> Result<Self> {
+        let raw: RawMeta =
+            val.clone().try_into().map_err(|src| Error::Deserializing {
+                section: "meta".to_owned(),
+                path: path.to_owned(),
+                src: Box::new(src),
+            })?;
+
+        for key in raw.unknown.keys() {
+            warn!("ignoring unknown meta key `{key}` in `{path}`");
+        }
+
+        let text =
+            |field: &str, raw: Option<RawText>, ascii: Option<String>| {
+                raw.map(|raw| Text::parse(field, raw, ascii)).transpose()
+            };
+
+        let authors = raw
+            .authors
+            .into_iter()
+            .enumerate()
+            .map(|(i, author)| {
+                let (name, url, email) = match author {
+                    RawAuthor::Plain(name) => {
+                        (RawText::Plain(name), None, None)
+                    }
+                    RawAuthor::Full { name, url, email } => (name, url, email),
+                };
+
+                Ok(Author {
+                    name: Text::parse(&format!("authors[{i}]"), name, None)?,
+                    url,
+                    email,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        for (field, value) in [
+            ("homepage", &raw.homepage),
+            ("version", &raw.version),
+            ("upstream_original", &raw.upstream_original),
+        ] {
+            if let Some(value) = value {
+                validate(field, value)?;
+            }
+        }
+
+        Ok(Self {
+            author: text("author", raw.author, raw.author_ascii)?,
+            license: text("license", raw.license, raw.license_ascii)?,
+            blurb: text("blurb", raw.blurb, raw.blurb_ascii)?,
+            authors,
+            homepage: raw.homepage,
+            version: raw.version,
+            upstream_original: raw.upstream_original,
+            appearance: raw.appearance,
+            custom: raw.custom,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawText {
+    Plain(String),
+    Smart { name: String, ascii: Option<String> },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawAuthor {
+    Plain(String),
+    Full {
+        name: RawText,
+        url: Option<String>,
+        email: Option<String>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct RawMeta {
+    author: Option<RawText>,
+    author_ascii: Option<String>,
+    license: Option<RawText>,
+    license_ascii: Option<String>,
+    blurb: Option<RawText>,
+    blurb_ascii: Option<String>,
+    #[serde(default)]
+    authors: Vec<RawAuthor>,
+    homepage: Option<String>,
+    version: Option<String>,
+    upstream_original: Option<String>,
+    appearance: Option<Appearance>,
+    #[serde(default)]
+    custom: toml::Table,
+    /// Keys theymer doesn't know, ignored with a warning.
+    #[serde(flatten)]
+    unknown: toml::Table,
+}
+
+fn validate(field: &str, text: &str) -> Result<()> {
+    if text.len() > MAX_FIELD_LENGTH {
+        return Err(Error::InvalidMeta {
+            field: field.to_owned(),
+            reason: format!(
+                "too long ({} characters; max is {MAX_FIELD_LENGTH})",
+                text.len()
+            ),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn meta(toml: &str) -> Result<Meta> {
+        Meta::parse(
+            &toml::Value::Table(toml::from_str(toml).expect("valid toml")),
+            "test.toml",
+        )
+    }
+
+    #[test]
+    fn text_accepts_strings_and_tables() {
+        let meta = meta(indoc! {r#"
+            author = "jezzy"
+            blurb = { name = "cute ♡", ascii = "cute <3" }
+            license = "MIT ♡"
+            license_ascii = "MIT"
+        "#})
+        .expect("valid meta");
+
+        let text = |t: Option<Text>| t.map(|t| (t.name, t.ascii));
+
+        assert_eq!(
+            text(meta.author),
+            Some(("jezzy".to_owned(), "jezzy".to_owned()))
+        );
+        assert_eq!(
+            text(meta.blurb),
+            Some(("cute ♡".to_owned(), "cute <3".to_owned()))
+        );
+        assert_eq!(
+            text(meta.license),
+            Some(("MIT ♡".to_owned(), "MIT".to_owned()))
+        );
+    }
+
+    #[test]
+    fn structured_fields_are_parsed() {
+        let meta = meta(indoc! {r#"
+            authors = ["a", { name = "b", url = "https://b.example" }]
+            appearance = "dark"
+            version = "1.0.0"
+
+            [custom]
+            anything = [1, 2]
+        "#})
+        .expect("valid meta");
+
+        assert_eq!(meta.authors.len(), 2);
+        assert_eq!(
+            meta.authors.get(1).and_then(|a| a.url.as_deref()),
+            Some("https://b.example")
+        );
+        assert_eq!(meta.appearance, Some(Appearance::Dark));
+        assert!(meta.custom.contains_key("anything"));
+    }
+
+    #[test]
+    fn appearance_follows_luminance() {
+        let black = (0x1a, 0x1b, 0x26);
+        let white = (0xfa, 0xf4, 0xed);
+
+        assert_eq!(Appearance::detect(black, white), Appearance::Dark);
+        assert_eq!(Appearance::detect(white, black), Appearance::Light);
+        assert_eq!(
+            Appearance::detect((0, 0, 255), (0, 255, 0)),
+            Appearance::Dark
+        );
+    }
+
+    #[test]
+    fn invalid_meta_is_rejected() {
+        let invalid = |toml: &str| {
+            matches!(
+                meta(toml),
+                Err(crate::Error::Scheme(Error::InvalidMeta { .. }))
+            )
+        };
+
+        assert!(invalid(r#"author = { name = "a", ascii = "♡" }"#));
+        assert!(invalid(&format!("blurb = \"{}\"", "a".repeat(1001))));
+    }
+
+    #[test]
+    fn unknown_keys_are_ignored() {
+        let meta = meta(indoc! {r#"
+            author = "jezzy"
+            maintainer = "someone"
+        "#})
+        .expect("unknown keys are ignored");
+
+        assert!(meta.author.is_some());
+    }
+}
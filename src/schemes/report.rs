@@ -0,0 +1,254 @@
+//! Summaries of what a scheme actually defines.
+
+use std::cmp::Reverse;
+use std::iter;
+
+use indexmap::IndexMap;
+use serde::Serialize;
+
+use super::{RoleKind, RoleValue, Scheme, roles};
+use crate::Result;
+use crate::output::table::{self, Format};
+
+/// Swatches used by at least this many roles are listed as popular.
+const POPULAR_THRESHOLD: usize = 10;
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Report {
+    scheme: String,
+    roles: Vec<Role>,
+    unused: Vec<String>,
+    popular: Vec<Usage>,
+}
+
+#[derive(Debug, Serialize)]
+struct Role {
+    role: String,
+    #[serde(flatten)]
+    source: Source,
+    swatch: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "source", rename_all = "snake_case")]
+enum Source {
+    Set,
+    Aliased { target: String },
+    Inherited { base: String },
+}
+
+impl Source {
+    fn describe(&self) -> String {
+        match self {
+            Self::Set => "set".to_owned(),
+            Self::Aliased { target } => format!("alias of `{target}`"),
+            Self::Inherited { base } => format!("inherited from `{base}`"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Usage {
+    swatch: String,
+    roles: Vec<String>,
+}
+
+impl Report {
+    #[must_use]
+    pub(crate) fn new(scheme: &Scheme) -> Self {
+        let roles = roles::iter()
+            .filter_map(|role| {
+                let resolved = scheme.resolved_roles.get(&role)?;
+
+                let source = match scheme.roles.get(&role) {
+                    Some(RoleValue::Swatch(_)) => Source::Set,
+                    Some(RoleValue::Role(target)) => Source::Aliased {
+                        target: target.to_string(),
+                    },
+                    None => match role.classify() {
+                        RoleKind::Base(_) => return None,
+                        RoleKind::Optional { base } => Source::Inherited {
+                            base: base.to_string(),
+                        },
+                    },
+                };
+
+                Some(Role {
+                    role: role.to_string(),
+                    source,
+                    swatch: resolved.swatch.clone(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut usage: IndexMap<String, Vec<String>> = scheme
+            .palette
+            .iter()
+            .map(|swatch| (swatch.name.to_string(), Vec::new()))
+            .collect();
+
+        for role in &roles {
+            usage
+                .entry(role.swatch.clone())
+                .or_default()
+                .push(role.role.clone());
+        }
+
+        let unused = usage
+            .iter()
+            .filter(|(_, roles)| roles.is_empty())
+            .map(|(swatch, _)| swatch.clone())
+            .collect();
+
+        let mut popular = usage
+            .into_iter()
+            .filter(|(_, roles)| roles.len() >= POPULAR_THRESHOLD)
+            .map(|(swatch, roles)| Usage { swatch, roles })
+            .collect::<Vec<_>>();
+        popular.sort_by_key(|u| Reverse(u.roles.len()));
+
+        Self {
+            scheme: scheme.name.to_string(),
+            roles,
+            unused,
+            popular,
+        }
+    }
+}
+
+pub(crate) fn render(reports: &[Report], format: Format) -> Result<String> {
+    match format {
+        Format::Table => {
+            Ok(reports.iter().map(table).collect::<Vec<_>>().join("\n"))
+        }
+        Format::Markdown => {
+            Ok(reports.iter().map(markdown).collect::<Vec<_>>().join("\n"))
+        }
+        Format::Json => table::json(&reports),
+    }
+}
+
+fn rows(report: &Report, code: fn(&str) -> String) -> Vec<[String; 3]> {
+    let header = ["role", "source", "swatch"].map(str::to_owned);
+    let rows = report
+        .roles
+        .iter()
+        .map(|r| [code(&r.role), r.source.describe(), code(&r.swatch)]);
+
+    iter::once(header).chain(rows).collect()
+}
+
+fn table(report: &Report) -> String {
+    let popular = report
+        .popular
+        .iter()
+        .map(|u| format!("{} ({})", u.swatch, u.roles.len()))
+        .collect::<Vec<_>>();
+
+    format!(
+        "{}\n\n{}\nunused swatches: {}\npopular swatches: {}\n",
+        report.scheme,
+        table::columns(&rows(report, str::to_owned)),
+        list(&report.unused),
+        list(&popular),
+    )
+}
+
+fn markdown(report: &Report) -> String {
+    let code = |s: &str| format!("`{s}`");
+
+    let unused = report.unused.iter().map(|swatch| format!("- `{swatch}`"));
+    let popular = report.popular.iter().map(|usage| {
+        format!("- `{}` ({} roles)", usage.swatch, usage.roles.len())
+    });
+
+    let mut out = format!(
+        "## {}\n\n{}\n### unused swatches\n\n",
+        report.scheme,
+        table::markdown(&rows(report, code)),
+    );
+    out.push_str(&bullets(unused));
+    out.push_str("\n### popular swatches\n\n");
+    out.push_str(&bullets(popular));
+
+    out
+}
+
+fn bullets<I: Iterator<Item = String>>(items: I) -> String {
+    let lines = items.map(|item| item + "\n").collect::<String>();
+
+    if lines.is_empty() {
+        "none\n".to_owned()
+    } else {
+        lines
+    }
+}
+
+fn list(items: &[String]) -> String {
+    if items.is_empty() {
+        "none".to_owned()
+    } else {
+        items.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::schemes;
+
+    fn cutiepro() -> Report {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("schemes")
+            .join("cutiepro.toml");
+        let scheme =
+            schemes::load("cutiepro", &path).expect("valid repo scheme");
+
+        Report::new(&scheme)
+    }
+
+    fn source(report: &Report, role: &str) -> String {
+        report.roles.iter().find(|r| r.role == role).map_or_else(
+            || panic!("`{role}` missing from report"),
+            |r| r.source.describe(),
+        )
+    }
+
+    #[test]
+    fn roles_are_classified() {
+        let report = cutiepro();
+
+        assert_eq!(report.roles.len(), roles::iter().count());
+        assert_eq!(source(&report, "bg"), "set");
+        assert_eq!(source(&report, "bg_alt"), "inherited from `bg`");
+        assert_eq!(source(&report, "hint"), "alias of `fg`");
+    }
+
+    #[test]
+    fn usage_covers_the_palette() {
+        let report = cutiepro();
+
+        assert!(
+            report
+                .popular
+                .iter()
+                .all(|u| u.roles.len() >= POPULAR_THRESHOLD)
+        );
+        assert!(
+            report
+                .unused
+                .iter()
+                .all(|s| report.roles.iter().all(|r| &r.swatch != s))
+        );
+    }
+
+    #[test]
+    fn json_is_tagged_by_source() {
+        let json = render(&[cutiepro()], Format::Json).expect("serializable");
+
+        assert!(json.contains(r#""source": "inherited""#), "{json}");
+        assert!(json.contains(r#""base": "bg""#), "{json}");
+    }
+}
@@ -35,16 +35,24 @@
 //! # rendered with cutiepro:
 //! background  #181716
 //! ```
-
-#![allow(
-    non_camel_case_types,
-    reason = "`define_roles` macro generates lowercase variants"
-)]
+//!
+//! Projects can declare extra roles and groups in `theymer.toml`, each falling
+//! back to an existing role:
+//!
+//! ```toml
+//! [roles]
+//! scroll = "guide"
+//!
+//! [roles.vcs]
+//! added = "syntax.string"
+//! ```
 
 use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::result::Result as StdResult;
 use std::str::FromStr;
+use std::sync::{Arc, LazyLock, Mutex, PoisonError, RwLock};
 
+use indexmap::{IndexMap, IndexSet};
 use owo_colors::OwoColorize as _;
 use owo_colors::Stream::Stdout;
 use serde::{Deserialize, Serialize};
@@ -89,23 +97,9 @@ macro_rules! define_roles {
             $($roles)*
         ];
 
-        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-        pub(crate) enum Group {
-            Root,
-            $($groups,)*
-        }
-
-        impl Name {
-            pub(crate) fn group(&self) -> Group {
-                match self.0 {
-                    $(
-                        s if s.starts_with(concat!(stringify!($groups), "."))
-                            => Group::$groups,
-                    )*
-                    _ => Group::Root,
-                }
-            }
-        }
+        const GROUPS: &[&str] = &[
+            $(stringify!($groups),)*
+        ];
     };
 
     // entry
@@ -282,6 +276,36 @@ define_roles! {
 }
 
 const VARIANT_SEPARATOR: char = '_';
+const GROUP_SEPARATOR: char = '.';
+
+/// Top-level names in the template context that roles can't shadow.
+pub(crate) const RESERVED: &[&str] = &[
+    "scheme",
+    "scheme_ascii",
+    "schemes",
+    "meta",
+    "palette",
+    "swatch",
+    "role",
+    "role_name",
+    "group",
+    "group_name",
+    "item",
+    "index",
+    "special",
+    "appearance",
+    "is_dark",
+    "is_light",
+];
+
+/// Roles declared in `theymer.toml`, replaced whenever the config is loaded.
+static CUSTOM: LazyLock<RwLock<Arc<[Custom]>>> =
+    LazyLock::new(|| RwLock::new(Arc::from([])));
+
+/// Every custom role name ever registered, so a name is only leaked once
+/// however often the config is reloaded.
+static INTERNED: LazyLock<Mutex<IndexSet<&'static str>>> =
+    LazyLock::new(Mutex::default);
 
 type Result<T> = StdResult<T, Error>;
 
@@ -296,6 +320,9 @@ pub(crate) enum Error {
 
     #[error("required role `{0}` missing")]
     MissingRequired(String),
+
+    #[error("invalid custom role `{role}`: {reason}")]
+    InvalidCustom { role: String, reason: String },
 }
 
 #[non_exhaustive]
@@ -305,6 +332,31 @@ pub(crate) enum Kind {
     Optional { base: Name },
 }
 
+/// A role group, i.e. the part of a role name before the `.`, if any.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Group {
+    Root,
+    Named(&'static str),
+}
+
+impl Group {
+    #[must_use]
+    pub(crate) const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Root => "",
+            Self::Named(name) => name,
+        }
+    }
+}
+
+/// A project-defined role and the role it falls back to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Custom {
+    name: &'static str,
+    base: &'static str,
+}
+
 impl Kind {
     pub(crate) const fn base(&self) -> &Name {
         match self {
@@ -345,6 +397,12 @@ impl Name {
     #[expect(clippy::unreachable, reason = "guaranteed by role design")]
     #[must_use]
     pub(crate) fn classify(&self) -> Kind {
+        if let Some(custom) = custom().iter().find(|c| c.name == self.0) {
+            return Kind::Optional {
+                base: Self(custom.base),
+            };
+        }
+
         if is_base(self.as_str()) {
             Kind::Base(*self)
         } else {
@@ -363,9 +421,16 @@ impl Name {
     }
 
     #[must_use]
-    pub(crate) const fn as_str(&self) -> &str {
+    pub(crate) const fn as_str(&self) -> &'static str {
         self.0
     }
+
+    #[must_use]
+    pub(crate) fn group(&self) -> Group {
+        self.0
+            .split_once(GROUP_SEPARATOR)
+            .map_or(Group::Root, |(group, _)| Group::Named(group))
+    }
 }
 
 impl Display for Name {
@@ -378,10 +443,8 @@ impl FromStr for Name {
     type Err = Error;
 
     fn from_str(s: &str) -> StdResult<Self, Self::Err> {
-        ROLES
-            .iter()
-            .find(|&&role| role == s)
-            .copied()
+        names()
+            .find(|&role| role == s)
             .map(Self)
             .ok_or_else(|| Error::Undefined(s.to_owned()))
     }
@@ -396,8 +459,145 @@ pub(crate) struct Resolved {
     pub rgb: (u8, u8, u8),
 }
 
+/// Every role, built-in roles first, followed by custom roles in the order
+/// they were declared.
 pub(crate) fn iter() -> impl Iterator<Item = Name> {
-    ROLES.iter().copied().map(Name)
+    names().map(Name)
+}
+
+/// Every role group, in the order their roles are defined.
+pub(crate) fn groups() -> impl Iterator<Item = Group> {
+    let custom = custom();
+    let custom_groups = custom
+        .iter()
+        .filter_map(|c| c.name.split_once(GROUP_SEPARATOR))
+        .map(|(group, _)| group);
+
+    GROUPS
+        .iter()
+        .copied()
+        .chain(custom_groups)
+        .collect::<IndexSet<_>>()
+        .into_iter()
+        .map(Group::Named)
+}
+
+/// Registers the custom roles declared in `theymer.toml`, mapping each role
+/// name (`role` or `group.role`) to the role it falls back to. Any custom
+/// roles registered before are replaced.
+pub(crate) fn register(declared: &IndexMap<String, String>) -> Result<()> {
+    validate(declared)?;
+
+    let custom = declared
+        .iter()
+        .map(|(name, base)| Custom {
+            name: intern(name),
+            base: intern(base),
+        })
+        .collect::<Arc<[_]>>();
+
+    *CUSTOM.write().unwrap_or_else(PoisonError::into_inner) = custom;
+
+    Ok(())
+}
+
+fn custom() -> Arc<[Custom]> {
+    Arc::clone(&CUSTOM.read().unwrap_or_else(PoisonError::into_inner))
+}
+
+/// Custom role names live as long as the process, like built-in ones.
+fn intern(name: &str) -> &'static str {
+    let mut interned = INTERNED.lock().unwrap_or_else(PoisonError::into_inner);
+
+    if let Some(&name) = interned.get(name) {
+        return name;
+    }
+
+    let name: &'static str = Box::leak(name.to_owned().into_boxed_str());
+    interned.insert(name);
+
+    name
+}
+
+fn names() -> impl Iterator<Item = &'static str> {
+    let custom = custom().iter().map(|c| c.name).collect::<Vec<_>>();
+
+    ROLES.iter().copied().chain(custom)
+}
+
+fn validate(declared: &IndexMap<String, String>) -> Result<()> {
+    let invalid = |role: &str, reason: &str| Error::InvalidCustom {
+        role: role.to_owned(),
+        reason: reason.to_owned(),
+    };
+
+    let builtin_groups = GROUPS.iter().copied().collect::<IndexSet<_>>();
+    let builtin_roles = ROLES.iter().copied().collect::<IndexSet<_>>();
+
+    for (role, base) in declared {
+        let (group, key) = match role.split_once(GROUP_SEPARATOR) {
+            Some((group, key)) => (Some(group), key),
+            None => (None, role.as_str()),
+        };
+
+        if !group.into_iter().chain([key]).all(is_identifier) {
+            return Err(invalid(
+                role,
+                "names must start with a lowercase letter and only contain \
+                 lowercase letters, digits and `_`",
+            ));
+        }
+
+        if builtin_roles.contains(role.as_str()) {
+            return Err(invalid(role, "already a built-in role"));
+        }
+
+        let clashes = group.map_or_else(
+            || RESERVED.contains(&key) || builtin_groups.contains(key),
+            |group| {
+                RESERVED.contains(&group)
+                    || builtin_roles.contains(group)
+                    || declared.contains_key(group)
+            },
+        );
+
+        if clashes {
+            return Err(invalid(
+                role,
+                "clashes with another name in templates",
+            ));
+        }
+
+        if !builtin_roles.contains(base.as_str())
+            && !declared.contains_key(base)
+        {
+            return Err(invalid(role, &format!("unknown base role `{base}`")));
+        }
+
+        let mut chain = vec![role.as_str()];
+        let mut next = base.as_str();
+        while let Some(base) = declared.get(next) {
+            if chain.contains(&next) {
+                chain.push(next);
+
+                return Err(Error::CircularReference(
+                    chain.into_iter().map(str::to_owned).collect(),
+                ));
+            }
+
+            chain.push(next);
+            next = base;
+        }
+    }
+
+    Ok(())
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
 }
 
 pub(crate) fn base() -> impl Iterator<Item = Name> {
@@ -462,4 +662,46 @@ mod tests {
     }
 
     fn role_name_errors_on_invalid_name() {}
+
+    fn declare(roles: &[(&str, &str)]) -> IndexMap<String, String> {
+        roles
+            .iter()
+            .map(|&(name, base)| (name.to_owned(), base.to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn custom_roles_are_validated() {
+        validate(&declare(&[
+            ("scroll", "guide"),
+            ("vcs.modified", "vcs.added"),
+            ("vcs.added", "syntax.string"),
+            ("syntax.keyword_async", "syntax.keyword"),
+        ]))
+        .unwrap_or_else(|e| panic!("valid custom roles rejected: {e}"));
+
+        for invalid in [
+            &[("Scroll", "guide")][..],
+            &[("vcs.added.new", "guide")],
+            &[("bg_alt", "bg")],
+            &[("scroll", "nope")],
+            &[("palette", "bg")],
+            &[("syntax", "bg")],
+            &[("bg.scroll", "bg")],
+            &[("a", "b"), ("b", "a")],
+        ] {
+            assert!(validate(&declare(invalid)).is_err(), "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn groups_include_builtin_groups() {
+        let groups = groups().map(|g| g.as_str()).collect::<Vec<_>>();
+
+        assert!(groups.starts_with(&["debug", "mode"]), "{groups:?}");
+        assert_eq!(
+            "syntax.keyword".parse::<Name>().map(|n| n.group()).ok(),
+            Some(Group::Named("syntax"))
+        );
+    }
 }
@@ -0,0 +1,154 @@
+//! Skeletons for new scheme files.
+
+use indexmap::IndexMap;
+
+use super::dictionary::Namer;
+use super::format::{self, key as toml_key};
+use super::roles::{self, Kind as RoleKind};
+use super::{Color, Error, RoleName, Swatch};
+use crate::Result;
+
+const META_FIELDS: &[&str] = &["author", "license", "blurb"];
+const NAME_SEPARATOR: char = '=';
+
+/// Builds a palette from `--color` arguments, either `name=#hex` or a bare
+/// `#hex`, which gets named from the color dictionary.
+pub(crate) fn palette(colors: &[String], path: &str) -> Result<Vec<Swatch>> {
+    let mut namer = Namer::new();
+
+    // reserve every given name first, so generated names can't take them
+    let given = colors
+        .iter()
+        .map(|color| {
+            let Some((name, hex)) = color.split_once(NAME_SEPARATOR) else {
+                return Ok(None);
+            };
+
+            let swatch =
+                Swatch::parse(name.trim(), &toml::Value::from(hex.trim()))?;
+
+            if namer.is_taken(swatch.name.as_str(), swatch.ascii.as_str()) {
+                return Err(Error::InvalidStructure {
+                    path: path.to_owned(),
+                    reason: format!("swatch name `{}` is taken", swatch.name),
+                }
+                .into());
+            }
+
+            namer.reserve(&swatch);
+
+            Ok(Some(swatch))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    given
+        .into_iter()
+        .zip(colors)
+        .map(|(swatch, color)| match swatch {
+            Some(swatch) => Ok(swatch),
+            None => namer.name(Color::parse(color.trim())?),
+        })
+        .collect()
+}
+
+/// Renders a new scheme with `palette` and a slot for every role, filling in
+/// the raw toml values in `assigned`, like `$swatch` or `fg`.
+pub(crate) fn scheme(
+    palette: &[Swatch],
+    assigned: &IndexMap<RoleName, String>,
+    path: &str,
+) -> Result<String> {
+    let mut lines = vec!["[meta]".to_owned()];
+
+    lines.extend(META_FIELDS.iter().map(|field| format!("{field} = \"\"")));
+
+    lines.push(String::new());
+    lines.push("[palette]".to_owned());
+    lines.extend(palette.iter().map(|swatch| {
+        format!("{} = \"{}\"", toml_key(swatch.name.as_str()), swatch.hex())
+    }));
+
+    lines.push(String::new());
+    lines.push("[roles]".to_owned());
+
+    for role in roles::iter() {
+        if let Some(value) = assigned.get(&role) {
+            lines.push(format!(
+                "{role} = {}",
+                toml::Value::from(value.as_str())
+            ));
+            continue;
+        }
+
+        lines.push(match role.classify() {
+            RoleKind::Base(_) => format!("{role} = \"\" # required"),
+            RoleKind::Optional { base } => {
+                format!("# {role} (falls back to `{base}`)")
+            }
+        });
+    }
+
+    lines.push(String::new());
+
+    format::format(&lines.join("\n"), path, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn colors(args: &[&str]) -> Vec<String> {
+        args.iter().map(|&a| a.to_owned()).collect()
+    }
+
+    #[test]
+    fn palette_keeps_order_and_names_unnamed_colors() {
+        let palette = palette(
+            &colors(&["#ff7f50", "coral=#123456", "ink = #111010"]),
+            "test",
+        )
+        .expect("valid colors");
+
+        let names: Vec<_> = palette.iter().map(|s| s.name.as_str()).collect();
+
+        assert_eq!(names, ["coral_2", "coral", "ink"]);
+    }
+
+    #[test]
+    fn palette_rejects_duplicates() {
+        for duplicates in [["a=#000", "a=#fff"], ["a=#000", "A=#fff"]] {
+            assert!(matches!(
+                palette(&colors(&duplicates), "test"),
+                Err(crate::Error::Scheme(Error::InvalidStructure { .. }))
+            ));
+        }
+    }
+
+    #[test]
+    fn scheme_lists_every_role() {
+        let content =
+            scheme(&[], &IndexMap::new(), "test").expect("valid scheme");
+
+        for role in roles::iter() {
+            let key = format::key(
+                role.as_str().rsplit('.').next().unwrap_or_default(),
+            );
+
+            assert!(
+                content.lines().any(|line| {
+                    line.starts_with(&format!("{key} ="))
+                        || line.starts_with(&format!("# {key} "))
+                }),
+                "`{role}` missing from:\n{content}"
+            );
+        }
+    }
+
+    #[test]
+    fn scheme_is_formatted() {
+        let content =
+            scheme(&[], &IndexMap::new(), "test").expect("valid scheme");
+
+        assert_eq!(format::format(&content, "test", false).ok(), Some(content));
+    }
+}
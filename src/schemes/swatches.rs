@@ -4,6 +4,7 @@ use std::result::Result as StdResult;
 use std::str::FromStr;
 
 use hex_color::{Case, Display as HexDisplay, HexColor, ParseHexColorError};
+use palette::{IntoColor as _, Oklab, Oklch, Srgb};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use super::names::Validated;
@@ -47,6 +48,77 @@ impl Color {
     pub(crate) fn parse(s: &str) -> Result<Self> {
         s.parse()
     }
+
+    #[must_use]
+    pub(crate) const fn hex(self) -> HexDisplay {
+        self.0
+    }
+
+    #[must_use]
+    pub(crate) const fn rgb(self) -> (u8, u8, u8) {
+        self.0.color().split_rgb()
+    }
+
+    #[must_use]
+    pub(crate) fn oklab(self) -> Oklab {
+        let (r, g, b) = self.rgb();
+
+        Srgb::new(r, g, b).into_format::<f32>().into_color()
+    }
+
+    #[must_use]
+    pub(crate) fn oklch(self) -> Oklch {
+        self.oklab().into_color()
+    }
+
+    /// Converts back from OKLCH, or `None` if `color` is outside sRGB.
+    #[must_use]
+    pub(crate) fn from_oklch(color: Oklch) -> Option<Self> {
+        const EPSILON: f32 = 1e-4;
+
+        let srgb: Srgb = color.into_color();
+        let in_gamut = [srgb.red, srgb.green, srgb.blue]
+            .iter()
+            .all(|c| (-EPSILON..=1.0 + EPSILON).contains(c));
+
+        if !in_gamut {
+            return None;
+        }
+
+        let (r, g, b) = srgb.into_format::<u8>().into_components();
+
+        Some(HexColor::rgb(r, g, b).into())
+    }
+
+    /// WCAG contrast ratio between two colors, from 1 to 21.
+    #[must_use]
+    pub(crate) fn contrast(self, other: Self) -> f64 {
+        let (lighter, darker) = {
+            let a = relative_luminance(self.rgb());
+            let b = relative_luminance(other.rgb());
+
+            if a > b { (a, b) } else { (b, a) }
+        };
+
+        (lighter + 0.05) / (darker + 0.05)
+    }
+}
+
+/// WCAG relative luminance of an sRGB color.
+#[must_use]
+pub(crate) fn relative_luminance((r, g, b): (u8, u8, u8)) -> f64 {
+    let linear = |c: u8| {
+        let c = f64::from(c) / 255.0;
+
+        if c <= 0.040_45 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+
+    0.0722_f64
+        .mul_add(linear(b), 0.2126_f64.mul_add(linear(r), 0.7152 * linear(g)))
 }
 
 impl From<HexColor> for Color {
@@ -167,7 +239,7 @@ impl Swatch {
 
     #[must_use]
     pub(crate) const fn rgb(&self) -> (u8, u8, u8) {
-        self.color.0.color().split_rgb()
+        self.color.rgb()
     }
 }
 
@@ -1,34 +1,70 @@
-use std::fs;
+use std::path::{Path, PathBuf};
 use std::result::Result as StdResult;
+use std::{fs, iter};
 
 use anyhow::Context as _;
-use indexmap::IndexMap;
+use indexmap::{IndexMap, IndexSet};
+use itertools::Itertools as _;
+use log::debug;
 use walkdir::WalkDir;
 
-use crate::{Config, Error, PathExt as _, Result};
+use crate::{Config, Error, PathExt as _, Result, manifest};
 
+pub(crate) mod analysis;
+pub(crate) mod builtin;
+pub(crate) mod dependencies;
 pub(crate) mod directives;
+pub(crate) mod output;
 pub(crate) mod providers;
+pub(crate) mod sources;
 
+pub(crate) use self::analysis::Usage;
+pub(crate) use self::builtin::Error as BuiltinError;
 pub(crate) use self::directives::{Directives, Error as DirectiveError};
 pub(crate) use self::providers::{
     Error as ProviderError, Resolved as ResolvedProvider,
 };
+pub(crate) use self::sources::Error as SourceError;
 
 pub(crate) const SET_TEST_OBJECT: &str = "_set";
 pub(crate) const JINJA_TEMPLATE_SUFFIX: &str = ".jinja";
 pub(crate) const SKIP_RENDERING_PREFIX: char = '_';
+/// Marks templates rendered once with every scheme, like `SCHEMES.nix.jinja`.
+pub(crate) const AGGREGATE_MARKER: &str = "SCHEMES";
+
+/// The port a template belongs to: its directory under `ports/` or
+/// `assets/`, like `fish` or `circles`.
+#[must_use]
+pub(crate) fn port(name: &str) -> Option<&str> {
+    let mut components = name.split('/');
+
+    match (components.next(), components.next(), components.next()) {
+        (Some("ports" | "assets"), Some(port), Some(_)) => Some(port),
+        _ => None,
+    }
+}
 
 #[derive(Debug)]
 pub(crate) struct Loader {
     pub env: minijinja::Environment<'static>,
     pub providers: Vec<ResolvedProvider>,
     pub directives: IndexMap<String, Directives>,
+    pub usage: IndexMap<String, Usage>,
+    /// Features defined by any of the project's schemes.
+    pub features: IndexSet<String>,
+    /// Hashes of each template with its directives and every template it
+    /// includes or imports.
+    pub fingerprints: IndexMap<String, String>,
 }
 
 impl Loader {
-    pub(crate) fn init(config: &Config) -> Result<Self> {
-        Self::load(config)
+    /// Loads the templates, analyzing them against the `features` defined
+    /// by the project's schemes.
+    pub(crate) fn init(
+        config: &Config,
+        features: &IndexSet<String>,
+    ) -> Result<Self> {
+        Self::load(config, features)
     }
 
     pub(crate) fn with_directives(
@@ -56,11 +92,23 @@ impl Loader {
         Ok(map)
     }
 
-    pub(crate) fn resolve_blob(&self, url: &str) -> Result<String> {
-        Ok(providers::resolve_blob(url, &self.providers)?)
+    /// Templates affected by changes to the `changed` templates, e.g. every
+    /// template importing a changed partial.
+    #[must_use]
+    pub(crate) fn dependents(
+        &self,
+        changed: &IndexSet<String>,
+    ) -> IndexSet<String> {
+        let templates = self.env.templates().collect::<Vec<_>>();
+        let sources = templates
+            .iter()
+            .map(|(name, template)| (*name, template.source()))
+            .collect::<Vec<_>>();
+
+        dependencies::dependents(&sources, changed)
     }
 
-    fn load(config: &Config) -> Result<Self> {
+    fn load(config: &Config, features: &IndexSet<String>) -> Result<Self> {
         let mut env = minijinja::Environment::new();
 
         env.set_undefined_behavior(minijinja::UndefinedBehavior::SemiStrict);
@@ -73,20 +121,60 @@ impl Loader {
 
         let directives = Self::templates_with_directives(
             &mut env,
-            &config.dirs.templates,
+            &sources::layers(config, false)?,
+            config.builtin_templates,
             &config.strip_directives,
         )?;
 
+        let usage = analysis::analyze(&env, features);
         let providers = providers::resolve(&config.providers)?;
+        let fingerprints = Self::fingerprints(&env, &directives);
 
         Ok(Self {
             env,
             providers,
             directives,
+            usage,
+            features: features.clone(),
+            fingerprints,
         })
     }
 
-    fn create_set_test(
+    fn fingerprints(
+        env: &minijinja::Environment<'static>,
+        directives: &IndexMap<String, Directives>,
+    ) -> IndexMap<String, String> {
+        let templates = env.templates().collect::<Vec<_>>();
+        let sources = templates
+            .iter()
+            .map(|(name, template)| (*name, template.source()))
+            .collect::<IndexMap<_, _>>();
+
+        directives
+            .iter()
+            .map(|(name, directives)| {
+                let dependencies = dependencies::closure(&sources, name)
+                    .into_iter()
+                    .map(|dependency| {
+                        let source = sources
+                            .get(dependency.as_str())
+                            .copied()
+                            .unwrap_or_default();
+
+                        format!("{dependency}\0{source}")
+                    });
+
+                let inputs = iter::once(directives.fingerprint())
+                    .chain(dependencies)
+                    .join("\0");
+
+                (name.clone(), manifest::hash(&inputs))
+            })
+            .collect()
+    }
+
+    /// The `set` test: whether the scheme sets a role or feature itself.
+    pub(crate) fn create_set_test(
         state: &minijinja::State<'_, '_>,
         value: &minijinja::Value,
     ) -> StdResult<bool, minijinja::Error> {
@@ -118,13 +206,53 @@ impl Loader {
         }
     }
 
+    /// Loads the templates in every directory of `layers`, then the built-in
+    /// ones if `builtin` is set. Templates in earlier layers shadow those
+    /// with the same name in later ones.
     fn templates_with_directives(
         env: &mut minijinja::Environment<'static>,
-        dir: &str,
+        layers: &[PathBuf],
+        builtin: bool,
         strip_patterns: &[Vec<String>],
     ) -> Result<IndexMap<String, Directives>> {
         let mut directives_map = IndexMap::new();
 
+        for dir in layers {
+            Self::layer_with_directives(
+                env,
+                dir,
+                strip_patterns,
+                &mut directives_map,
+            )?;
+        }
+
+        if builtin {
+            for (name, raw_src) in builtin::TEMPLATES {
+                if directives_map.contains_key(name) {
+                    debug!("built-in template `{name}` is shadowed");
+                    continue;
+                }
+
+                Self::add_template(
+                    env,
+                    name,
+                    raw_src,
+                    &format!("built-in `{name}`"),
+                    strip_patterns,
+                    &mut directives_map,
+                )?;
+            }
+        }
+
+        Ok(directives_map)
+    }
+
+    fn layer_with_directives(
+        env: &mut minijinja::Environment<'static>,
+        dir: &Path,
+        strip_patterns: &[Vec<String>],
+        directives_map: &mut IndexMap<String, Directives>,
+    ) -> Result<()> {
         for entry in WalkDir::new(dir).into_iter().filter_map(StdResult::ok) {
             let path = entry.path();
 
@@ -142,28 +270,52 @@ impl Loader {
                     .to_string_lossy()
                     .replace('\\', "/");
 
+                if directives_map.contains_key(&name) {
+                    debug!(
+                        "template `{name}` in `{}` is shadowed",
+                        dir.display()
+                    );
+                    continue;
+                }
+
                 let raw_src = fs::read_to_string(path)
                     .with_context(|| {
                         format!("reading template `{}`", path.display())
                     })
                     .map_err(Error::template)?;
 
-                let (directives, filtered) = Directives::from_template(
+                Self::add_template(
+                    env,
                     &name,
                     &raw_src,
-                    strip_patterns,
                     path.to_string_lossy().as_str(),
-                )
+                    strip_patterns,
+                    directives_map,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_template(
+        env: &mut minijinja::Environment<'static>,
+        name: &str,
+        raw_src: &str,
+        origin: &str,
+        strip_patterns: &[Vec<String>],
+        directives_map: &mut IndexMap<String, Directives>,
+    ) -> Result<()> {
+        let (directives, filtered) =
+            Directives::from_template(name, raw_src, strip_patterns, origin)
                 .map_err(Error::Directive)?;
 
-                directives_map.insert(name.clone(), directives);
+        directives_map.insert(name.to_owned(), directives);
 
-                env.add_template_owned(name.clone(), filtered)
-                    .with_context(|| format!("compiling template `{name}`"))
-                    .map_err(Error::template)?;
-            }
-        }
+        env.add_template_owned(name.to_owned(), filtered)
+            .with_context(|| format!("compiling template `{name}`"))
+            .map_err(Error::template)?;
 
-        Ok(directives_map)
+        Ok(())
     }
 }
@@ -0,0 +1,281 @@
+//! Static analysis of the names each template reads from its context.
+
+use std::iter;
+use std::sync::LazyLock;
+
+use indexmap::{IndexMap, IndexSet};
+use log::warn;
+use regex::Regex;
+use serde::Serialize;
+
+use super::{JINJA_TEMPLATE_SUFFIX, SET_TEST_OBJECT};
+use crate::Result;
+use crate::output::table::{self, Format};
+use crate::schemes::{RoleName, roles};
+
+/// Names minijinja reports as undeclared that are provided at render time.
+const IMPLICIT_NAMES: &[&str] =
+    &["loop", "caller", "varargs", "kwargs", "self"];
+
+const PATH_SEPARATOR: char = '.';
+const USED_MARK: &str = "x";
+
+/// Names guarded with the `set` test, e.g. `{% if "rainbow" is set %}`.
+#[expect(clippy::expect_used, reason = "the pattern is constant")]
+static SET_TEST: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"["']([A-Za-z_][A-Za-z0-9_]*)["']\s+is\s+set\b"#)
+        .expect("regex should be valid")
+});
+
+/// The context names a template reads.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct Usage {
+    pub roles: IndexSet<RoleName>,
+    /// Role groups used as a whole, e.g. iterated over.
+    pub groups: IndexSet<String>,
+    /// Attributes read on roles, e.g. `hex` in `{{ bg.hex }}`.
+    pub attributes: IndexSet<String>,
+    /// Scheme features, e.g. `rainbow`.
+    pub features: IndexSet<String>,
+    /// References to names that aren't in the render context.
+    pub unknown: IndexSet<String>,
+}
+
+impl Usage {
+    /// Records a name read at `path`. Names in `guarded` are only read when
+    /// set, so they're features even if no loaded scheme defines them.
+    fn record(
+        &mut self,
+        path: &str,
+        known: &Known<'_>,
+        guarded: &IndexSet<&str>,
+    ) {
+        let mut parts = path.split(PATH_SEPARATOR);
+        let Some(root) = parts.next() else {
+            return;
+        };
+
+        if let Ok(role) = root.parse::<RoleName>() {
+            self.roles.insert(role);
+            self.attributes.extend(parts.next().map(str::to_owned));
+        } else if is_group(root) {
+            match parts.next() {
+                Some(key) => {
+                    match format!("{root}{PATH_SEPARATOR}{key}").parse() {
+                        Ok(role) => {
+                            self.roles.insert(role);
+                            self.attributes
+                                .extend(parts.next().map(str::to_owned));
+                        }
+                        Err(_) => {
+                            self.unknown
+                                .insert(format!("{root}{PATH_SEPARATOR}{key}"));
+                        }
+                    }
+                }
+                None => {
+                    self.groups.insert(root.to_owned());
+                }
+            }
+        } else if known.features.contains(root) || guarded.contains(root) {
+            self.features.insert(root.to_owned());
+        } else if !roles::RESERVED.contains(&root)
+            && root != SET_TEST_OBJECT
+            && !IMPLICIT_NAMES.contains(&root)
+            && !known.globals.contains(&root)
+        {
+            self.unknown.insert(root.to_owned());
+        }
+    }
+}
+
+/// Context names that depend on the environment and the loaded schemes.
+struct Known<'a> {
+    globals: Vec<&'a str>,
+    features: &'a IndexSet<String>,
+}
+
+/// Analyzes every template in `env`, warning about unknown references.
+///
+/// `features` are the features defined by any scheme, which templates may
+/// reference like roles.
+pub(crate) fn analyze(
+    env: &minijinja::Environment<'_>,
+    features: &IndexSet<String>,
+) -> IndexMap<String, Usage> {
+    let known = Known {
+        globals: env.globals().map(|(name, _)| name).collect(),
+        features,
+    };
+
+    let mut analyzed = env
+        .templates()
+        .map(|(name, template)| {
+            let mut usage = Usage::default();
+
+            let mut paths = template
+                .undeclared_variables(true)
+                .into_iter()
+                .collect::<Vec<_>>();
+            paths.sort();
+
+            let guarded = SET_TEST
+                .captures_iter(template.source())
+                .filter_map(|captures| captures.get(1))
+                .map(|name| name.as_str())
+                .collect::<IndexSet<_>>();
+
+            for path in paths {
+                usage.record(&path, &known, &guarded);
+            }
+
+            for unknown in &usage.unknown {
+                warn!("template `{name}` references unknown name `{unknown}`");
+            }
+
+            (name.to_owned(), usage)
+        })
+        .collect::<IndexMap<_, _>>();
+
+    analyzed.sort_keys();
+
+    analyzed
+}
+
+/// Renders a summary per template, followed by a role × template matrix.
+pub(crate) fn render(
+    usage: &IndexMap<String, Usage>,
+    format: Format,
+) -> Result<String> {
+    let code = |s: &str| format!("`{s}`");
+
+    match format {
+        Format::Table => Ok(format!(
+            "{}\n{}",
+            summary(usage, str::to_owned),
+            table::columns(&matrix(usage, str::to_owned))
+        )),
+        Format::Markdown => Ok(format!(
+            "{}\n{}",
+            summary(usage, code),
+            table::markdown(&matrix(usage, code))
+        )),
+        Format::Json => table::json(usage),
+    }
+}
+
+fn summary(
+    usage: &IndexMap<String, Usage>,
+    code: fn(&str) -> String,
+) -> String {
+    let list = |items: &IndexSet<String>| {
+        if items.is_empty() {
+            "none".to_owned()
+        } else {
+            items.iter().map(|i| code(i)).collect::<Vec<_>>().join(", ")
+        }
+    };
+
+    usage
+        .iter()
+        .map(|(name, usage)| {
+            format!(
+                "{}: {} roles\n  groups: {}\n  attributes: {}\n  features: \
+                 {}\n  unknown: {}",
+                code(name),
+                usage.roles.len(),
+                list(&usage.groups),
+                list(&usage.attributes),
+                list(&usage.features),
+                list(&usage.unknown),
+            )
+        })
+        .map(|line| line + "\n")
+        .collect()
+}
+
+fn matrix(
+    usage: &IndexMap<String, Usage>,
+    code: fn(&str) -> String,
+) -> Vec<Vec<String>> {
+    let header =
+        iter::once("role".to_owned()).chain(usage.keys().map(|name| {
+            code(name.strip_suffix(JINJA_TEMPLATE_SUFFIX).unwrap_or(name))
+        }));
+
+    let rows = roles::iter().map(|role| {
+        iter::once(code(role.as_str()))
+            .chain(usage.values().map(|usage| {
+                let used = usage.roles.contains(&role)
+                    || usage.groups.contains(role.group().as_str());
+
+                if used { USED_MARK } else { "" }.to_owned()
+            }))
+            .collect()
+    });
+
+    iter::once(header.collect()).chain(rows).collect()
+}
+
+fn is_group(name: &str) -> bool {
+    roles::groups().any(|group| group.as_str() == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(source: &str) -> Usage {
+        let mut env = minijinja::Environment::new();
+        env.add_template("test", source).expect("valid template");
+
+        analyze(&env, &IndexSet::from(["rainbow".to_owned()]))
+            .swap_remove("test")
+            .expect("analyzed template")
+    }
+
+    fn names(set: &IndexSet<String>) -> Vec<&str> {
+        set.iter().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn roles_and_attributes_are_collected() {
+        let usage = usage("{{ bg }} {{ syntax.keyword.hex }} {{ fg.rgb }}");
+
+        let roles =
+            usage.roles.iter().map(RoleName::as_str).collect::<Vec<_>>();
+
+        assert_eq!(roles, ["bg", "fg", "syntax.keyword"]);
+        assert_eq!(names(&usage.attributes), ["rgb", "hex"]);
+        assert!(usage.unknown.is_empty());
+    }
+
+    #[test]
+    fn groups_and_context_names_are_known() {
+        let usage = usage(
+            "{% for k, v in syntax|items %}{{ k }}{% endfor %}{{ meta.author \
+             }}{{ palette }}{{ range(3) }}{{ rainbow[0] }}",
+        );
+
+        assert_eq!(names(&usage.groups), ["syntax"]);
+        assert_eq!(names(&usage.features), ["rainbow"]);
+        assert!(usage.unknown.is_empty(), "{:?}", usage.unknown);
+    }
+
+    #[test]
+    fn unknown_names_are_reported() {
+        let usage = usage("{{ bgg }} {{ syntax.keywrd }} {{ x }}");
+
+        assert_eq!(names(&usage.unknown), ["bgg", "syntax.keywrd", "x"]);
+    }
+
+    #[test]
+    fn names_guarded_by_the_set_test_are_features() {
+        let usage = usage(
+            r#"{% if "stars" is set %}{{ stars[0] }}{% endif %}{{ moons }}"#,
+        );
+
+        assert_eq!(names(&usage.features), ["stars"]);
+        assert_eq!(names(&usage.unknown), ["moons"]);
+    }
+}
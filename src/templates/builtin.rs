@@ -0,0 +1,165 @@
+//! Templates bundled with the binary, layered under every other source.
+
+use std::path::{Path, PathBuf};
+use std::result::Result as StdResult;
+use std::{fs, io};
+
+use indexmap::IndexSet;
+use itertools::Itertools as _;
+
+use super::port;
+
+type Result<T> = StdResult<T, Error>;
+
+#[non_exhaustive]
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum Error {
+    #[error(
+        "no built-in port `{name}`, expected one of {}",
+        ports().iter().map(|port| format!("`{port}`")).join(", ")
+    )]
+    UnknownPort { name: String },
+
+    #[error("`{path}` already exists, pass `--force` to overwrite it")]
+    Exists { path: String },
+
+    #[error("failed to write `{path}`: {src}")]
+    Writing { path: String, src: io::Error },
+}
+
+/// Every built-in template, by its name under `templates/`.
+pub(crate) const TEMPLATES: [(&str, &str); 7] = [
+    (
+        "assets/circles/SWATCH.svg.jinja",
+        include_str!("../../templates/assets/circles/SWATCH.svg.jinja"),
+    ),
+    (
+        "ports/fish/SCHEME.theme.jinja",
+        include_str!("../../templates/ports/fish/SCHEME.theme.jinja"),
+    ),
+    (
+        "ports/ghostty/SCHEME.jinja",
+        include_str!("../../templates/ports/ghostty/SCHEME.jinja"),
+    ),
+    (
+        "ports/helix/SCHEME.toml.jinja",
+        include_str!("../../templates/ports/helix/SCHEME.toml.jinja"),
+    ),
+    (
+        "ports/iterm2/SCHEME.itermcolors.jinja",
+        include_str!("../../templates/ports/iterm2/SCHEME.itermcolors.jinja"),
+    ),
+    (
+        "ports/kitty/SCHEME.conf.jinja",
+        include_str!("../../templates/ports/kitty/SCHEME.conf.jinja"),
+    ),
+    (
+        "ports/starship/SCHEME.toml.jinja",
+        include_str!("../../templates/ports/starship/SCHEME.toml.jinja"),
+    ),
+];
+
+/// Names of every built-in port.
+#[must_use]
+pub(crate) fn ports() -> IndexSet<&'static str> {
+    TEMPLATES
+        .iter()
+        .filter_map(|(name, _)| port(name))
+        .collect()
+}
+
+/// The built-in templates of `port`.
+#[must_use]
+pub(crate) fn templates(port_name: &str) -> Vec<(&'static str, &'static str)> {
+    TEMPLATES
+        .iter()
+        .filter(|(name, _)| port(name) == Some(port_name))
+        .copied()
+        .collect()
+}
+
+/// Copies the built-in templates of `port_name` into the templates
+/// directory `dir`, where they shadow the built-in ones. Nothing is written
+/// if any of them exists, unless `force` is set.
+pub(crate) fn eject(
+    port_name: &str,
+    dir: &Path,
+    force: bool,
+) -> Result<Vec<PathBuf>> {
+    let templates = templates(port_name);
+    if templates.is_empty() {
+        return Err(Error::UnknownPort {
+            name: port_name.to_owned(),
+        });
+    }
+
+    let targets = templates
+        .iter()
+        .map(|&(name, content)| (dir.join(name), content))
+        .collect::<Vec<_>>();
+
+    if !force && let Some((path, _)) = targets.iter().find(|(p, _)| p.exists())
+    {
+        return Err(Error::Exists {
+            path: path.display().to_string(),
+        });
+    }
+
+    for (path, content) in &targets {
+        let writing = |src| Error::Writing {
+            path: path.display().to_string(),
+            src,
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(writing)?;
+        }
+        fs::write(path, content).map_err(writing)?;
+    }
+
+    Ok(targets.into_iter().map(|(path, _)| path).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::slice;
+
+    use super::*;
+
+    #[test]
+    fn templates_belong_to_ports() {
+        assert_eq!(port("ports/fish/SCHEME.theme.jinja"), Some("fish"));
+        assert_eq!(port("assets/circles/SWATCH.svg.jinja"), Some("circles"));
+        assert_eq!(port("_macros.md.jinja"), None);
+
+        assert!(ports().contains("helix"));
+        assert_eq!(
+            templates("kitty")
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>(),
+            ["ports/kitty/SCHEME.conf.jinja"]
+        );
+        assert!(templates("nope").is_empty());
+    }
+
+    #[test]
+    fn ejecting_copies_a_port() {
+        let dir = tempfile::tempdir().expect("temp dir");
+
+        let ejected = eject("fish", dir.path(), false).expect("ejected");
+        let path = dir.path().join("ports/fish/SCHEME.theme.jinja");
+        assert_eq!(ejected, slice::from_ref(&path));
+        assert_eq!(fs::read_to_string(&path).expect("written"), TEMPLATES[1].1);
+
+        assert!(matches!(
+            eject("fish", dir.path(), false),
+            Err(Error::Exists { .. })
+        ));
+        eject("fish", dir.path(), true).expect("overwritten");
+        assert!(matches!(
+            eject("nope", dir.path(), false),
+            Err(Error::UnknownPort { .. })
+        ));
+    }
+}
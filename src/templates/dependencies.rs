@@ -0,0 +1,130 @@
+//! Which templates each template pulls in, by literal name.
+
+use std::sync::LazyLock;
+
+use indexmap::{IndexMap, IndexSet};
+use regex::Regex;
+
+#[expect(clippy::expect_used, reason = "the pattern is constant")]
+static TAG: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?s)\{%[-+]?\s*(?:include|import|from|extends)\b(.*?)[-+]?%\}")
+        .expect("regex should be valid")
+});
+
+#[expect(clippy::expect_used, reason = "the pattern is constant")]
+static NAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#""([^"]+)"|'([^']+)'"#).expect("regex should be valid")
+});
+
+/// Names of the templates `source` refers to directly.
+#[must_use]
+pub(crate) fn of(source: &str) -> IndexSet<String> {
+    TAG.captures_iter(source)
+        .filter_map(|tag| tag.get(1))
+        .flat_map(|args| NAME.captures_iter(args.as_str()))
+        .filter_map(|name| name.get(1).or_else(|| name.get(2)))
+        .map(|name| name.as_str().to_owned())
+        .collect()
+}
+
+/// Every template `name` depends on, directly or through other templates,
+/// including `name` itself.
+#[must_use]
+pub(crate) fn closure(
+    sources: &IndexMap<&str, &str>,
+    name: &str,
+) -> IndexSet<String> {
+    let mut found = IndexSet::from([name.to_owned()]);
+    let mut next = 0;
+
+    while let Some(current) = found.get_index(next) {
+        if let Some(source) = sources.get(current.as_str()) {
+            found.extend(of(source));
+        }
+        next += 1;
+    }
+
+    found
+}
+
+/// Every template that depends on any of `changed`, directly or through
+/// other templates, including `changed` itself.
+#[must_use]
+pub(crate) fn dependents(
+    sources: &[(&str, &str)],
+    changed: &IndexSet<String>,
+) -> IndexSet<String> {
+    let mut affected = changed.clone();
+
+    loop {
+        let before = affected.len();
+
+        for &(name, source) in sources {
+            if !affected.contains(name)
+                && of(source).iter().any(|dep| affected.contains(dep))
+            {
+                affected.insert(name.to_owned());
+            }
+        }
+
+        if affected.len() == before {
+            return affected;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literal_names_are_found() {
+        let source = r#"
+            {% import "_macros.md.jinja" as macros %}
+            {%- from '_colors.jinja' import swatch -%}
+            {% include ["_a.jinja", "_b.jinja"] ignore missing %}
+            {% include name %}
+            {{ "_not_a_dependency.jinja" }}
+        "#;
+
+        assert_eq!(of(source).into_iter().collect::<Vec<_>>(), [
+            "_macros.md.jinja",
+            "_colors.jinja",
+            "_a.jinja",
+            "_b.jinja"
+        ]);
+    }
+
+    #[test]
+    fn dependents_are_transitive() {
+        let sources = [
+            ("_base.jinja", ""),
+            ("_macros.jinja", r#"{% extends "_base.jinja" %}"#),
+            ("a.jinja", r#"{% import "_macros.jinja" as m %}"#),
+            ("b.jinja", "no dependencies"),
+        ];
+
+        let affected =
+            dependents(&sources, &IndexSet::from(["_base.jinja".to_owned()]));
+
+        assert!(affected.contains("a.jinja"));
+        assert!(affected.contains("_macros.jinja"));
+        assert!(!affected.contains("b.jinja"));
+    }
+
+    #[test]
+    fn closure_follows_dependencies() {
+        let sources = IndexMap::from([
+            ("_base.jinja", ""),
+            ("_macros.jinja", r#"{% extends "_base.jinja" %}"#),
+            ("a.jinja", r#"{% import "_macros.jinja" as m %}"#),
+            ("b.jinja", r#"{% include "_missing.jinja" %}"#),
+        ]);
+
+        assert_eq!(
+            closure(&sources, "a.jinja").into_iter().collect::<Vec<_>>(),
+            ["a.jinja", "_macros.jinja", "_base.jinja"]
+        );
+        assert_eq!(closure(&sources, "b.jinja").len(), 2);
+    }
+}
@@ -6,6 +6,8 @@ use indexmap::{IndexMap, IndexSet};
 use itertools::Itertools as _;
 
 use self::DirectiveType::{Other, Theymer};
+use super::AGGREGATE_MARKER;
+use super::output::{Error as OutputError, OutputPath};
 use crate::PathExt as _;
 use crate::output::{ColorStyle, Style, TextStyle};
 
@@ -36,6 +38,9 @@ pub(crate) enum Error {
         directive: String,
         path: String,
     },
+
+    #[error("invalid `output` directive in `{path}`: {src}")]
+    Output { path: String, src: OutputError },
 }
 
 fn format_list(directives: &[String]) -> String {
@@ -43,10 +48,14 @@ fn format_list(directives: &[String]) -> String {
 }
 
 #[non_exhaustive]
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub(crate) struct Directives {
     pub style: Arc<Style>,
     pub source: Option<String>,
+    /// Renders once with every scheme, instead of once per scheme.
+    pub aggregate: bool,
+    /// Where to render to, instead of mirroring the template's path.
+    pub output: Option<OutputPath>,
     pub passthrough: IndexSet<String>,
 }
 
@@ -79,6 +88,18 @@ impl Directives {
 
         let style = Arc::new(Self::extract_style(&mut theymer, name)?);
         let source = theymer.shift_remove("source");
+        let aggregate = match theymer.shift_remove("aggregate") {
+            Some(v) => Self::parse_bool("aggregate", &v, path)?,
+            None => name.contains(AGGREGATE_MARKER),
+        };
+        let output = theymer
+            .shift_remove("output")
+            .map(|pattern| OutputPath::parse(&pattern, aggregate))
+            .transpose()
+            .map_err(|src| Error::Output {
+                path: path.to_owned(),
+                src,
+            })?;
 
         // TODO: refactor into own function
         if !theymer.is_empty() {
@@ -99,12 +120,29 @@ impl Directives {
             Self {
                 style,
                 source,
+                aggregate,
+                output,
                 passthrough,
             },
             filtered,
         ))
     }
 
+    /// Everything the directives change about the output, to tell when they
+    /// changed since the last render.
+    #[must_use]
+    pub(crate) fn fingerprint(&self) -> String {
+        format!(
+            "{:?} {:?} {:?} {:?} {:?} {:?}",
+            self.style.color,
+            self.style.text,
+            self.source,
+            self.aggregate,
+            self.output.as_ref().map(OutputPath::as_str),
+            self.passthrough
+        )
+    }
+
     pub(crate) fn make_header(&self, output_path: &Path) -> String {
         let mut directives = self.passthrough.clone();
 
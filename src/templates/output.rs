@@ -0,0 +1,303 @@
+//! Output paths set with the `output` directive.
+
+use std::path::{Component, Path, PathBuf};
+use std::result::Result as StdResult;
+
+use itertools::Itertools as _;
+
+const PLACEHOLDERS: [&str; 10] = [
+    "scheme",
+    "scheme_ascii",
+    "swatch",
+    "swatch_ascii",
+    "role",
+    "group",
+    "index",
+    "variant",
+    "appearance",
+    "template_dir",
+];
+
+type Result<T> = StdResult<T, Error>;
+
+#[non_exhaustive]
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum Error {
+    #[error(
+        "unknown placeholder `{{{name}}}`, expected one of {}",
+        PLACEHOLDERS.iter().map(|p| format!("`{{{p}}}`")).join(", ")
+    )]
+    UnknownPlaceholder { name: String },
+
+    #[error("unclosed `{{` in `{pattern}`")]
+    Unclosed { pattern: String },
+
+    #[error("`{pattern}` has to stay inside the render directory")]
+    Escapes { pattern: String },
+
+    #[error(
+        "`{pattern}` needs `{{scheme}}` or `{{scheme_ascii}}`, or every \
+         scheme renders to the same file"
+    )]
+    NoScheme { pattern: String },
+
+    #[error(
+        "`{{{name}}}` in `{pattern}` has no value for aggregate templates, \
+         which render once for every scheme"
+    )]
+    PerScheme { name: String, pattern: String },
+
+    #[error(
+        "`{{{name}}}` in `{pattern}` can't contain a path separator: `{value}`"
+    )]
+    Separator {
+        name: String,
+        value: String,
+        pattern: String,
+    },
+
+    #[error("`{pattern}` expands to `{path}`, outside the render directory")]
+    ExpandedEscapes { pattern: String, path: String },
+}
+
+/// Values for the placeholders of one output.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Values<'a> {
+    pub scheme: &'a str,
+    pub scheme_ascii: &'a str,
+    pub swatch: Option<&'a str>,
+    pub swatch_ascii: Option<&'a str>,
+    pub role: Option<&'a str>,
+    pub group: Option<&'a str>,
+    /// Position of the current item of a template rendered once per item.
+    pub index: Option<&'a str>,
+    /// `meta.custom.variant` if set, otherwise the appearance.
+    pub variant: &'a str,
+    pub appearance: &'a str,
+    /// The template's directory under the templates directory.
+    pub template_dir: &'a str,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct OutputPath {
+    pattern: String,
+}
+
+impl OutputPath {
+    pub(crate) fn parse(pattern: &str, aggregate: bool) -> Result<Self> {
+        let pattern = pattern.trim_matches(['"', '\'']).to_owned();
+        let names = placeholders(&pattern)?;
+
+        if let Some(unknown) =
+            names.iter().find(|name| !PLACEHOLDERS.contains(name))
+        {
+            return Err(Error::UnknownPlaceholder {
+                name: (*unknown).to_owned(),
+            });
+        }
+
+        if aggregate {
+            if let Some(name) =
+                names.iter().find(|&&name| name != "template_dir")
+            {
+                return Err(Error::PerScheme {
+                    name: (*name).to_owned(),
+                    pattern,
+                });
+            }
+        } else if !names
+            .iter()
+            .any(|&name| name == "scheme" || name == "scheme_ascii")
+        {
+            return Err(Error::NoScheme { pattern });
+        }
+
+        let path = Path::new(&pattern);
+        if path.is_absolute()
+            || path.components().any(|c| {
+                matches!(c, Component::ParentDir | Component::Prefix(_))
+            })
+        {
+            return Err(Error::Escapes { pattern });
+        }
+
+        Ok(Self { pattern })
+    }
+
+    #[must_use]
+    pub(crate) fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    #[must_use]
+    pub(crate) fn uses(&self, placeholder: &str) -> bool {
+        placeholders(&self.pattern)
+            .is_ok_and(|names| names.contains(&placeholder))
+    }
+
+    /// The path with every placeholder filled in. Empty components, e.g.
+    /// from `{template_dir}` of a top-level template, are dropped. Values
+    /// come from schemes, so they're checked again once filled in.
+    pub(crate) fn expand(&self, values: &Values<'_>) -> Result<PathBuf> {
+        let mut expanded = String::with_capacity(self.pattern.len());
+        let mut rest = self.pattern.as_str();
+
+        while let Some((before, after)) = rest.split_once('{') {
+            expanded.push_str(before);
+
+            let Some((name, after)) = after.split_once('}') else {
+                break;
+            };
+            let value = value(name, values);
+            if name.trim() != "template_dir" && value.contains(['/', '\\']) {
+                return Err(Error::Separator {
+                    name: name.trim().to_owned(),
+                    value: value.to_owned(),
+                    pattern: self.pattern.clone(),
+                });
+            }
+
+            expanded.push_str(value);
+            rest = after;
+        }
+        expanded.push_str(rest);
+
+        let path = expanded
+            .split('/')
+            .filter(|component| !component.is_empty())
+            .collect::<PathBuf>();
+        if path.components().any(|c| {
+            matches!(
+                c,
+                Component::ParentDir
+                    | Component::RootDir
+                    | Component::Prefix(_)
+            )
+        }) {
+            return Err(Error::ExpandedEscapes {
+                pattern: self.pattern.clone(),
+                path: expanded,
+            });
+        }
+
+        Ok(path)
+    }
+}
+
+fn placeholders(pattern: &str) -> Result<Vec<&str>> {
+    let mut names = Vec::new();
+    let mut rest = pattern;
+
+    while let Some((_, after)) = rest.split_once('{') {
+        let (name, after) =
+            after.split_once('}').ok_or_else(|| Error::Unclosed {
+                pattern: pattern.to_owned(),
+            })?;
+
+        names.push(name.trim());
+        rest = after;
+    }
+
+    Ok(names)
+}
+
+fn value<'a>(name: &str, values: &Values<'a>) -> &'a str {
+    match name.trim() {
+        "scheme" => values.scheme,
+        "scheme_ascii" => values.scheme_ascii,
+        "swatch" => values.swatch.unwrap_or_default(),
+        "swatch_ascii" => values.swatch_ascii.unwrap_or_default(),
+        "role" => values.role.unwrap_or_default(),
+        "group" => values.group.unwrap_or_default(),
+        "index" => values.index.unwrap_or_default(),
+        "variant" => values.variant,
+        "appearance" => values.appearance,
+        "template_dir" => values.template_dir,
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALUES: Values<'static> = Values {
+        scheme: "château",
+        scheme_ascii: "chateau",
+        swatch: Some("rosé"),
+        swatch_ascii: Some("rose"),
+        role: None,
+        group: None,
+        index: Some("0"),
+        variant: "dark",
+        appearance: "dark",
+        template_dir: "",
+    };
+
+    #[test]
+    fn placeholders_are_filled_in() {
+        let output = OutputPath::parse(
+            r#""{template_dir}/{appearance}/{scheme_ascii}/{swatch}.svg""#,
+            false,
+        )
+        .expect("valid output");
+
+        assert!(output.uses("swatch"));
+        assert!(!output.uses("role"));
+        assert_eq!(
+            output.expand(&VALUES).expect("valid values"),
+            Path::new("dark").join("chateau").join("rosé.svg")
+        );
+    }
+
+    #[test]
+    fn invalid_outputs_are_rejected() {
+        assert!(matches!(
+            OutputPath::parse("{scheme}/{colour}.toml", false),
+            Err(Error::UnknownPlaceholder { name }) if name == "colour"
+        ));
+        assert!(matches!(
+            OutputPath::parse("{scheme", false),
+            Err(Error::Unclosed { .. })
+        ));
+        assert!(matches!(
+            OutputPath::parse("../{scheme}.toml", false),
+            Err(Error::Escapes { .. })
+        ));
+        assert!(matches!(
+            OutputPath::parse("/etc/{scheme}.toml", false),
+            Err(Error::Escapes { .. })
+        ));
+        assert!(matches!(
+            OutputPath::parse("themes/{appearance}.toml", false),
+            Err(Error::NoScheme { .. })
+        ));
+        assert!(matches!(
+            OutputPath::parse("{template_dir}/{scheme}.nix", true),
+            Err(Error::PerScheme { name, .. }) if name == "scheme"
+        ));
+        OutputPath::parse("{template_dir}/themes.nix", true)
+            .expect("valid aggregate output");
+    }
+
+    #[test]
+    fn values_cant_leave_the_render_directory() {
+        let output = OutputPath::parse("{scheme}/{variant}.toml", false)
+            .expect("valid output");
+
+        assert!(matches!(
+            output.expand(&Values {
+                variant: "../../../.bashrc",
+                ..VALUES
+            }),
+            Err(Error::Separator { name, .. }) if name == "variant"
+        ));
+        assert!(matches!(
+            output.expand(&Values {
+                scheme: "..",
+                ..VALUES
+            }),
+            Err(Error::ExpandedEscapes { .. })
+        ));
+    }
+}
@@ -1,13 +1,25 @@
+mod pattern;
+
 use std::result::Result as StdResult;
+use std::sync::LazyLock;
 
 use git_url_parse::GitUrl;
 use git_url_parse::types::provider::GenericProvider;
 use globset::{Glob, GlobMatcher};
-use regex::Regex;
 use url::Url;
 
+pub(crate) use self::pattern::Error as PatternError;
+use self::pattern::{Components, Pattern};
 use crate::config::Provider;
 
+#[expect(clippy::expect_used, reason = "the pattern is constant")]
+static REPO: LazyLock<Pattern> = LazyLock::new(|| {
+    Pattern::parse("{host}/{owner}/{repo}").expect("pattern should be valid")
+});
+
+/// Used when neither the config nor the remote says otherwise.
+const DEFAULT_BRANCH: &str = "main";
+
 type Result<T> = StdResult<T, Error>;
 
 #[non_exhaustive]
@@ -31,13 +43,16 @@ pub(crate) enum Error {
 
     #[error("error parsing pattern for provider `{host}`: {src}")]
     ParsingGlob { host: String, src: globset::Error },
+
+    #[error("error parsing url pattern for provider `{host}`: {src}")]
+    ParsingPattern { host: String, src: PatternError },
 }
 
 #[derive(Debug, Clone)]
 pub(crate) struct Resolved {
     host: String,
-    blob_path: String,
-    raw_path: String,
+    blob: Pattern,
+    raw: Pattern,
     branch: Option<String>,
     matcher: GlobMatcher,
 }
@@ -51,31 +66,37 @@ impl Provider {
             })?
             .compile_matcher();
 
-        Ok(Resolved {
-            host: self.host.clone(),
-            blob_path: self.blob_path.ok_or_else(|| {
-                Error::MissingRequired {
-                    field: "blob_path".to_owned(),
-                    host: self.host.clone(),
-                }
-            })?,
-            raw_path: self.raw_path.ok_or_else(|| Error::MissingRequired {
-                field: "raw_path".to_owned(),
+        let pattern = |field: &str, path: Option<String>| {
+            let path = path.ok_or_else(|| Error::MissingRequired {
+                field: field.to_owned(),
+                host: self.host.clone(),
+            })?;
+
+            Pattern::parse(&path).map_err(|src| Error::ParsingPattern {
                 host: self.host.clone(),
-            })?,
+                src,
+            })
+        };
+
+        Ok(Resolved {
+            blob: pattern("blob_path", self.blob_path.clone())?,
+            raw: pattern("raw_path", self.raw_path.clone())?,
+            host: self.host,
             branch: self.branch,
             matcher,
         })
     }
 }
 
-#[derive(Debug)]
-struct Components<'a> {
-    host: &'a str,
-    owner: &'a str,
-    repo: &'a str,
-    ref_: &'a str,
-    file: &'a str,
+/// What a blob url points at in a repo.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Refs<'a> {
+    /// Overrides the provider's branch.
+    pub branch: Option<&'a str>,
+    /// The remote's default branch, used if neither sets one.
+    pub default_branch: Option<&'a str>,
+    /// Fills in `{sha}`, which falls back to the branch without it.
+    pub sha: Option<&'a str>,
 }
 
 pub(crate) fn resolve(merged: &[Provider]) -> Result<Vec<Resolved>> {
@@ -94,35 +115,20 @@ pub(crate) fn resolve(merged: &[Provider]) -> Result<Vec<Resolved>> {
     Ok(resolved)
 }
 
+/// The raw url of the file at the blob url `url`.
 pub(crate) fn resolve_blob(
     url: &str,
     providers: &[Resolved],
 ) -> Result<String> {
-    let parsed = Url::parse(url).map_err(|src| Error::ParsingUrl {
-        url: url.to_owned(),
-        src,
-    })?;
-
-    let host = parsed.host_str().ok_or_else(|| Error::Nohost {
-        url: url.to_owned(),
-    })?;
-
-    let provider =
-        find_matching(host, providers).ok_or_else(|| Error::NoneFound {
-            host: host.to_owned(),
-        })?;
+    let (provider, components) = parse_blob(url, providers)?;
 
-    let components = extract_components(&parsed, url)?;
-
-    let raw = apply(&provider.raw_path, &components);
-
-    Ok(format!("https://{raw}"))
+    Ok(format!("https://{}", provider.raw.format(&components)))
 }
 
 pub(crate) fn build_blob(
     url: &GitUrl,
     path: &str,
-    ref_: &str,
+    refs: &Refs<'_>,
     providers: &[Resolved],
 ) -> Result<String> {
     let host = url.host().ok_or_else(|| Error::Nohost {
@@ -134,38 +140,76 @@ pub(crate) fn build_blob(
             host: host.to_owned(),
         })?;
 
+    let (owner, repo) = owner_and_repo(url)?;
+
+    let reference = refs
+        .branch
+        .or(resolved.branch.as_deref())
+        .or(refs.default_branch)
+        .unwrap_or(DEFAULT_BRANCH);
+
+    let components = Components {
+        host: host.to_owned(),
+        owner,
+        repo,
+        reference: reference.to_owned(),
+        sha: refs.sha.map(str::to_owned),
+        file: path.to_owned(),
+    };
+
+    Ok(format!("https://{}", resolved.blob.format(&components)))
+}
+
+/// The owner and name of the repo at `url`.
+pub(crate) fn owner_and_repo(url: &GitUrl) -> Result<(String, String)> {
     let provider = url.provider_info::<GenericProvider>().map_err(|_src| {
         Error::UnrecognizedPattern {
             url: url.to_string(),
         }
     })?;
 
-    let owner = provider.owner();
-    let repo = provider.repo();
+    Ok((provider.owner().to_owned(), provider.repo().to_owned()))
+}
 
-    let blob = resolved
-        .blob_path
-        .replace("{host}", host)
-        .replace("{owner}", owner)
-        .replace("{repo}", repo)
-        .replace("{ref}", ref_)
-        .replace("{file}", path);
+/// The url of the repo the blob url `blob` points into.
+pub(crate) fn extract_repo_url(
+    blob: &str,
+    providers: &[Resolved],
+) -> Result<String> {
+    let (_, components) = parse_blob(blob, providers)?;
 
-    Ok(format!("https://{blob}"))
+    Ok(format!("https://{}", REPO.format(&components)))
 }
 
-pub(crate) fn extract_repo_url(blob: &str) -> Result<Option<String>> {
-    let parsed = Url::parse(blob).map_err(|src| Error::ParsingUrl {
-        url: blob.to_owned(),
+/// Parses `url` with the blob pattern of the provider for its host.
+fn parse_blob<'a>(
+    url: &str,
+    providers: &'a [Resolved],
+) -> Result<(&'a Resolved, Components)> {
+    let parsed = Url::parse(url).map_err(|src| Error::ParsingUrl {
+        url: url.to_owned(),
         src,
     })?;
 
-    let components = extract_components(&parsed, blob)?;
+    let host = parsed.host_str().ok_or_else(|| Error::Nohost {
+        url: url.to_owned(),
+    })?;
+
+    let provider =
+        find_matching(host, providers).ok_or_else(|| Error::NoneFound {
+            host: host.to_owned(),
+        })?;
+
+    let mut components = provider.blob.parse_url(url).ok_or_else(|| {
+        Error::UnrecognizedPattern {
+            url: url.to_owned(),
+        }
+    })?;
+    if components.host.is_empty() {
+        host.clone_into(&mut components.host);
+    }
 
-    Ok(Some(format!(
-        "https://{}/{}/{}",
-        components.host, components.owner, components.repo
-    )))
+    Ok((provider, components))
 }
 
 // TODO: refactor to use constants (and iterate over them?)
@@ -207,51 +251,110 @@ fn find_matching<'a>(
     providers.iter().find(|&p| p.matcher.is_match(host))
 }
 
-fn extract_components<'a>(
-    url: &'a Url,
-    original: &str,
-) -> Result<Components<'a>> {
-    let path = url.path();
-
-    // TODO: verify correctness and robustness
-    let re = Regex::new(
-        "^/(?P<owner>[^/]+)/(?P<repo>[^/]+)(?:/(?:-|src/branch))?/(?:\
-         blob|raw)/(?P<ref>[^/]+)/(?P<file>.+)$",
-    )
-    .expect("regex should be valid");
-
-    let caps = re
-        .captures(path)
-        .ok_or_else(|| Error::UnrecognizedPattern {
-            url: original.to_owned(),
-        })?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
 
-    Ok(Components {
-        host: url.host_str().expect("already validated by regex").as_str(),
-        owner: caps
-            .name("owner")
-            .expect("already validated by regex")
-            .as_str(),
-        repo: caps
-            .name("repo")
-            .expect("already validated by regex")
-            .as_str(),
-        ref_: caps
-            .name("ref")
-            .expect("already validated by regex")
-            .as_str(),
-        file: caps
-            .name("file")
-            .expect("already validated by regex")
-            .as_str(),
-    })
-}
+    fn providers() -> Vec<Resolved> {
+        resolve(&Config::default().providers).expect("valid defaults")
+    }
 
-fn apply(pattern: &str, components: &Components<'_>) -> String {
-    pattern
-        .replace("{host}", components.host)
-        .replace("{owner}", components.owner)
-        .replace("{repo}", components.repo)
-        .replace("{ref}", components.ref_)
-        .replace("{file}", components.file)
+    #[test]
+    fn branches_and_commits_fill_in_blob_urls() {
+        let url = GitUrl::parse("git@github.com:cutiepro/kitty.git")
+            .expect("valid url");
+        let mut providers = providers();
+
+        let refs = Refs {
+            branch: None,
+            default_branch: Some("trunk"),
+            sha: Some("abc123"),
+        };
+        assert_eq!(
+            build_blob(&url, "cutiepro.conf", &refs, &providers)
+                .expect("built"),
+            "https://github.com/cutiepro/kitty/blob/trunk/cutiepro.conf"
+        );
+
+        for provider in &mut providers {
+            provider.blob =
+                Pattern::parse("{host}/{owner}/{repo}/blob/{sha}/{file}")
+                    .expect("valid pattern");
+            provider.branch = Some("dev".to_owned());
+        }
+        assert_eq!(
+            build_blob(&url, "a.conf", &refs, &providers).expect("built"),
+            "https://github.com/cutiepro/kitty/blob/abc123/a.conf"
+        );
+        assert_eq!(
+            build_blob(&url, "a.conf", &Refs::default(), &providers)
+                .expect("built"),
+            "https://github.com/cutiepro/kitty/blob/dev/a.conf"
+        );
+    }
+
+    #[test]
+    fn raw_urls_resolve_for_every_default_provider() {
+        let providers = providers();
+
+        for (blob, raw) in [
+            (
+                "https://github.com/o/r/blob/main/a/b.toml",
+                "https://raw.githubusercontent.com/o/r/main/a/b.toml",
+            ),
+            (
+                "https://gitlab.com/o/r/-/blob/main/b.toml",
+                "https://gitlab.com/o/r/-/raw/main/b.toml",
+            ),
+            (
+                "https://codeberg.org/o/r/src/branch/main/b.toml",
+                "https://codeberg.org/o/r/raw/branch/main/b.toml",
+            ),
+            (
+                "https://bitbucket.org/o/r/src/main/b.toml",
+                "https://bitbucket.org/o/r/raw/main/b.toml",
+            ),
+        ] {
+            assert_eq!(resolve_blob(blob, &providers).expect("resolved"), raw);
+        }
+    }
+
+    #[test]
+    fn custom_providers_build_and_parse_encoded_urls() {
+        let providers = resolve(&[Provider {
+            host: "git.sr.ht".to_owned(),
+            blob_path: Some(
+                "{host}/{owner}/{repo}/tree/{ref}/item/{file}".to_owned(),
+            ),
+            raw_path: Some(
+                "{host}/{owner}/{repo}/blob/{ref}/{file}".to_owned(),
+            ),
+            branch: None,
+        }])
+        .expect("valid provider");
+        let url =
+            GitUrl::parse("https://git.sr.ht/~they/ports").expect("valid url");
+
+        let blob = build_blob(
+            &url,
+            "themes/THEME Latte.toml",
+            &Refs::default(),
+            &providers,
+        )
+        .expect("built");
+        assert_eq!(
+            blob,
+            "https://git.sr.ht/~they/ports/tree/main/item/themes/\
+             THEME%20Latte.toml"
+        );
+        assert_eq!(
+            resolve_blob(&blob, &providers).expect("resolved"),
+            "https://git.sr.ht/~they/ports/blob/main/themes/THEME%20Latte.toml"
+        );
+        assert_eq!(
+            extract_repo_url(&blob, &providers).expect("extracted"),
+            "https://git.sr.ht/~they/ports"
+        );
+    }
 }
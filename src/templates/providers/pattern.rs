@@ -0,0 +1,299 @@
+//! Url patterns of providers, e.g. `{host}/{owner}/{repo}/blob/{ref}/{file}`.
+
+use std::result::Result as StdResult;
+
+use indexmap::IndexSet;
+use itertools::Itertools as _;
+use percent_encoding::{AsciiSet, CONTROLS, NON_ALPHANUMERIC};
+use regex::Regex;
+
+const PLACEHOLDERS: [&str; 6] = ["host", "owner", "repo", "ref", "sha", "file"];
+
+/// Everything but unreserved characters, for a single path segment or query
+/// value.
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+/// Like [`SEGMENT`], but values can span several path segments.
+const PATH: &AsciiSet = &SEGMENT.remove(b'/');
+
+type Result<T> = StdResult<T, Error>;
+
+#[non_exhaustive]
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum Error {
+    #[error(
+        "unknown placeholder `{{{name}}}` in `{pattern}`, expected one of {}",
+        PLACEHOLDERS.iter().map(|p| format!("`{{{p}}}`")).join(", ")
+    )]
+    UnknownPlaceholder { name: String, pattern: String },
+
+    #[error("unclosed `{{` in `{pattern}`")]
+    Unclosed { pattern: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Placeholder {
+    Host,
+    Owner,
+    Repo,
+    Ref,
+    Sha,
+    File,
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+/// What a url points at.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Components {
+    pub host: String,
+    pub owner: String,
+    pub repo: String,
+    pub reference: String,
+    /// Falls back to `reference` in urls without one.
+    pub sha: Option<String>,
+    pub file: String,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Pattern {
+    segments: Vec<Segment>,
+    matcher: Regex,
+}
+
+impl Placeholder {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "host" => Self::Host,
+            "owner" => Self::Owner,
+            "repo" => Self::Repo,
+            "ref" => Self::Ref,
+            "sha" => Self::Sha,
+            "file" => Self::File,
+            _ => return None,
+        })
+    }
+
+    fn value(self, components: &Components) -> &str {
+        match self {
+            Self::Host => &components.host,
+            Self::Owner => &components.owner,
+            Self::Repo => &components.repo,
+            Self::Ref => &components.reference,
+            Self::Sha => {
+                components.sha.as_deref().unwrap_or(&components.reference)
+            }
+            Self::File => &components.file,
+        }
+    }
+
+    /// Hosts are left as they are. Owners (with subgroups), refs and files
+    /// can contain `/`.
+    const fn encode_set(self) -> &'static AsciiSet {
+        match self {
+            Self::Host => CONTROLS,
+            Self::Owner | Self::Ref | Self::Sha | Self::File => PATH,
+            Self::Repo => SEGMENT,
+        }
+    }
+
+    /// What the placeholder matches, which stops at the next `/` unless it
+    /// can contain one. Those are lazy but for the file, which always ends
+    /// the path.
+    const fn class(self, in_query: bool) -> &'static str {
+        match self {
+            _ if in_query => "[^&;#]+",
+            Self::Host | Self::Repo => "[^/?#]+",
+            Self::Owner | Self::Ref | Self::Sha => "[^?#]+?",
+            Self::File => "[^?#]+",
+        }
+    }
+
+    const fn group(self) -> &'static str {
+        match self {
+            Self::Host => "host",
+            Self::Owner => "owner",
+            Self::Repo => "repo",
+            Self::Ref => "ref",
+            Self::Sha => "sha",
+            Self::File => "file",
+        }
+    }
+}
+
+impl Pattern {
+    pub(crate) fn parse(pattern: &str) -> Result<Self> {
+        let mut segments = Vec::new();
+        let mut rest = pattern;
+
+        while let Some((before, after)) = rest.split_once('{') {
+            if !before.is_empty() {
+                segments.push(Segment::Literal(before.to_owned()));
+            }
+
+            let (name, after) =
+                after.split_once('}').ok_or_else(|| Error::Unclosed {
+                    pattern: pattern.to_owned(),
+                })?;
+            let placeholder =
+                Placeholder::parse(name.trim()).ok_or_else(|| {
+                    Error::UnknownPlaceholder {
+                        name: name.trim().to_owned(),
+                        pattern: pattern.to_owned(),
+                    }
+                })?;
+
+            segments.push(Segment::Placeholder(placeholder));
+            rest = after;
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_owned()));
+        }
+
+        let matcher = matcher(&segments);
+
+        Ok(Self { segments, matcher })
+    }
+
+    /// The url without its scheme, with every placeholder filled in and
+    /// percent-encoded.
+    #[must_use]
+    pub(crate) fn format(&self, components: &Components) -> String {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Literal(literal) => literal.clone(),
+                Segment::Placeholder(placeholder) => {
+                    percent_encoding::utf8_percent_encode(
+                        placeholder.value(components),
+                        placeholder.encode_set(),
+                    )
+                    .to_string()
+                }
+            })
+            .collect()
+    }
+
+    /// The components of `url`, with or without its scheme, if it matches
+    /// the pattern.
+    #[must_use]
+    pub(crate) fn parse_url(&self, url: &str) -> Option<Components> {
+        let captures = self.matcher.captures(url)?;
+        let get = |name: &str| {
+            captures.name(name).map(|value| {
+                percent_encoding::percent_decode_str(value.as_str())
+                    .decode_utf8_lossy()
+                    .into_owned()
+            })
+        };
+
+        let sha = get("sha");
+        let reference = get("ref").or_else(|| sha.clone());
+
+        Some(Components {
+            host: get("host").unwrap_or_default(),
+            owner: get("owner").unwrap_or_default(),
+            repo: get("repo").unwrap_or_default(),
+            reference: reference.unwrap_or_default(),
+            sha,
+            file: get("file").unwrap_or_default(),
+        })
+    }
+}
+
+/// A regex matching the urls built from `segments`. A placeholder used more
+/// than once is only captured the first time.
+#[expect(clippy::expect_used, reason = "literals are escaped")]
+fn matcher(segments: &[Segment]) -> Regex {
+    let mut seen = IndexSet::new();
+    let mut in_query = false;
+
+    let body = segments
+        .iter()
+        .map(|segment| match segment {
+            Segment::Literal(literal) => {
+                in_query |= literal.contains('?');
+                regex::escape(literal)
+            }
+            Segment::Placeholder(placeholder) => {
+                let class = placeholder.class(in_query);
+
+                if seen.insert(*placeholder) {
+                    format!("(?P<{}>{class})", placeholder.group())
+                } else {
+                    format!("(?:{class})")
+                }
+            }
+        })
+        .collect::<String>();
+
+    Regex::new(&format!("^(?:https?://)?{body}(?:#.*)?$"))
+        .expect("regex should be valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn components() -> Components {
+        Components {
+            host: "git.example.com".to_owned(),
+            owner: "~they".to_owned(),
+            repo: "ports".to_owned(),
+            reference: "main".to_owned(),
+            sha: None,
+            file: "themes/THEME Latte+#1.toml".to_owned(),
+        }
+    }
+
+    #[test]
+    fn urls_round_trip() {
+        for (pattern, url) in [
+            (
+                "{host}/{owner}/{repo}/tree/{ref}/item/{file}",
+                "git.example.com/~they/ports/tree/main/item/themes/THEME%\
+                 20Latte%2B%231.toml",
+            ),
+            (
+                "{host}/{repo}/plain/{file}?h={ref}&id={sha}",
+                "git.example.com/ports/plain/themes/THEME%20Latte%2B%231.toml?\
+                 h=main&id=main",
+            ),
+            (
+                "{host}/{owner}/{repo}?path={file}&ref={ref}",
+                "git.example.com/~they/ports?path=themes/THEME%20Latte%2B%231.\
+                 toml&ref=main",
+            ),
+        ] {
+            let pattern = Pattern::parse(pattern).expect("valid pattern");
+            let built = pattern.format(&components());
+            assert_eq!(built, url);
+
+            let parsed = pattern
+                .parse_url(&format!("https://{built}"))
+                .expect("matches");
+            assert_eq!(parsed.file, components().file);
+            assert_eq!(parsed.reference, "main");
+            assert_eq!(parsed.repo, "ports");
+        }
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        assert!(matches!(
+            Pattern::parse("{host}/{branch}/{file}"),
+            Err(Error::UnknownPlaceholder { name, .. }) if name == "branch"
+        ));
+        assert!(matches!(
+            Pattern::parse("{host}/{file"),
+            Err(Error::Unclosed { .. })
+        ));
+    }
+}
@@ -0,0 +1,402 @@
+//! Templates shared between projects, layered under the project's own.
+
+use std::path::{Path, PathBuf};
+use std::result::Result as StdResult;
+use std::{fs, io, iter};
+
+use git2::build::CheckoutBuilder;
+use git2::{Oid, Repository};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::config::TemplateSource;
+use crate::{Config, manifest};
+
+pub(crate) const LOCK_FILENAME: &str = "theymer.lock";
+const LOCK_HEADER: &str =
+    "# Generated by theymer, update with `they update`.\n\n";
+const CACHE_DIR: &str = "sources";
+const REFSPECS: [&str; 3] = [
+    "+refs/heads/*:refs/remotes/origin/*",
+    "+refs/tags/*:refs/tags/*",
+    "+HEAD:refs/remotes/origin/HEAD",
+];
+
+type Result<T> = StdResult<T, Error>;
+
+#[non_exhaustive]
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum Error {
+    #[error("template source `{path}` doesn't exist")]
+    Missing { path: String },
+
+    #[error("failed to create `{path}`: {src}")]
+    CreatingCache { path: String, src: git2::Error },
+
+    #[error("failed to fetch `{url}`: {src}")]
+    Fetching { url: String, src: git2::Error },
+
+    #[error("ref `{reference}` not found in `{url}`")]
+    UnknownRef { url: String, reference: String },
+
+    #[error(
+        "locked commit `{commit}` not found in `{url}`, run `they update` to \
+         lock it again: {src}"
+    )]
+    UnknownCommit {
+        url: String,
+        commit: String,
+        src: git2::Error,
+    },
+
+    #[error("failed to check out `{commit}` of `{url}`: {src}")]
+    CheckingOut {
+        url: String,
+        commit: String,
+        src: git2::Error,
+    },
+
+    #[error("failed to read `{LOCK_FILENAME}`: {src}")]
+    ReadingLock { src: io::Error },
+
+    #[error("failed to parse `{LOCK_FILENAME}`: {src}")]
+    ParsingLock { src: Box<toml::de::Error> },
+
+    #[error("failed to write `{LOCK_FILENAME}`: {src}")]
+    WritingLock { src: io::Error },
+
+    #[error("failed to serialize `{LOCK_FILENAME}`: {src}")]
+    SerializingLock { src: toml::ser::Error },
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Lock {
+    #[serde(default, rename = "source")]
+    sources: Vec<Locked>,
+}
+
+/// The commit a git source was resolved to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Locked {
+    git: String,
+    #[serde(rename = "ref", default, skip_serializing_if = "Option::is_none")]
+    reference: Option<String>,
+    commit: String,
+}
+
+impl Lock {
+    fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content)
+                .map_err(|src| Error::ParsingLock { src: Box::new(src) }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                Ok(Self::default())
+            }
+            Err(src) => Err(Error::ReadingLock { src }),
+        }
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        let content = toml::to_string(self)
+            .map_err(|src| Error::SerializingLock { src })?;
+
+        fs::write(path, format!("{LOCK_HEADER}{content}"))
+            .map_err(|src| Error::WritingLock { src })
+    }
+
+    fn get(&self, url: &str, reference: Option<&str>) -> Option<&str> {
+        self.sources
+            .iter()
+            .find(|locked| {
+                locked.git == url && locked.reference.as_deref() == reference
+            })
+            .map(|locked| locked.commit.as_str())
+    }
+}
+
+/// Every template directory in the order templates are looked up, starting
+/// with the project's own. With `update`, git sources move to the latest
+/// commit of their ref instead of the locked one, and `theymer.lock` is
+/// written. Otherwise sources that aren't locked yet use the latest commit.
+pub(crate) fn layers(config: &Config, update: bool) -> Result<Vec<PathBuf>> {
+    let lock_path = Path::new(LOCK_FILENAME);
+    let lock = Lock::load(lock_path)?;
+    let mut resolved = Lock::default();
+
+    let cache = Path::new(manifest::DIR).join(CACHE_DIR);
+    let mut dirs = vec![PathBuf::from(&config.dirs.templates)];
+
+    for source in &config.template_sources {
+        let (root, dir) = match source {
+            TemplateSource::Local { path, dir } => (PathBuf::from(path), dir),
+            TemplateSource::Git {
+                git,
+                reference,
+                dir,
+            } => {
+                let locked =
+                    lock.get(git, reference.as_deref()).filter(|_| !update);
+                if locked.is_none() && !update {
+                    warn!(
+                        "`{git}` isn't locked yet, run `they update` to lock \
+                         it"
+                    );
+                }
+                let (root, commit) =
+                    check_out(&cache, git, reference.as_deref(), locked)?;
+
+                if update
+                    && lock.get(git, reference.as_deref()) != Some(&commit)
+                {
+                    info!("locked `{git}` to `{commit}`");
+                }
+
+                resolved.sources.push(Locked {
+                    git: git.clone(),
+                    reference: reference.clone(),
+                    commit,
+                });
+
+                (root, dir)
+            }
+        };
+
+        let path = dir.as_ref().map_or_else(|| root.clone(), |d| root.join(d));
+        if !path.is_dir() {
+            return Err(Error::Missing {
+                path: path.display().to_string(),
+            });
+        }
+
+        dirs.push(path);
+    }
+
+    if update && resolved != lock {
+        resolved.save(lock_path)?;
+        info!("wrote `{LOCK_FILENAME}`");
+    }
+
+    Ok(dirs)
+}
+
+/// Template directories that can change without `they update`: the
+/// project's own and those of local sources.
+#[must_use]
+pub(crate) fn local_dirs(config: &Config) -> Vec<PathBuf> {
+    let sources =
+        config
+            .template_sources
+            .iter()
+            .filter_map(|source| match source {
+                TemplateSource::Local { path, dir } => {
+                    Some(dir.as_ref().map_or_else(
+                        || PathBuf::from(path),
+                        |d| Path::new(path).join(d),
+                    ))
+                }
+                TemplateSource::Git { .. } => None,
+            });
+
+    iter::once(PathBuf::from(&config.dirs.templates))
+        .chain(sources)
+        .collect()
+}
+
+/// Checks out `url` under `cache` at the `locked` commit, or the latest
+/// commit of `reference` without one, returning the checkout and its commit.
+/// Every ref gets its own checkout, so sources can share a url.
+fn check_out(
+    cache: &Path,
+    url: &str,
+    reference: Option<&str>,
+    locked: Option<&str>,
+) -> Result<(PathBuf, String)> {
+    let hash = manifest::hash(
+        &reference.map_or_else(|| url.to_owned(), |r| format!("{url}#{r}")),
+    );
+    let key = hash.strip_prefix("sha256:").unwrap_or(&hash);
+    let path = cache.join(&key[..16.min(key.len())]);
+
+    let repo = Repository::open(&path)
+        .or_else(|_| Repository::init(&path))
+        .map_err(|src| Error::CreatingCache {
+            path: path.display().to_string(),
+            src,
+        })?;
+
+    let commit = if let Some(commit) =
+        locked.and_then(|commit| Oid::from_str(commit).ok())
+    {
+        if repo.find_commit(commit).is_err() {
+            fetch(&repo, url)?;
+            repo.find_commit(commit)
+                .map_err(|src| Error::UnknownCommit {
+                    url: url.to_owned(),
+                    commit: commit.to_string(),
+                    src,
+                })?;
+        }
+
+        commit
+    } else {
+        fetch(&repo, url)?;
+        resolve(&repo, url, reference)?
+    };
+
+    let head = repo.head().ok().and_then(|head| head.target());
+    if head != Some(commit) {
+        debug!("checking out `{commit}` of `{url}`");
+
+        let error = |src| Error::CheckingOut {
+            url: url.to_owned(),
+            commit: commit.to_string(),
+            src,
+        };
+        let object = repo.find_object(commit, None).map_err(error)?;
+
+        repo.checkout_tree(
+            &object,
+            Some(CheckoutBuilder::new().force().remove_untracked(true)),
+        )
+        .map_err(error)?;
+        repo.set_head_detached(commit).map_err(error)?;
+    }
+
+    Ok((path, commit.to_string()))
+}
+
+fn fetch(repo: &Repository, url: &str) -> Result<()> {
+    info!("fetching `{url}`");
+
+    repo.remote_anonymous(url)
+        .and_then(|mut remote| remote.fetch(&REFSPECS, None, None))
+        .map_err(|src| Error::Fetching {
+            url: url.to_owned(),
+            src,
+        })
+}
+
+/// The commit `reference` points to, as a branch, a tag or a commit hash.
+fn resolve(
+    repo: &Repository,
+    url: &str,
+    reference: Option<&str>,
+) -> Result<Oid> {
+    let name = reference.unwrap_or("HEAD");
+
+    [
+        format!("refs/remotes/origin/{name}"),
+        format!("refs/tags/{name}"),
+        name.to_owned(),
+    ]
+    .iter()
+    .find_map(|spec| {
+        let object = repo.revparse_single(spec).ok()?;
+
+        object.peel_to_commit().ok().map(|commit| commit.id())
+    })
+    .ok_or_else(|| Error::UnknownRef {
+        url: url.to_owned(),
+        reference: name.to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use git2::Signature;
+
+    use super::*;
+
+    fn commit(repo: &Repository, content: &str) -> String {
+        let workdir = repo.workdir().expect("non-bare repo");
+        fs::write(workdir.join("a.txt.jinja"), content).expect("writable");
+
+        let mut index = repo.index().expect("index");
+        index.add_path(Path::new("a.txt.jinja")).expect("added");
+        index.write().expect("index written");
+        let tree = repo
+            .find_tree(index.write_tree().expect("tree written"))
+            .expect("tree");
+
+        let signature = Signature::now("they", "they@example.com")
+            .expect("valid signature");
+        let parent =
+            repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            content,
+            &tree,
+            parent.as_slice().iter().collect::<Vec<_>>().as_slice(),
+        )
+        .expect("committed")
+        .to_string()
+    }
+
+    #[test]
+    fn git_sources_stay_at_the_locked_commit() {
+        let origin_dir = tempfile::tempdir().expect("temp dir");
+        let cache = tempfile::tempdir().expect("temp dir");
+
+        let origin = Repository::init(origin_dir.path()).expect("repo");
+        let url = format!("file://{}", origin_dir.path().display());
+
+        let first = commit(&origin, "first");
+        let (path, locked) =
+            check_out(cache.path(), &url, None, None).expect("checked out");
+        assert_eq!(locked, first);
+
+        let second = commit(&origin, "second");
+        let read = || {
+            fs::read_to_string(path.join("a.txt.jinja")).expect("checked out")
+        };
+
+        let (_, commit) = check_out(cache.path(), &url, None, Some(&first))
+            .expect("checked out");
+        assert_eq!((commit, read()), (first, "first".to_owned()));
+
+        let (_, commit) =
+            check_out(cache.path(), &url, None, None).expect("checked out");
+        assert_eq!((commit, read()), (second, "second".to_owned()));
+
+        assert!(matches!(
+            check_out(cache.path(), &url, Some("missing"), None),
+            Err(Error::UnknownRef { .. })
+        ));
+    }
+
+    #[test]
+    fn refs_of_one_url_are_checked_out_apart() {
+        let origin_dir = tempfile::tempdir().expect("temp dir");
+        let cache = tempfile::tempdir().expect("temp dir");
+
+        let origin = Repository::init(origin_dir.path()).expect("repo");
+        let url = format!("file://{}", origin_dir.path().display());
+
+        let first = commit(&origin, "first");
+        let object = origin.revparse_single(&first).expect("committed");
+        origin
+            .tag_lightweight("v1", &object, false)
+            .expect("tagged");
+        let second = commit(&origin, "second");
+
+        let (latest, commit) =
+            check_out(cache.path(), &url, None, None).expect("checked out");
+        assert_eq!(commit, second);
+        let (tagged, commit) = check_out(cache.path(), &url, Some("v1"), None)
+            .expect("checked out");
+        assert_eq!(commit, first);
+
+        let read = |path: &Path| {
+            fs::read_to_string(path.join("a.txt.jinja")).expect("checked out")
+        };
+        assert_eq!(
+            (read(&latest), read(&tagged)),
+            ("second".to_owned(), "first".to_owned())
+        );
+    }
+}
@@ -0,0 +1,348 @@
+//! Re-rendering on changes to schemes, templates and `theymer.toml`.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::result::Result as StdResult;
+use std::time::{Duration, SystemTime};
+use std::{iter, thread};
+
+use indexmap::{IndexMap, IndexSet};
+use log::{error, info};
+use walkdir::{DirEntry, WalkDir};
+
+use crate::output::WriteMode;
+use crate::render::Selection;
+use crate::templates::{Loader, sources};
+use crate::{Config, PathExt as _, Result, Scheme, config, render, schemes};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// How long files have to stay unchanged before rendering.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Modification times of every watched file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Snapshot(BTreeMap<PathBuf, SystemTime>);
+
+impl Snapshot {
+    fn take(config: &Config) -> Self {
+        let templates = sources::local_dirs(config)
+            .into_iter()
+            .flat_map(|dir| WalkDir::new(dir).into_iter())
+            .filter_map(StdResult::ok)
+            .map(DirEntry::into_path)
+            .filter(|path| path.is_jinja());
+
+        let files = iter::once(PathBuf::from(config::FILENAME))
+            .chain(schemes::find(&config.dirs.schemes))
+            .chain(templates);
+
+        Self(
+            files
+                .filter_map(|path| {
+                    let modified =
+                        path.metadata().and_then(|m| m.modified()).ok()?;
+
+                    Some((path, modified))
+                })
+                .collect(),
+        )
+    }
+
+    /// Paths added, modified or removed since `earlier`.
+    fn changed_since<'a>(
+        &'a self,
+        earlier: &'a Self,
+    ) -> impl Iterator<Item = &'a Path> {
+        let added_or_modified = self
+            .0
+            .iter()
+            .filter(|&(path, modified)| earlier.0.get(path) != Some(modified))
+            .map(|(path, _)| path.as_path());
+
+        let removed = earlier
+            .0
+            .keys()
+            .filter(|path| !self.0.contains_key(*path))
+            .map(PathBuf::as_path);
+
+        added_or_modified.chain(removed)
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Changes {
+    config: bool,
+    schemes: IndexSet<PathBuf>,
+    /// Template names, as the loader knows them.
+    templates: IndexSet<String>,
+}
+
+impl Changes {
+    fn classify<'a, I: IntoIterator<Item = &'a Path>>(
+        paths: I,
+        config: &Config,
+    ) -> Self {
+        let mut changes = Self::default();
+        let template_dirs = sources::local_dirs(config);
+
+        for path in paths {
+            if path == Path::new(config::FILENAME) {
+                changes.config = true;
+            } else if let Some(name) = template_dirs
+                .iter()
+                .find_map(|dir| path.strip_prefix(dir).ok())
+            {
+                changes
+                    .templates
+                    .insert(name.to_string_lossy().replace('\\', "/"));
+            } else {
+                changes.schemes.insert(path.to_owned());
+            }
+        }
+
+        changes
+    }
+}
+
+/// Everything loaded so far. Schemes that fail to load keep their last good
+/// version, so one broken file doesn't stop the others from rendering.
+struct Project {
+    config: Config,
+    schemes: IndexMap<String, Scheme>,
+    templates: Option<Loader>,
+    write_mode: WriteMode,
+    dry_run: bool,
+    /// Pairs outside it are never rendered, whatever changes.
+    selection: Selection,
+}
+
+impl Project {
+    fn reload(&mut self) {
+        self.schemes = schemes::find(&self.config.dirs.schemes)
+            .iter()
+            .filter_map(|path| load_scheme(path))
+            .collect();
+        self.reload_templates();
+    }
+
+    fn reload_templates(&mut self) {
+        self.templates = report(Loader::init(
+            &self.config,
+            &schemes::features(self.schemes.values()),
+        ));
+    }
+
+    fn update(&mut self, changes: &Changes) {
+        if changes.config {
+            info!("`{}` changed, reloading everything", config::FILENAME);
+
+            let Some(config) = report(config::load().map_err(Into::into))
+            else {
+                return;
+            };
+            self.config = config;
+            self.reload();
+            self.render(|_, _| true);
+
+            return;
+        }
+
+        // templates that failed to load last time are all affected
+        let everything = self.templates.is_none();
+
+        let mut changed_schemes = IndexSet::new();
+        let mut removed = Vec::new();
+        for path in &changes.schemes {
+            if path.exists() {
+                if let Some((key, scheme)) = load_scheme(path) {
+                    info!("scheme `{key}` changed");
+
+                    self.schemes.insert(key.clone(), scheme);
+                    changed_schemes.insert(key);
+                }
+            } else if let Some(key) = scheme_key(path) {
+                info!("scheme `{key}` removed");
+
+                if let Some(scheme) = self.schemes.shift_remove(&key) {
+                    removed.push(scheme.name.as_str().to_owned());
+                }
+            }
+        }
+
+        for name in &removed {
+            report(render::prune_scheme(name, &self.config, self.dry_run));
+        }
+
+        let mut affected = IndexSet::new();
+        if everything || !changes.templates.is_empty() {
+            self.reload_templates();
+
+            if let Some(templates) = &self.templates {
+                affected = templates.dependents(&changes.templates);
+            }
+        }
+
+        // aggregates still list removed schemes
+        if !removed.is_empty()
+            && let Some(templates) = &self.templates
+        {
+            affected.extend(
+                templates
+                    .directives
+                    .iter()
+                    .filter(|(_, directives)| directives.aggregate)
+                    .map(|(name, _)| name.clone()),
+            );
+        }
+
+        self.render(|scheme, template| {
+            everything
+                || changed_schemes.contains(scheme)
+                || affected.contains(template)
+        });
+    }
+
+    fn render(&self, include: impl Fn(&str, &str) -> bool) {
+        let Some(templates) = &self.templates else {
+            return;
+        };
+
+        report(render::selected(
+            templates,
+            &self.schemes,
+            &self.config,
+            self.write_mode,
+            self.dry_run,
+            |scheme, template| {
+                self.selection.includes(scheme, template)
+                    && include(scheme, template)
+            },
+        ));
+    }
+}
+
+/// Renders everything, then again on every change until interrupted.
+#[expect(clippy::infinite_loop, reason = "watches until interrupted")]
+pub(crate) fn run(
+    write_mode: WriteMode,
+    dry_run: bool,
+    selection: Selection,
+) -> Result<()> {
+    let mut project = Project {
+        config: config::load()?,
+        schemes: IndexMap::new(),
+        templates: None,
+        write_mode,
+        dry_run,
+        selection,
+    };
+
+    // taken first, so changes made while rendering aren't missed
+    let mut snapshot = Snapshot::take(&project.config);
+
+    project.reload();
+    project.render(|_, _| true);
+
+    info!("watching for changes");
+
+    loop {
+        thread::sleep(POLL_INTERVAL);
+
+        let mut current = Snapshot::take(&project.config);
+        if current == snapshot {
+            continue;
+        }
+
+        loop {
+            thread::sleep(DEBOUNCE);
+
+            let settled = Snapshot::take(&project.config);
+            if settled == current {
+                break;
+            }
+            current = settled;
+        }
+
+        let changes = Changes::classify(
+            current.changed_since(&snapshot),
+            &project.config,
+        );
+        snapshot = current;
+
+        project.update(&changes);
+
+        // the watched directories may have moved
+        if changes.config {
+            snapshot = Snapshot::take(&project.config);
+        }
+    }
+}
+
+fn load_scheme(path: &Path) -> Option<(String, Scheme)> {
+    let key = scheme_key(path)?;
+
+    report(schemes::load(&key, path)).map(|scheme| (key, scheme))
+}
+
+fn scheme_key(path: &Path) -> Option<String> {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .map(str::to_owned)
+}
+
+/// Logs `result`'s error instead of returning it, to keep watching.
+fn report<T>(result: Result<T>) -> Option<T> {
+    result.map_err(|err| error!("{err}")).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(files: &[(&str, u64)]) -> Snapshot {
+        Snapshot(
+            files
+                .iter()
+                .map(|&(path, secs)| {
+                    (
+                        PathBuf::from(path),
+                        SystemTime::UNIX_EPOCH + Duration::from_secs(secs),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn changes_are_classified_by_location() {
+        let config = Config::default();
+        let templates = Path::new(&config.dirs.templates);
+        let schemes = Path::new(&config.dirs.schemes);
+
+        let before = snapshot(&[("a", 1), ("b", 1)]);
+        let after = snapshot(&[("a", 2), ("c", 1)]);
+        assert_eq!(after.changed_since(&before).collect::<Vec<_>>(), [
+            Path::new("a"),
+            Path::new("c"),
+            Path::new("b")
+        ]);
+
+        let partial = templates.join("_macros.md.jinja");
+        let scheme = schemes.join("cutiepro.toml");
+        let changes = Changes::classify(
+            [
+                partial.as_path(),
+                scheme.as_path(),
+                Path::new(config::FILENAME),
+            ],
+            &config,
+        );
+
+        assert!(changes.config);
+        assert_eq!(
+            changes.templates,
+            IndexSet::from(["_macros.md.jinja".to_owned()])
+        );
+        assert_eq!(changes.schemes, IndexSet::from([scheme]));
+    }
+}